@@ -1,7 +1,9 @@
-use egg::{define_language, Analysis, DidMerge, Id};
+use egg::{define_language, merge_option, Analysis, DidMerge, Id, Language};
 
 use fxhash::FxHashSet as HashSet;
 
+use crate::util::decompose_using_graph;
+
 pub type MarId = egg::Id;
 pub type MarVar = egg::Var;
 pub type MarGraph = egg::EGraph<Marlang, MarAnalysis>;
@@ -24,6 +26,7 @@ define_language! {
         "marlang.operator.int.>=" = IntGe([MarId; 1]),
         "marlang.operator.int.<" = IntLt([MarId; 1]),
         "marlang.operator.int.<=" = IntLe([MarId; 1]),
+        "marlang.operator.int.div" = IntDiv([MarId; 1]),
 
         "marlang.operator.real.+" = RealAdd([MarId; 1]),
         "marlang.operator.real.-" = RealSub([MarId; 1]),
@@ -35,6 +38,18 @@ define_language! {
         "marlang.operator.real.<=" = RealLe([MarId; 1]),
 
         "marlang.operator.str.++" = Concat([MarId; 1]),
+        "marlang.operator.str.<" = StrLt([MarId; 1]),
+        "marlang.operator.str.<=" = StrLe([MarId; 1]),
+
+        "marlang.operator.re.++" = ReConcat([MarId; 1]),
+        "marlang.operator.re.union" = ReUnion([MarId; 1]),
+        "marlang.operator.re.inter" = ReInter([MarId; 1]),
+
+        "marlang.operator.bv.bvadd" = BvAdd([MarId; 1]),
+        "marlang.operator.bv.bvmul" = BvMul([MarId; 1]),
+        "marlang.operator.bv.bvand" = BvAnd([MarId; 1]),
+        "marlang.operator.bv.bvor" = BvOr([MarId; 1]),
+        "marlang.operator.bv.bvxor" = BvXor([MarId; 1]),
 
         "marlang.operator.core.and" = And([MarId; 1]),
         "marlang.operator.core.or" = Or([MarId; 1]),
@@ -43,17 +58,199 @@ define_language! {
         // takes a list of lists (bindings) and a body
         "marlang.operator.core.let" = Let([MarId; 2]),
         "marlang.operator.core.=" = Eq([MarId; 1]),
+        "marlang.operator.core.distinct" = Distinct([MarId; 1]),
         // END N-ARY
 
         "marlang.operator.core.not" = Not([MarId; 1]),
         "marlang.operator.core.=>" = Implies([MarId; 2]),
         "marlang.operator.core.ite" = Ite([MarId; 3]),
+        // take a list of sorted variables, like the parameters of define-fun, and then a body
+        "marlang.operator.core.forall" = Forall([MarId; 2]),
+        "marlang.operator.core.exists" = Exists([MarId; 2]),
+        // takes a term and then a list of attributes
+        "marlang.operator.core.!" = Annotate([MarId; 2]),
+
+        "marlang.attribute.pattern" = PatternAttr([MarId; 1]),
+        "marlang.attribute.named" = NamedAttr([MarId; 1]),
+
+        // linear temporal logic over the states of a transition system
+        "marlang.operator.ltl.G" = Globally([MarId; 1]),
+        "marlang.operator.ltl.F" = Finally([MarId; 1]),
+        "marlang.operator.ltl.X" = Next([MarId; 1]),
+        "marlang.operator.ltl.U" = Until([MarId; 2]),
+        "marlang.operator.ltl.R" = Release([MarId; 2]),
+
+        "marlang.operator.int.mod" = IntMod([MarId; 2]),
+        "marlang.operator.int.abs" = IntAbs([MarId; 1]),
+        "marlang.operator.int.neg" = IntNeg([MarId; 1]),
+        "marlang.operator.real.neg" = RealNeg([MarId; 1]),
+        "marlang.operator.int.to_real" = ToReal([MarId; 1]),
+        "marlang.operator.real.to_int" = ToInt([MarId; 1]),
+        "marlang.operator.real.is_int" = IsInt([MarId; 1]),
+
+        "marlang.operator.bv.bvnot" = BvNot([MarId; 1]),
+        "marlang.operator.bv.bvneg" = BvNeg([MarId; 1]),
+        "marlang.operator.bv.bvsub" = BvSub([MarId; 2]),
+        "marlang.operator.bv.bvudiv" = BvUdiv([MarId; 2]),
+        "marlang.operator.bv.bvurem" = BvUrem([MarId; 2]),
+        "marlang.operator.bv.bvsdiv" = BvSdiv([MarId; 2]),
+        "marlang.operator.bv.bvsrem" = BvSrem([MarId; 2]),
+        "marlang.operator.bv.bvsmod" = BvSmod([MarId; 2]),
+        "marlang.operator.bv.bvnand" = BvNand([MarId; 2]),
+        "marlang.operator.bv.bvnor" = BvNor([MarId; 2]),
+        "marlang.operator.bv.bvxnor" = BvXnor([MarId; 2]),
+        "marlang.operator.bv.bvshl" = BvShl([MarId; 2]),
+        "marlang.operator.bv.bvlshr" = BvLshr([MarId; 2]),
+        "marlang.operator.bv.bvashr" = BvAshr([MarId; 2]),
+        "marlang.operator.bv.bvcomp" = BvComp([MarId; 2]),
+        "marlang.operator.bv.bvult" = BvUlt([MarId; 2]),
+        "marlang.operator.bv.bvule" = BvUle([MarId; 2]),
+        "marlang.operator.bv.bvugt" = BvUgt([MarId; 2]),
+        "marlang.operator.bv.bvuge" = BvUge([MarId; 2]),
+        "marlang.operator.bv.bvslt" = BvSlt([MarId; 2]),
+        "marlang.operator.bv.bvsle" = BvSle([MarId; 2]),
+        "marlang.operator.bv.bvsgt" = BvSgt([MarId; 2]),
+        "marlang.operator.bv.bvsge" = BvSge([MarId; 2]),
+        "marlang.operator.bv.concat" = BvConcat([MarId; 2]),
+        // the indices come first and are symbols, like the value of an int
+        "marlang.operator.bv.extract" = BvExtract([MarId; 3]),
+        "marlang.operator.bv.zero_extend" = BvZeroExtend([MarId; 2]),
+        "marlang.operator.bv.sign_extend" = BvSignExtend([MarId; 2]),
+        "marlang.operator.bv.repeat" = BvRepeat([MarId; 2]),
+        "marlang.operator.bv.rotate_left" = BvRotateLeft([MarId; 2]),
+        "marlang.operator.bv.rotate_right" = BvRotateRight([MarId; 2]),
+
+        "marlang.operator.str.len" = StrLen([MarId; 1]),
+        "marlang.operator.str.at" = StrAt([MarId; 2]),
+        "marlang.operator.str.substr" = StrSubstr([MarId; 3]),
+        "marlang.operator.str.contains" = StrContains([MarId; 2]),
+        "marlang.operator.str.indexof" = StrIndexOf([MarId; 3]),
+        "marlang.operator.str.replace" = StrReplace([MarId; 3]),
+        "marlang.operator.str.replace_all" = StrReplaceAll([MarId; 3]),
+        "marlang.operator.str.prefixof" = StrPrefixOf([MarId; 2]),
+        "marlang.operator.str.suffixof" = StrSuffixOf([MarId; 2]),
+        "marlang.operator.str.is_digit" = StrIsDigit([MarId; 1]),
+        "marlang.operator.str.to_code" = StrToCode([MarId; 1]),
+        "marlang.operator.str.from_code" = StrFromCode([MarId; 1]),
+        "marlang.operator.str.to_int" = StrToInt([MarId; 1]),
+        "marlang.operator.str.from_int" = StrFromInt([MarId; 1]),
+        "marlang.operator.str.in_re" = StrInRe([MarId; 2]),
+        "marlang.operator.str.to_re" = StrToRe([MarId; 1]),
+
+        "marlang.operator.re.none" = ReNone,
+        "marlang.operator.re.all" = ReAll,
+        "marlang.operator.re.allchar" = ReAllChar,
+        "marlang.operator.re.*" = ReStar([MarId; 1]),
+        "marlang.operator.re.+" = RePlus([MarId; 1]),
+        "marlang.operator.re.opt" = ReOpt([MarId; 1]),
+        "marlang.operator.re.comp" = ReComp([MarId; 1]),
+        "marlang.operator.re.diff" = ReDiff([MarId; 2]),
+        "marlang.operator.re.range" = ReRange([MarId; 2]),
+        // the bounds come first and are symbols, like the indices of extract
+        "marlang.operator.re.loop" = ReLoop([MarId; 3]),
+        "marlang.operator.re.^" = RePower([MarId; 2]),
+
+        // the sign, exponent and significand as bit-vector literals
+        "marlang.operator.fp" = FpLit([MarId; 3]),
+        "marlang.operator.fp.abs" = FpAbs([MarId; 1]),
+        "marlang.operator.fp.neg" = FpNeg([MarId; 1]),
+        // the rounding mode comes first, like in SMT-LIB
+        "marlang.operator.fp.add" = FpAdd([MarId; 3]),
+        "marlang.operator.fp.sub" = FpSub([MarId; 3]),
+        "marlang.operator.fp.mul" = FpMul([MarId; 3]),
+        "marlang.operator.fp.div" = FpDiv([MarId; 3]),
+        "marlang.operator.fp.fma" = FpFma([MarId; 4]),
+        "marlang.operator.fp.sqrt" = FpSqrt([MarId; 2]),
+        "marlang.operator.fp.rem" = FpRem([MarId; 2]),
+        "marlang.operator.fp.roundToIntegral" = FpRoundToIntegral([MarId; 2]),
+        "marlang.operator.fp.min" = FpMin([MarId; 2]),
+        "marlang.operator.fp.max" = FpMax([MarId; 2]),
+        "marlang.operator.fp.leq" = FpLeq([MarId; 2]),
+        "marlang.operator.fp.lt" = FpLt([MarId; 2]),
+        "marlang.operator.fp.geq" = FpGeq([MarId; 2]),
+        "marlang.operator.fp.gt" = FpGt([MarId; 2]),
+        "marlang.operator.fp.eq" = FpEq([MarId; 2]),
+        "marlang.operator.fp.isNormal" = FpIsNormal([MarId; 1]),
+        "marlang.operator.fp.isSubnormal" = FpIsSubnormal([MarId; 1]),
+        "marlang.operator.fp.isZero" = FpIsZero([MarId; 1]),
+        "marlang.operator.fp.isInfinite" = FpIsInfinite([MarId; 1]),
+        "marlang.operator.fp.isNaN" = FpIsNaN([MarId; 1]),
+        "marlang.operator.fp.isNegative" = FpIsNegative([MarId; 1]),
+        "marlang.operator.fp.isPositive" = FpIsPositive([MarId; 1]),
+        // the exponent and significand widths come first and are symbols, then the rounding mode
+        "marlang.operator.fp.to_fp.real" = FpFromReal([MarId; 4]),
+        "marlang.operator.fp.to_fp.bv" = FpFromBv([MarId; 3]),
+        "marlang.operator.fp.to_fp.sbv" = FpFromSbv([MarId; 4]),
+        "marlang.operator.fp.to_fp.fp" = FpFromFp([MarId; 4]),
+        "marlang.operator.fp.to_fp_unsigned" = FpFromUbv([MarId; 4]),
+        // the width of the result comes first and is a symbol, then the rounding mode
+        "marlang.operator.fp.to_ubv" = FpToUbv([MarId; 3]),
+        "marlang.operator.fp.to_sbv" = FpToSbv([MarId; 3]),
+        "marlang.operator.fp.to_real" = FpToReal([MarId; 1]),
+
+        "marlang.operator.array.select" = Select([MarId; 2]),
+        "marlang.operator.array.store" = Store([MarId; 3]),
+        // takes the sort of the array and then the value at every index
+        "marlang.operator.array.const" = ConstArray([MarId; 2]),
+
+        "marlang.datatype" = Datatype([MarId; 2]), // name and then constructors
+        "marlang.datatype.constructor" = Constructor([MarId; 3]), // datatype, name, selectors
+        "marlang.datatype.selector" = Selector([MarId; 3]), // datatype, name, sort
+        "marlang.datatype.tester" = Tester([MarId; 1]), // point to constructor
 
         "marlang.command.set-logic" = SetLogic([MarId; 1]),
         "marlang.command.check-sat" = CheckSat,
         "marlang.command.assert" = Assert([MarId; 1]),
         "marlang.command.declare-fun" = DeclareFun([MarId; 3]),
         "marlang.command.define-fun" = DefineFun([MarId; 4]),
+        // a recursive function by its name, parameter sorts and sort, which calls can point to
+        // before its body is built
+        "marlang.function.rec" = RecFun([MarId; 3]),
+        // the function, the sorted parameters and then the body
+        "marlang.command.define-fun-rec" = DefineFunRec([MarId; 3]),
+        // a list of define-fun-rec definitions that can call each other
+        "marlang.command.define-funs-rec" = DefineFunsRec([MarId; 1]),
+        "marlang.command.declare-datatype" = DeclareDatatype([MarId; 1]),
+        "marlang.command.declare-datatypes" = DeclareDatatypes([MarId; 1]),
+        // the name and then the arity, kept as a symbol
+        "marlang.command.declare-sort" = DeclareSort([MarId; 2]),
+        // the name, the parameter symbols and then the aliased sort
+        "marlang.command.define-sort" = DefineSort([MarId; 3]),
+        // the number of scopes is a symbol, like the value of an int
+        "marlang.command.push" = Push([MarId; 1]),
+        "marlang.command.pop" = Pop([MarId; 1]),
+        "marlang.command.reset-assertions" = ResetAssertions,
+        "marlang.command.check-sat-assuming" = CheckSatAssuming([MarId; 1]),
+        "marlang.command.get-model" = GetModel,
+        "marlang.command.get-unsat-core" = GetUnsatCore,
+        "marlang.command.get-value" = GetValue([MarId; 1]),
+        // the keyword and then the value, both kept as symbols
+        "marlang.command.set-option" = SetOption([MarId; 2]),
+        "marlang.command.set-info" = SetInfo([MarId; 2]),
+        "marlang.command.exit" = Exit,
+
+        // SyGuS: the name, the sorted parameters, the sort and then the grammar, which is a list
+        // of nonterminals with the start symbol first, or empty when any term of the sort will do
+        "marlang.command.synth-fun" = SynthFun([MarId; 4]),
+        "marlang.command.declare-var" = DeclareVar([MarId; 2]),
+        "marlang.command.constraint" = Constraint([MarId; 1]),
+        "marlang.command.check-synth" = CheckSynth,
+        // the name, the sort and then the rules, which refer to nonterminals by their symbol
+        "marlang.sygus.nonterminal" = NonTerminal([MarId; 3]),
+        "marlang.sygus.constant" = GrammarConstant([MarId; 1]),
+        "marlang.sygus.variable" = GrammarVariable([MarId; 1]),
+
+        // constrained Horn clauses: a relation has a name and its argument sorts, a rule points
+        // to a (universally quantified) implication whose head is an application or false, and
+        // a query points to an (existentially quantified) body
+        "marlang.command.declare-rel" = DeclareRel([MarId; 2]),
+        "marlang.command.rule" = Rule([MarId; 1]),
+        "marlang.command.query" = Query([MarId; 1]),
+
+        // optimization modulo theories, in the dialect of z3 and OptiMathSAT
+        "marlang.command.minimize" = Minimize([MarId; 1]),
+        "marlang.command.maximize" = Maximize([MarId; 1]),
+        "marlang.command.get-objectives" = GetObjectives,
 
         "marlang.meta.cons" = Cons([MarId; 2]),
         "marlang.meta.nil" = Nil,
@@ -62,22 +259,57 @@ define_language! {
         "marlang.sort.int" = IntSort,
         "marlang.sort.real" = RealSort,
         "marlang.sort.string" = StringSort,
+        "marlang.sort.reglan" = RegLanSort,
+        "marlang.sort.bitvec" = BitVecSort([MarId; 1]),
+        "marlang.sort.array" = ArraySort([MarId; 2]),
+        "marlang.sort.datatype" = DatatypeSort([MarId; 1]),
+        // the exponent and significand widths
+        "marlang.sort.fp" = FpSort([MarId; 2]),
+        "marlang.sort.roundingmode" = RoundingModeSort,
+        // points to a declare-sort or define-sort and then the argument sorts
+        "marlang.sort.apply" = SortApply([MarId; 2]),
 
         "marlang.value.bool" = BoolVal([MarId; 1]),
         "marlang.value.int" = IntVal([MarId; 1]),
         "marlang.value.real" = RealVal([MarId; 1]),
         "marlang.value.string" = StringVal([MarId; 1]),
+        // the value (in decimal) and then the width
+        "marlang.value.bv" = BvVal([MarId; 2]),
+        // one of RNE, RNA, RTP, RTN and RTZ
+        "marlang.value.rm" = RmVal([MarId; 1]),
+        // the exponent and significand widths
+        "marlang.value.fp.+zero" = FpPosZero([MarId; 2]),
+        "marlang.value.fp.-zero" = FpNegZero([MarId; 2]),
+        "marlang.value.fp.+oo" = FpPosInf([MarId; 2]),
+        "marlang.value.fp.-oo" = FpNegInf([MarId; 2]),
+        "marlang.value.fp.NaN" = FpNaN([MarId; 2]),
 
         Symbol(String),
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MarSort {
+    Bool,
+    Int,
+    Real,
+    String,
+    RegLan,
+    BitVec(u32),
+    FloatingPoint(u32, u32),
+    RoundingMode,
+    Array(Box<MarSort>, Box<MarSort>),
+    Datatype(String),
+    Uninterpreted(String, Vec<MarSort>),
+}
+
 #[derive(Default)]
 pub struct MarAnalysis;
 
 #[derive(Debug)]
 pub struct MarData {
-    free: HashSet<Id>,
+    pub free: HashSet<String>,
+    pub sort: Option<MarSort>,
 }
 
 impl Analysis<Marlang> for MarAnalysis {
@@ -85,14 +317,386 @@ impl Analysis<Marlang> for MarAnalysis {
     fn merge(&mut self, to: &mut MarData, from: MarData) -> DidMerge {
         let before_len = to.free.len();
         to.free.retain(|i| from.free.contains(i));
-        DidMerge(
+        let free = DidMerge(
             before_len != to.free.len(),
             to.free.len() != from.free.len(),
-        )
+        );
+        free | merge_option(&mut to.sort, from.sort, |_, _| DidMerge(false, false))
     }
 
-    fn make(_egraph: &MarGraph, _enode: &Marlang) -> MarData {
-        let free = HashSet::default();
-        MarData { free }
+    fn make(egraph: &MarGraph, enode: &Marlang) -> MarData {
+        let free = make_free(egraph, enode);
+        let sort = make_sort(egraph, enode);
+        MarData { free, sort }
     }
 }
+
+// Bound variables are referred to by their symbol, so the free variables of a term are the
+// symbols in term position that no enclosing let, quantifier or definition binds.
+fn make_free(egraph: &MarGraph, enode: &Marlang) -> HashSet<String> {
+    let free = |id: &Id| egraph[*id].data.free.clone();
+    match enode {
+        Marlang::Symbol(s) => [s.clone()].into_iter().collect(),
+        Marlang::Call([_, args]) => free(args),
+        Marlang::Let([bindings, body]) => {
+            let bindings: Vec<(Option<String>, HashSet<String>)> =
+                decompose_using_graph(egraph, *bindings)
+                    .into_iter()
+                    .map(|b| match decompose_using_graph(egraph, b)[..] {
+                        [name, value] => (symbol(egraph, name), free(&value)),
+                        _ => (None, HashSet::default()),
+                    })
+                    .collect();
+            let mut out = free(body);
+            for (name, _) in &bindings {
+                if let Some(name) = name {
+                    out.remove(name);
+                }
+            }
+            for (_, value) in bindings {
+                out.extend(value);
+            }
+            out
+        }
+        Marlang::Forall([vars, body])
+        | Marlang::Exists([vars, body])
+        | Marlang::DefineFun([_, vars, _, body])
+        | Marlang::DefineFunRec([_, vars, body]) => {
+            let mut out = free(body);
+            for name in bound_names(egraph, *vars) {
+                out.remove(&name);
+            }
+            out
+        }
+        Marlang::BvExtract([_, _, x])
+        | Marlang::BvZeroExtend([_, x])
+        | Marlang::BvSignExtend([_, x])
+        | Marlang::BvRepeat([_, x])
+        | Marlang::BvRotateLeft([_, x])
+        | Marlang::BvRotateRight([_, x])
+        | Marlang::ConstArray([_, x])
+        | Marlang::ReLoop([_, _, x])
+        | Marlang::RePower([_, x])
+        | Marlang::FpFromBv([_, _, x]) => free(x),
+        Marlang::FpFromReal([_, _, rm, x])
+        | Marlang::FpFromSbv([_, _, rm, x])
+        | Marlang::FpFromFp([_, _, rm, x])
+        | Marlang::FpFromUbv([_, _, rm, x])
+        | Marlang::FpToUbv([_, rm, x])
+        | Marlang::FpToSbv([_, rm, x]) => free(rm).union(&free(x)).cloned().collect(),
+        Marlang::SetLogic(_)
+        | Marlang::Push(_)
+        | Marlang::Pop(_)
+        | Marlang::SetOption(_)
+        | Marlang::SetInfo(_)
+        | Marlang::DeclareFun(_)
+        | Marlang::DeclareDatatype(_)
+        | Marlang::DeclareDatatypes(_)
+        | Marlang::DeclareSort(_)
+        | Marlang::DefineSort(_)
+        | Marlang::SortApply(_)
+        | Marlang::SynthFun(_)
+        | Marlang::DeclareVar(_)
+        | Marlang::DeclareRel(_)
+        | Marlang::RecFun(_)
+        | Marlang::NonTerminal(_)
+        | Marlang::GrammarConstant(_)
+        | Marlang::GrammarVariable(_)
+        | Marlang::NamedAttr(_)
+        | Marlang::Datatype(_)
+        | Marlang::Constructor(_)
+        | Marlang::Selector(_)
+        | Marlang::Tester(_)
+        | Marlang::BoolVal(_)
+        | Marlang::IntVal(_)
+        | Marlang::RealVal(_)
+        | Marlang::StringVal(_)
+        | Marlang::BvVal(_)
+        | Marlang::RmVal(_)
+        | Marlang::FpPosZero(_)
+        | Marlang::FpNegZero(_)
+        | Marlang::FpPosInf(_)
+        | Marlang::FpNegInf(_)
+        | Marlang::FpNaN(_)
+        | Marlang::FpSort(_)
+        | Marlang::BitVecSort(_)
+        | Marlang::ArraySort(_)
+        | Marlang::DatatypeSort(_) => HashSet::default(),
+        node => node.children().iter().flat_map(free).collect(),
+    }
+}
+
+// The names bound by a list of sorted variables or let bindings
+pub fn bound_names(egraph: &MarGraph, vars: Id) -> Vec<String> {
+    decompose_using_graph(egraph, vars)
+        .into_iter()
+        .filter_map(|v| symbol(egraph, *decompose_using_graph(egraph, v).first()?))
+        .collect()
+}
+
+fn make_sort(egraph: &MarGraph, enode: &Marlang) -> Option<MarSort> {
+    let sort = |id: &Id| egraph[*id].data.sort.clone();
+    let width = |id: &Id| match egraph[*id].data.sort {
+        Some(MarSort::BitVec(w)) => Some(w),
+        _ => None,
+    };
+    match enode {
+        Marlang::BoolSort => Some(MarSort::Bool),
+        Marlang::IntSort => Some(MarSort::Int),
+        Marlang::RealSort => Some(MarSort::Real),
+        Marlang::StringSort => Some(MarSort::String),
+        Marlang::RegLanSort => Some(MarSort::RegLan),
+        Marlang::BitVecSort([w]) => Some(MarSort::BitVec(numeral(egraph, *w)?)),
+        Marlang::ArraySort([i, e]) => Some(MarSort::Array(Box::new(sort(i)?), Box::new(sort(e)?))),
+        Marlang::DatatypeSort([n]) => Some(MarSort::Datatype(symbol(egraph, *n)?)),
+        Marlang::SortApply([def, args]) => {
+            let args = decompose_using_graph(egraph, *args)
+                .into_iter()
+                .map(|a| sort(&a))
+                .collect::<Option<Vec<_>>>()?;
+            apply_sort(egraph, *def, args)
+        }
+        Marlang::FpSort([e, s])
+        | Marlang::FpPosZero([e, s])
+        | Marlang::FpNegZero([e, s])
+        | Marlang::FpPosInf([e, s])
+        | Marlang::FpNegInf([e, s])
+        | Marlang::FpNaN([e, s])
+        | Marlang::FpFromReal([e, s, _, _])
+        | Marlang::FpFromBv([e, s, _])
+        | Marlang::FpFromSbv([e, s, _, _])
+        | Marlang::FpFromFp([e, s, _, _])
+        | Marlang::FpFromUbv([e, s, _, _]) => Some(MarSort::FloatingPoint(
+            numeral(egraph, *e)?,
+            numeral(egraph, *s)?,
+        )),
+        Marlang::RoundingModeSort | Marlang::RmVal(_) => Some(MarSort::RoundingMode),
+        Marlang::FpLit([_, e, s]) => Some(MarSort::FloatingPoint(width(e)?, width(s)? + 1)),
+        Marlang::FpAbs([x])
+        | Marlang::FpNeg([x])
+        | Marlang::FpAdd([_, x, _])
+        | Marlang::FpSub([_, x, _])
+        | Marlang::FpMul([_, x, _])
+        | Marlang::FpDiv([_, x, _])
+        | Marlang::FpFma([_, x, _, _])
+        | Marlang::FpSqrt([_, x])
+        | Marlang::FpRem([x, _])
+        | Marlang::FpRoundToIntegral([_, x])
+        | Marlang::FpMin([x, _])
+        | Marlang::FpMax([x, _]) => sort(x),
+        Marlang::FpToUbv([m, _, _]) | Marlang::FpToSbv([m, _, _]) => {
+            Some(MarSort::BitVec(numeral(egraph, *m)?))
+        }
+        Marlang::FpToReal(_) => Some(MarSort::Real),
+
+        Marlang::DeclareFun([_, _, s])
+        | Marlang::DefineFun([_, _, s, _])
+        | Marlang::RecFun([_, _, s])
+        | Marlang::SynthFun([_, _, s, _])
+        | Marlang::DeclareVar([_, s])
+        | Marlang::GrammarConstant([s])
+        | Marlang::GrammarVariable([s]) => sort(s),
+        Marlang::Call([def, _]) => sort(def),
+        Marlang::Constructor([d, _, _]) => Some(MarSort::Datatype(symbol(egraph, *d)?)),
+        Marlang::Selector([_, _, s]) => sort(s),
+        Marlang::Tester(_) => Some(MarSort::Bool),
+        Marlang::Ite([_, x, _]) => sort(x),
+        Marlang::Let([_, body]) => sort(body),
+        Marlang::Forall(_) | Marlang::Exists(_) | Marlang::DeclareRel(_) => Some(MarSort::Bool),
+        Marlang::Globally(_)
+        | Marlang::Finally(_)
+        | Marlang::Next(_)
+        | Marlang::Until(_)
+        | Marlang::Release(_) => Some(MarSort::Bool),
+        Marlang::Annotate([x, _]) => sort(x),
+
+        Marlang::Select([a, _]) => match sort(a)? {
+            MarSort::Array(_, e) => Some(*e),
+            _ => None,
+        },
+        Marlang::Store([a, _, _]) => sort(a),
+        Marlang::ConstArray([s, _]) => sort(s),
+
+        Marlang::BoolVal(_) => Some(MarSort::Bool),
+        Marlang::IntVal(_) => Some(MarSort::Int),
+        Marlang::RealVal(_) => Some(MarSort::Real),
+        Marlang::StringVal(_) => Some(MarSort::String),
+        Marlang::BvVal([_, w]) => Some(MarSort::BitVec(numeral(egraph, *w)?)),
+
+        Marlang::IntAdd(_)
+        | Marlang::IntSub(_)
+        | Marlang::IntMul(_)
+        | Marlang::IntDiv(_)
+        | Marlang::IntMod(_)
+        | Marlang::IntAbs(_)
+        | Marlang::IntNeg(_)
+        | Marlang::ToInt(_) => Some(MarSort::Int),
+        Marlang::RealAdd(_)
+        | Marlang::RealSub(_)
+        | Marlang::RealMul(_)
+        | Marlang::RealDiv(_)
+        | Marlang::RealNeg(_)
+        | Marlang::ToReal(_) => Some(MarSort::Real),
+        Marlang::Concat(_)
+        | Marlang::StrAt(_)
+        | Marlang::StrSubstr(_)
+        | Marlang::StrReplace(_)
+        | Marlang::StrReplaceAll(_)
+        | Marlang::StrFromCode(_)
+        | Marlang::StrFromInt(_) => Some(MarSort::String),
+        Marlang::StrLen(_)
+        | Marlang::StrIndexOf(_)
+        | Marlang::StrToCode(_)
+        | Marlang::StrToInt(_) => Some(MarSort::Int),
+        Marlang::StrToRe(_)
+        | Marlang::ReConcat(_)
+        | Marlang::ReUnion(_)
+        | Marlang::ReInter(_)
+        | Marlang::ReNone
+        | Marlang::ReAll
+        | Marlang::ReAllChar
+        | Marlang::ReStar(_)
+        | Marlang::RePlus(_)
+        | Marlang::ReOpt(_)
+        | Marlang::ReComp(_)
+        | Marlang::ReDiff(_)
+        | Marlang::ReRange(_)
+        | Marlang::ReLoop(_)
+        | Marlang::RePower(_) => Some(MarSort::RegLan),
+
+        Marlang::IntGt(_)
+        | Marlang::IntGe(_)
+        | Marlang::IntLt(_)
+        | Marlang::IntLe(_)
+        | Marlang::RealGt(_)
+        | Marlang::RealGe(_)
+        | Marlang::RealLt(_)
+        | Marlang::RealLe(_)
+        | Marlang::And(_)
+        | Marlang::Or(_)
+        | Marlang::Xor(_)
+        | Marlang::Eq(_)
+        | Marlang::Distinct(_)
+        | Marlang::IsInt(_)
+        | Marlang::Not(_)
+        | Marlang::Implies(_)
+        | Marlang::StrLt(_)
+        | Marlang::StrLe(_)
+        | Marlang::StrContains(_)
+        | Marlang::StrPrefixOf(_)
+        | Marlang::StrSuffixOf(_)
+        | Marlang::StrIsDigit(_)
+        | Marlang::StrInRe(_)
+        | Marlang::BvUlt(_)
+        | Marlang::BvUle(_)
+        | Marlang::BvUgt(_)
+        | Marlang::BvUge(_)
+        | Marlang::BvSlt(_)
+        | Marlang::BvSle(_)
+        | Marlang::BvSgt(_)
+        | Marlang::BvSge(_)
+        | Marlang::FpLeq(_)
+        | Marlang::FpLt(_)
+        | Marlang::FpGeq(_)
+        | Marlang::FpGt(_)
+        | Marlang::FpEq(_)
+        | Marlang::FpIsNormal(_)
+        | Marlang::FpIsSubnormal(_)
+        | Marlang::FpIsZero(_)
+        | Marlang::FpIsInfinite(_)
+        | Marlang::FpIsNaN(_)
+        | Marlang::FpIsNegative(_)
+        | Marlang::FpIsPositive(_) => Some(MarSort::Bool),
+
+        Marlang::BvAdd([args])
+        | Marlang::BvMul([args])
+        | Marlang::BvAnd([args])
+        | Marlang::BvOr([args])
+        | Marlang::BvXor([args]) => egraph[*args].iter().find_map(|node| match node {
+            Marlang::Cons([x, _]) => sort(x),
+            _ => None,
+        }),
+        Marlang::BvNot([x])
+        | Marlang::BvNeg([x])
+        | Marlang::BvSub([x, _])
+        | Marlang::BvUdiv([x, _])
+        | Marlang::BvUrem([x, _])
+        | Marlang::BvSdiv([x, _])
+        | Marlang::BvSrem([x, _])
+        | Marlang::BvSmod([x, _])
+        | Marlang::BvNand([x, _])
+        | Marlang::BvNor([x, _])
+        | Marlang::BvXnor([x, _])
+        | Marlang::BvShl([x, _])
+        | Marlang::BvLshr([x, _])
+        | Marlang::BvAshr([x, _])
+        | Marlang::BvRotateLeft([_, x])
+        | Marlang::BvRotateRight([_, x]) => sort(x),
+        Marlang::BvComp(_) => Some(MarSort::BitVec(1)),
+        // the widths come from numerals, so they are left unknown when they overflow
+        Marlang::BvConcat([x, y]) => width(x)?.checked_add(width(y)?).map(MarSort::BitVec),
+        Marlang::BvExtract([i, j, _]) => numeral(egraph, *i)?
+            .checked_sub(numeral(egraph, *j)?)?
+            .checked_add(1)
+            .map(MarSort::BitVec),
+        Marlang::BvZeroExtend([i, x]) | Marlang::BvSignExtend([i, x]) => width(x)?
+            .checked_add(numeral(egraph, *i)?)
+            .map(MarSort::BitVec),
+        Marlang::BvRepeat([i, x]) => width(x)?
+            .checked_mul(numeral(egraph, *i)?)
+            .filter(|w| *w > 0)
+            .map(MarSort::BitVec),
+
+        _ => None,
+    }
+}
+
+// Declared sorts stay opaque while aliases are expanded with their parameters replaced
+fn apply_sort(egraph: &MarGraph, def: Id, args: Vec<MarSort>) -> Option<MarSort> {
+    egraph[def].iter().find_map(|node| match node {
+        Marlang::DeclareSort([n, _]) => {
+            Some(MarSort::Uninterpreted(symbol(egraph, *n)?, args.clone()))
+        }
+        Marlang::DefineSort([_, params, body]) => {
+            let params: Vec<(String, MarSort)> = decompose_using_graph(egraph, *params)
+                .into_iter()
+                .filter_map(|p| symbol(egraph, p))
+                .zip(args.iter().cloned())
+                .collect();
+            instantiate(egraph, *body, &params)
+        }
+        _ => None,
+    })
+}
+
+fn instantiate(egraph: &MarGraph, id: Id, params: &[(String, MarSort)]) -> Option<MarSort> {
+    if let Some(s) = &egraph[id].data.sort {
+        return Some(s.clone());
+    }
+    egraph[id].iter().find_map(|node| match node {
+        Marlang::Symbol(p) => params.iter().find(|(q, _)| q == p).map(|(_, s)| s.clone()),
+        Marlang::ArraySort([i, e]) => Some(MarSort::Array(
+            Box::new(instantiate(egraph, *i, params)?),
+            Box::new(instantiate(egraph, *e, params)?),
+        )),
+        Marlang::SortApply([def, args]) => {
+            let args = decompose_using_graph(egraph, *args)
+                .into_iter()
+                .map(|a| instantiate(egraph, a, params))
+                .collect::<Option<Vec<_>>>()?;
+            apply_sort(egraph, *def, args)
+        }
+        _ => None,
+    })
+}
+
+fn numeral(egraph: &MarGraph, id: Id) -> Option<u32> {
+    symbol(egraph, id)?.parse().ok()
+}
+
+pub fn symbol(egraph: &MarGraph, id: Id) -> Option<String> {
+    egraph[id].iter().find_map(|node| match node {
+        Marlang::Symbol(s) => Some(s.clone()),
+        _ => None,
+    })
+}
@@ -1,60 +1,78 @@
+use egg::Language;
 use fxhash::FxBuildHasher as BuildHasher;
 
 use std::fmt;
 
+use rug::Integer;
+
 use crate::ast::{
-    MarExplanation, MarGraph, MarId, MarPattern, MarRecExpr, MarRewrite, MarRunner, Marlang,
+    symbol, MarExplanation, MarGraph, MarId, MarPattern, MarRecExpr, MarRewrite, MarRunner,
+    MarSort, Marlang,
 };
+use crate::{float::RoundingMode, rewrites, smtlib, util::decompose_using_graph};
 
 type HashMap<K, V> = hashbrown::HashMap<K, V, BuildHasher>;
 
 pub struct MarContext {
     runner: MarRunner,
     commands: Vec<MarId>,
+    scopes: Vec<Vec<MarId>>,
     rewrites: Vec<MarRewrite>,
+    assume: bool,
+    assumed: Vec<(MarId, MarRecExpr)>,
+    mixed: bool,
 }
 
 impl MarContext {
     pub fn mk_call(&mut self, def: MarId, args: MarId) -> MarId {
+        self.check_call(def, args);
         self.add(Marlang::Call([def, args]))
     }
 
     pub fn mk_real_add(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealAdd([folded]))
     }
 
     pub fn mk_real_sub(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealSub([folded]))
     }
 
     pub fn mk_real_mul(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealMul([folded]))
     }
 
     pub fn mk_real_div(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealDiv([folded]))
     }
 
     pub fn mk_real_gt(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealGt([folded]))
     }
 
     pub fn mk_real_ge(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealGe([folded]))
     }
 
     pub fn mk_real_lt(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealLt([folded]))
     }
 
     pub fn mk_real_le(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_to_real(args);
         let folded = self.fold(args);
         self.add(Marlang::RealLe([folded]))
     }
@@ -94,16 +112,85 @@ impl MarContext {
         self.add(Marlang::IntLe([folded]))
     }
 
+    pub fn mk_int_div(&mut self, args: Vec<MarId>) -> MarId {
+        let folded = self.fold(args);
+        self.add(Marlang::IntDiv([folded]))
+    }
+
     pub fn mk_eq(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_mixed(args);
+        self.check_same_sort(&args);
         let folded = self.fold(args);
         self.add(Marlang::Eq([folded]))
     }
 
+    pub fn mk_distinct(&mut self, args: Vec<MarId>) -> MarId {
+        let args = self.coerce_mixed(args);
+        self.check_same_sort(&args);
+        let folded = self.fold(args);
+        self.add(Marlang::Distinct([folded]))
+    }
+
     pub fn mk_concat(&mut self, args: Vec<MarId>) -> MarId {
         let folded = self.fold(args);
         self.add(Marlang::Concat([folded]))
     }
 
+    pub fn mk_str_lt(&mut self, args: Vec<MarId>) -> MarId {
+        let folded = self.fold(args);
+        self.add(Marlang::StrLt([folded]))
+    }
+
+    pub fn mk_str_le(&mut self, args: Vec<MarId>) -> MarId {
+        let folded = self.fold(args);
+        self.add(Marlang::StrLe([folded]))
+    }
+
+    pub fn mk_re_concat(&mut self, args: Vec<MarId>) -> MarId {
+        let folded = self.fold(args);
+        self.add(Marlang::ReConcat([folded]))
+    }
+
+    pub fn mk_re_union(&mut self, args: Vec<MarId>) -> MarId {
+        let folded = self.fold(args);
+        self.add(Marlang::ReUnion([folded]))
+    }
+
+    pub fn mk_re_inter(&mut self, args: Vec<MarId>) -> MarId {
+        let folded = self.fold(args);
+        self.add(Marlang::ReInter([folded]))
+    }
+
+    pub fn mk_bv_add(&mut self, args: Vec<MarId>) -> MarId {
+        self.check_same_width(&args);
+        let folded = self.fold(args);
+        self.add(Marlang::BvAdd([folded]))
+    }
+
+    pub fn mk_bv_mul(&mut self, args: Vec<MarId>) -> MarId {
+        self.check_same_width(&args);
+        let folded = self.fold(args);
+        self.add(Marlang::BvMul([folded]))
+    }
+
+    pub fn mk_bv_and(&mut self, args: Vec<MarId>) -> MarId {
+        self.check_same_width(&args);
+        let folded = self.fold(args);
+        self.add(Marlang::BvAnd([folded]))
+    }
+
+    pub fn mk_bv_or(&mut self, args: Vec<MarId>) -> MarId {
+        self.check_same_width(&args);
+        let folded = self.fold(args);
+        self.add(Marlang::BvOr([folded]))
+    }
+
+    pub fn mk_bv_xor(&mut self, args: Vec<MarId>) -> MarId {
+        self.check_same_width(&args);
+        let folded = self.fold(args);
+        self.add(Marlang::BvXor([folded]))
+    }
+
     pub fn mk_and(&mut self, args: Vec<MarId>) -> MarId {
         let folded = self.fold(args);
         self.add(Marlang::And([folded]))
@@ -119,250 +206,1797 @@ impl MarContext {
         self.add(Marlang::Xor([folded]))
     }
 
-    pub fn mk_let(&mut self, bindings: Vec<(String, MarId)>, body: MarId) -> MarId {
-        let bindings: Vec<MarId> = bindings
-            .into_iter()
-            .map(|(name, value)| {
-                let name = self.mk_symbol(name);
-                self.fold(vec![name, value])
-            })
-            .collect();
-        let bindings = self.fold(bindings);
-        self.add(Marlang::Let([bindings, body]))
+    pub fn mk_int_mod(&mut self, x: MarId, y: MarId) -> MarId {
+        self.add(Marlang::IntMod([x, y]))
     }
 
-    pub fn mk_not(&mut self, arg: MarId) -> MarId {
-        self.add(Marlang::Not([arg]))
+    pub fn mk_int_abs(&mut self, x: MarId) -> MarId {
+        self.add(Marlang::IntAbs([x]))
     }
 
-    pub fn mk_implies(&mut self, x: MarId, y: MarId) -> MarId {
-        self.add(Marlang::Implies([x, y]))
+    pub fn mk_int_neg(&mut self, x: MarId) -> MarId {
+        self.add(Marlang::IntNeg([x]))
     }
 
-    pub fn mk_ite(&mut self, x: MarId, y: MarId, z: MarId) -> MarId {
-        self.add(Marlang::Ite([x, y, z]))
+    pub fn mk_real_neg(&mut self, x: MarId) -> MarId {
+        let x = self.coerce_to_real(vec![x])[0];
+        self.add(Marlang::RealNeg([x]))
     }
 
-    pub fn mk_set_logic(&mut self, logic: String) -> MarId {
-        let logic = self.mk_symbol(logic);
-        self.add(Marlang::SetLogic([logic]))
+    pub fn mk_to_real(&mut self, x: MarId) -> MarId {
+        self.add(Marlang::ToReal([x]))
     }
 
-    pub fn mk_check_sat(&mut self) -> MarId {
-        self.add(Marlang::CheckSat)
+    pub fn mk_to_int(&mut self, x: MarId) -> MarId {
+        let x = self.coerce_to_real(vec![x])[0];
+        self.add(Marlang::ToInt([x]))
     }
 
-    pub fn mk_assert(&mut self, expr: MarId) -> MarId {
-        self.add(Marlang::Assert([expr]))
+    pub fn mk_is_int(&mut self, x: MarId) -> MarId {
+        let x = self.coerce_to_real(vec![x])[0];
+        self.add(Marlang::IsInt([x]))
     }
 
-    pub fn mk_declare_const<T: ToString>(&mut self, name: T, sort: MarId) -> MarId {
-        let x = self.mk_symbol(name);
-        let empty = self.mk_nil();
-        self.add(Marlang::DeclareFun([x, empty, sort]))
+    pub fn mk_bv_not(&mut self, x: MarId) -> MarId {
+        self.check_same_width(&[x]);
+        self.add(Marlang::BvNot([x]))
     }
 
-    pub fn mk_declare_fun<T: ToString>(
-        &mut self,
-        name: T,
-        params: Vec<MarId>,
-        sort: MarId,
-    ) -> MarId {
-        let params = self.fold(params);
-        let f = self.mk_symbol(name);
-        self.add(Marlang::DeclareFun([f, params, sort]))
+    pub fn mk_bv_neg(&mut self, x: MarId) -> MarId {
+        self.check_same_width(&[x]);
+        self.add(Marlang::BvNeg([x]))
     }
 
-    pub fn mk_define_fun<T: ToString>(
-        &mut self,
-        name: T,
-        params: Vec<(T, MarId)>,
-        sort: MarId,
-        body: MarId,
-    ) -> MarId {
-        let params: Vec<MarId> = params
-            .into_iter()
-            .map(|(name, value)| {
-                let name = self.mk_symbol(name);
-                self.fold(vec![name, value])
-            })
-            .collect();
-        let params = self.fold(params);
-        let f = self.mk_symbol(name);
-        self.add(Marlang::DefineFun([f, params, sort, body]))
+    pub fn mk_bv_sub(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSub([x, y]))
     }
 
-    pub fn mk_bool_sort(&mut self) -> MarId {
-        self.add(Marlang::BoolSort)
+    pub fn mk_bv_udiv(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvUdiv([x, y]))
     }
 
-    pub fn mk_int_sort(&mut self) -> MarId {
-        self.add(Marlang::IntSort)
+    pub fn mk_bv_urem(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvUrem([x, y]))
     }
 
-    pub fn mk_real_sort(&mut self) -> MarId {
-        self.add(Marlang::RealSort)
+    pub fn mk_bv_sdiv(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSdiv([x, y]))
     }
 
-    pub fn mk_string_sort(&mut self) -> MarId {
-        self.add(Marlang::StringSort)
+    pub fn mk_bv_srem(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSrem([x, y]))
     }
 
-    pub fn mk_bool_val(&mut self, i: bool) -> MarId {
-        let i = self.mk_symbol(i.to_string());
-        self.add(Marlang::BoolVal([i]))
+    pub fn mk_bv_smod(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSmod([x, y]))
     }
 
-    pub fn mk_int_val<T: ToString>(&mut self, i: T) -> MarId {
-        let i = self.mk_symbol(i.to_string());
-        self.add(Marlang::IntVal([i]))
+    pub fn mk_bv_nand(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvNand([x, y]))
     }
 
-    pub fn mk_real_val<T: ToString>(&mut self, i: T) -> MarId {
-        let i = self.mk_symbol(i.to_string());
-        self.add(Marlang::RealVal([i]))
+    pub fn mk_bv_nor(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvNor([x, y]))
     }
 
-    pub fn mk_string_val(&mut self, i: String) -> MarId {
-        let s = self.mk_symbol(i);
-        self.add(Marlang::StringVal([s]))
+    pub fn mk_bv_xnor(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvXnor([x, y]))
     }
 
-    pub fn mk_symbol<T: ToString>(&mut self, name: T) -> MarId {
-        self.add(Marlang::Symbol(name.to_string()))
+    pub fn mk_bv_shl(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvShl([x, y]))
     }
 
-    pub fn mk_cons(&mut self, x: MarId, y: MarId) -> MarId {
-        self.add(Marlang::Cons([x, y]))
+    pub fn mk_bv_lshr(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvLshr([x, y]))
     }
 
-    pub fn mk_nil(&mut self) -> MarId {
-        self.add(Marlang::Nil)
+    pub fn mk_bv_ashr(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvAshr([x, y]))
     }
 
-    pub fn mk_rest(&mut self) -> MarId {
-        self.mk_symbol("?MARLANG_REST_PATTERN")
+    pub fn mk_bv_comp(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvComp([x, y]))
     }
-}
 
-impl MarContext {
-    pub fn set_logic(&mut self, logic: String) -> MarId {
-        let c = self.mk_set_logic(logic);
-        self.commands.push(c);
-        c
+    pub fn mk_bv_ult(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvUlt([x, y]))
     }
 
-    pub fn check_sat(&mut self) -> MarId {
-        let c = self.mk_check_sat();
-        self.commands.push(c);
-        c
+    pub fn mk_bv_ule(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvUle([x, y]))
     }
 
-    pub fn assert(&mut self, expr: MarId) -> MarId {
-        let c = self.mk_assert(expr);
-        self.commands.push(c);
-        c
+    pub fn mk_bv_ugt(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvUgt([x, y]))
     }
 
-    pub fn declare_const<T: ToString>(&mut self, name: T, sort: MarId) -> MarId {
-        let c = self.mk_declare_const(name, sort);
-        self.commands.push(c);
-        c
+    pub fn mk_bv_uge(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvUge([x, y]))
     }
 
-    pub fn commit(&mut self, command: MarId) {
-        self.commands.push(command)
+    pub fn mk_bv_slt(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSlt([x, y]))
     }
-}
 
-impl MarContext {
-    pub fn new() -> Self {
-        let mgraph = MarGraph::default().with_explanations_enabled();
-        Self {
-            runner: MarRunner::default().with_egraph(mgraph),
-            commands: vec![],
-            rewrites: vec![],
+    pub fn mk_bv_sle(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSle([x, y]))
+    }
+
+    pub fn mk_bv_sgt(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSgt([x, y]))
+    }
+
+    pub fn mk_bv_sge(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_width(&[x, y]);
+        self.add(Marlang::BvSge([x, y]))
+    }
+
+    pub fn mk_bv_concat(&mut self, x: MarId, y: MarId) -> MarId {
+        self.bv_width(x);
+        self.bv_width(y);
+        self.add(Marlang::BvConcat([x, y]))
+    }
+
+    pub fn mk_bv_extract(&mut self, i: u32, j: u32, x: MarId) -> MarId {
+        assert!(i >= j, "extract: {} must be at least {}", i, j);
+        if let Some(w) = self.bv_width(x) {
+            assert!(i < w, "extract: {} is out of range for width {}", i, w);
         }
+        let i = self.mk_symbol(i);
+        let j = self.mk_symbol(j);
+        self.add(Marlang::BvExtract([i, j, x]))
     }
 
-    pub fn asg(&mut self) -> MarId {
-        self.fold(self.commands.clone())
+    pub fn mk_bv_zero_extend(&mut self, i: u32, x: MarId) -> MarId {
+        self.bv_width(x);
+        let i = self.mk_symbol(i);
+        self.add(Marlang::BvZeroExtend([i, x]))
     }
 
-    pub fn extract_best(&mut self) -> MarRecExpr {
-        let asg = self.asg();
-        self.runner.egraph.rebuild();
-        let extractor = egg::Extractor::new(&self.runner.egraph, egg::AstSize);
-        let (_, best_expr) = extractor.find_best(asg);
-        best_expr
+    pub fn mk_bv_sign_extend(&mut self, i: u32, x: MarId) -> MarId {
+        self.bv_width(x);
+        let i = self.mk_symbol(i);
+        self.add(Marlang::BvSignExtend([i, x]))
     }
 
-    pub fn extract_any(&mut self) -> MarRecExpr {
-        let asg = self.asg();
-        self.runner.egraph.rebuild();
-        self.get_expr(asg)
+    pub fn mk_bv_repeat(&mut self, i: u32, x: MarId) -> MarId {
+        assert!(i > 0, "repeat: must repeat at least once");
+        self.bv_width(x);
+        let i = self.mk_symbol(i);
+        self.add(Marlang::BvRepeat([i, x]))
     }
 
-    pub fn get_expr(&self, expr: MarId) -> MarRecExpr {
-        self.runner.egraph.id_to_expr(expr)
+    pub fn mk_bv_rotate_left(&mut self, i: u32, x: MarId) -> MarId {
+        self.bv_width(x);
+        let i = self.mk_symbol(i);
+        self.add(Marlang::BvRotateLeft([i, x]))
     }
 
-    pub fn get_pattern(&self, expr: MarId, subs: Vec<MarId>) -> MarPattern {
-        let mut subs_map = HashMap::default();
-        for x in subs.into_iter() {
-            subs_map.insert(x, x);
-        }
-        let (p, _) = self.runner.egraph.id_to_pattern(expr, &subs_map);
-        p
+    pub fn mk_bv_rotate_right(&mut self, i: u32, x: MarId) -> MarId {
+        self.bv_width(x);
+        let i = self.mk_symbol(i);
+        self.add(Marlang::BvRotateRight([i, x]))
     }
 
-    pub fn add_rewrite(&mut self, name: String, left: MarPattern, right: MarPattern) {
-        self.rewrites.push(egg::rewrite!(name; left => right))
+    pub fn mk_str_len(&mut self, s: MarId) -> MarId {
+        self.add(Marlang::StrLen([s]))
     }
 
-    pub fn simplify(mut self, iter_limit: usize) -> Self {
-        self.runner.egraph.rebuild();
-        if self.rewrites.len() > 0 {
-            let runner: MarRunner = MarRunner::default()
-                .with_egraph(self.runner.egraph)
-                .with_iter_limit(iter_limit)
-                .run(&self.rewrites);
-            Self {
-                runner,
-                commands: self.commands,
-                rewrites: self.rewrites,
-            }
-        } else {
-            self
-        }
+    pub fn mk_str_at(&mut self, s: MarId, i: MarId) -> MarId {
+        self.add(Marlang::StrAt([s, i]))
     }
 
-    pub fn equiv(&self, left: MarRecExpr, right: MarRecExpr) -> bool {
-        let equivs = self.runner.egraph.equivs(&left, &right);
-        equivs.len() > 0
+    pub fn mk_str_substr(&mut self, s: MarId, i: MarId, n: MarId) -> MarId {
+        self.add(Marlang::StrSubstr([s, i, n]))
     }
 
-    pub fn explain_equivalence(&mut self, left: MarRecExpr, right: MarRecExpr) -> MarExplanation {
-        self.runner.egraph.explain_equivalence(&left, &right)
+    pub fn mk_str_contains(&mut self, s: MarId, t: MarId) -> MarId {
+        self.add(Marlang::StrContains([s, t]))
     }
-}
 
-impl MarContext {
-    fn fold(&mut self, args: Vec<MarId>) -> MarId {
-        let (start, to_skip) = if args.last() == Some(&self.mk_rest()) {
-            (self.mk_rest(), 1)
-        } else {
-            (self.mk_nil(), 0)
-        };
-        args.iter()
-            .rev()
-            .skip(to_skip)
-            .fold(start, |acc, x| self.mk_cons(*x, acc))
+    pub fn mk_str_indexof(&mut self, s: MarId, t: MarId, i: MarId) -> MarId {
+        self.add(Marlang::StrIndexOf([s, t, i]))
     }
 
-    fn add(&mut self, x: Marlang) -> MarId {
-        let out = self.runner.egraph.add(x);
-        out
+    pub fn mk_str_replace(&mut self, s: MarId, t: MarId, u: MarId) -> MarId {
+        self.add(Marlang::StrReplace([s, t, u]))
+    }
+
+    pub fn mk_str_replace_all(&mut self, s: MarId, t: MarId, u: MarId) -> MarId {
+        self.add(Marlang::StrReplaceAll([s, t, u]))
+    }
+
+    pub fn mk_str_prefixof(&mut self, s: MarId, t: MarId) -> MarId {
+        self.add(Marlang::StrPrefixOf([s, t]))
+    }
+
+    pub fn mk_str_suffixof(&mut self, s: MarId, t: MarId) -> MarId {
+        self.add(Marlang::StrSuffixOf([s, t]))
+    }
+
+    pub fn mk_str_is_digit(&mut self, s: MarId) -> MarId {
+        self.add(Marlang::StrIsDigit([s]))
+    }
+
+    pub fn mk_str_to_code(&mut self, s: MarId) -> MarId {
+        self.add(Marlang::StrToCode([s]))
+    }
+
+    pub fn mk_str_from_code(&mut self, n: MarId) -> MarId {
+        self.add(Marlang::StrFromCode([n]))
+    }
+
+    pub fn mk_str_to_int(&mut self, s: MarId) -> MarId {
+        self.add(Marlang::StrToInt([s]))
+    }
+
+    pub fn mk_str_from_int(&mut self, n: MarId) -> MarId {
+        self.add(Marlang::StrFromInt([n]))
+    }
+
+    pub fn mk_str_in_re(&mut self, s: MarId, r: MarId) -> MarId {
+        self.add(Marlang::StrInRe([s, r]))
+    }
+
+    pub fn mk_str_to_re(&mut self, s: MarId) -> MarId {
+        self.add(Marlang::StrToRe([s]))
+    }
+
+    pub fn mk_re_none(&mut self) -> MarId {
+        self.add(Marlang::ReNone)
+    }
+
+    pub fn mk_re_all(&mut self) -> MarId {
+        self.add(Marlang::ReAll)
+    }
+
+    pub fn mk_re_allchar(&mut self) -> MarId {
+        self.add(Marlang::ReAllChar)
+    }
+
+    pub fn mk_re_star(&mut self, r: MarId) -> MarId {
+        self.add(Marlang::ReStar([r]))
+    }
+
+    pub fn mk_re_plus(&mut self, r: MarId) -> MarId {
+        self.add(Marlang::RePlus([r]))
+    }
+
+    pub fn mk_re_opt(&mut self, r: MarId) -> MarId {
+        self.add(Marlang::ReOpt([r]))
+    }
+
+    pub fn mk_re_comp(&mut self, r: MarId) -> MarId {
+        self.add(Marlang::ReComp([r]))
+    }
+
+    pub fn mk_re_diff(&mut self, r: MarId, s: MarId) -> MarId {
+        self.add(Marlang::ReDiff([r, s]))
+    }
+
+    pub fn mk_re_range(&mut self, lo: MarId, hi: MarId) -> MarId {
+        self.add(Marlang::ReRange([lo, hi]))
+    }
+
+    pub fn mk_re_loop(&mut self, lo: u32, hi: u32, r: MarId) -> MarId {
+        let lo = self.mk_symbol(lo);
+        let hi = self.mk_symbol(hi);
+        self.add(Marlang::ReLoop([lo, hi, r]))
+    }
+
+    pub fn mk_re_power(&mut self, n: u32, r: MarId) -> MarId {
+        let n = self.mk_symbol(n);
+        self.add(Marlang::RePower([n, r]))
+    }
+
+    pub fn mk_fp_add(&mut self, rm: MarId, x: MarId, y: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpAdd([rm, x, y]))
+    }
+
+    pub fn mk_fp_sub(&mut self, rm: MarId, x: MarId, y: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpSub([rm, x, y]))
+    }
+
+    pub fn mk_fp_mul(&mut self, rm: MarId, x: MarId, y: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpMul([rm, x, y]))
+    }
+
+    pub fn mk_fp_div(&mut self, rm: MarId, x: MarId, y: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpDiv([rm, x, y]))
+    }
+
+    pub fn mk_fp_fma(&mut self, rm: MarId, x: MarId, y: MarId, z: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x, y, z]);
+        self.add(Marlang::FpFma([rm, x, y, z]))
+    }
+
+    pub fn mk_fp_sqrt(&mut self, rm: MarId, x: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpSqrt([rm, x]))
+    }
+
+    pub fn mk_fp_round_to_integral(&mut self, rm: MarId, x: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpRoundToIntegral([rm, x]))
+    }
+
+    pub fn mk_fp_abs(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpAbs([x]))
+    }
+
+    pub fn mk_fp_neg(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpNeg([x]))
+    }
+
+    pub fn mk_fp_is_normal(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpIsNormal([x]))
+    }
+
+    pub fn mk_fp_is_subnormal(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpIsSubnormal([x]))
+    }
+
+    pub fn mk_fp_is_zero(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpIsZero([x]))
+    }
+
+    pub fn mk_fp_is_infinite(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpIsInfinite([x]))
+    }
+
+    pub fn mk_fp_is_nan(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpIsNaN([x]))
+    }
+
+    pub fn mk_fp_is_negative(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpIsNegative([x]))
+    }
+
+    pub fn mk_fp_is_positive(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpIsPositive([x]))
+    }
+
+    pub fn mk_fp_rem(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpRem([x, y]))
+    }
+
+    pub fn mk_fp_min(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpMin([x, y]))
+    }
+
+    pub fn mk_fp_max(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpMax([x, y]))
+    }
+
+    pub fn mk_fp_leq(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpLeq([x, y]))
+    }
+
+    pub fn mk_fp_lt(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpLt([x, y]))
+    }
+
+    pub fn mk_fp_geq(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpGeq([x, y]))
+    }
+
+    pub fn mk_fp_gt(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpGt([x, y]))
+    }
+
+    pub fn mk_fp_eq(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_same_format(&[x, y]);
+        self.add(Marlang::FpEq([x, y]))
+    }
+
+    // Builds (fp sign exponent significand) from bit-vectors of widths 1, eb and sb - 1
+    pub fn mk_fp(&mut self, sign: MarId, exponent: MarId, significand: MarId) -> MarId {
+        if let Some(w) = self.bv_width(sign) {
+            assert_eq!(w, 1, "the sign of a floating-point literal is a single bit");
+        }
+        self.bv_width(exponent);
+        self.bv_width(significand);
+        self.add(Marlang::FpLit([sign, exponent, significand]))
+    }
+
+    pub fn mk_float32(&mut self, x: f32) -> MarId {
+        let bits = x.to_bits();
+        let sign = self.mk_bv_val(bits >> 31, 1);
+        let exponent = self.mk_bv_val((bits >> 23) & 0xff, 8);
+        let significand = self.mk_bv_val(bits & 0x7f_ffff, 23);
+        self.mk_fp(sign, exponent, significand)
+    }
+
+    pub fn mk_float64(&mut self, x: f64) -> MarId {
+        let bits = x.to_bits();
+        let sign = self.mk_bv_val(bits >> 63, 1);
+        let exponent = self.mk_bv_val((bits >> 52) & 0x7ff, 11);
+        let significand = self.mk_bv_val(bits & 0xf_ffff_ffff_ffff, 52);
+        self.mk_fp(sign, exponent, significand)
+    }
+
+    pub fn mk_fp_pos_zero(&mut self, eb: u32, sb: u32) -> MarId {
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpPosZero([eb, sb]))
+    }
+
+    pub fn mk_fp_neg_zero(&mut self, eb: u32, sb: u32) -> MarId {
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpNegZero([eb, sb]))
+    }
+
+    pub fn mk_fp_pos_inf(&mut self, eb: u32, sb: u32) -> MarId {
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpPosInf([eb, sb]))
+    }
+
+    pub fn mk_fp_neg_inf(&mut self, eb: u32, sb: u32) -> MarId {
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpNegInf([eb, sb]))
+    }
+
+    pub fn mk_fp_nan(&mut self, eb: u32, sb: u32) -> MarId {
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpNaN([eb, sb]))
+    }
+
+    pub fn mk_to_fp_from_real(&mut self, eb: u32, sb: u32, rm: MarId, x: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        let x = self.coerce_to_real(vec![x])[0];
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpFromReal([eb, sb, rm, x]))
+    }
+
+    // Reinterprets the bits of x, which must be eb + sb wide
+    pub fn mk_to_fp_from_bv(&mut self, eb: u32, sb: u32, x: MarId) -> MarId {
+        if let Some(w) = self.bv_width(x) {
+            assert_eq!(w, eb + sb, "bit-vector width mismatch");
+        }
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpFromBv([eb, sb, x]))
+    }
+
+    pub fn mk_to_fp_from_sbv(&mut self, eb: u32, sb: u32, rm: MarId, x: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.bv_width(x);
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpFromSbv([eb, sb, rm, x]))
+    }
+
+    pub fn mk_to_fp_from_ubv(&mut self, eb: u32, sb: u32, rm: MarId, x: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.bv_width(x);
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpFromUbv([eb, sb, rm, x]))
+    }
+
+    pub fn mk_to_fp_from_fp(&mut self, eb: u32, sb: u32, rm: MarId, x: MarId) -> MarId {
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x]);
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpFromFp([eb, sb, rm, x]))
+    }
+
+    pub fn mk_fp_to_ubv(&mut self, width: u32, rm: MarId, x: MarId) -> MarId {
+        assert!(width > 0, "bit-vectors must have a positive width");
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x]);
+        let width = self.mk_symbol(width);
+        self.add(Marlang::FpToUbv([width, rm, x]))
+    }
+
+    pub fn mk_fp_to_sbv(&mut self, width: u32, rm: MarId, x: MarId) -> MarId {
+        assert!(width > 0, "bit-vectors must have a positive width");
+        self.check_sort(rm, &MarSort::RoundingMode);
+        self.check_same_format(&[x]);
+        let width = self.mk_symbol(width);
+        self.add(Marlang::FpToSbv([width, rm, x]))
+    }
+
+    pub fn mk_fp_to_real(&mut self, x: MarId) -> MarId {
+        self.check_same_format(&[x]);
+        self.add(Marlang::FpToReal([x]))
+    }
+
+    pub fn mk_select(&mut self, array: MarId, index: MarId) -> MarId {
+        if let Some((i, _)) = self.array_sorts(array) {
+            self.check_sort(index, &i);
+        }
+        self.add(Marlang::Select([array, index]))
+    }
+
+    pub fn mk_store(&mut self, array: MarId, index: MarId, value: MarId) -> MarId {
+        if let Some((i, e)) = self.array_sorts(array) {
+            self.check_sort(index, &i);
+            self.check_sort(value, &e);
+        }
+        self.add(Marlang::Store([array, index, value]))
+    }
+
+    pub fn mk_const_array(&mut self, sort: MarId, value: MarId) -> MarId {
+        if let Some((_, e)) = self.array_sorts(sort) {
+            self.check_sort(value, &e);
+        }
+        self.add(Marlang::ConstArray([sort, value]))
+    }
+
+    // Selectors of different datatypes may share a name, so each one records its datatype
+    pub fn mk_selector<T: ToString>(&mut self, datatype: T, name: T, sort: MarId) -> MarId {
+        let datatype = self.mk_symbol(datatype);
+        let name = self.mk_symbol(name);
+        self.add(Marlang::Selector([datatype, name, sort]))
+    }
+
+    pub fn mk_constructor<T: ToString>(
+        &mut self,
+        datatype: T,
+        name: T,
+        selectors: Vec<MarId>,
+    ) -> MarId {
+        let datatype = self.mk_symbol(datatype);
+        let name = self.mk_symbol(name);
+        let selectors = self.fold(selectors);
+        self.add(Marlang::Constructor([datatype, name, selectors]))
+    }
+
+    pub fn mk_tester(&mut self, constructor: MarId) -> MarId {
+        self.add(Marlang::Tester([constructor]))
+    }
+
+    pub fn mk_datatype<T: ToString>(&mut self, name: T, constructors: Vec<MarId>) -> MarId {
+        let name = self.mk_symbol(name);
+        let constructors = self.fold(constructors);
+        self.add(Marlang::Datatype([name, constructors]))
+    }
+
+    pub fn mk_let(&mut self, bindings: Vec<(String, MarId)>, body: MarId) -> MarId {
+        let bindings = self.fold_pairs(bindings);
+        self.add(Marlang::Let([bindings, body]))
+    }
+
+    pub fn mk_forall<T: ToString>(&mut self, vars: Vec<(T, MarId)>, body: MarId) -> MarId {
+        let vars = self.fold_pairs(vars);
+        self.add(Marlang::Forall([vars, body]))
+    }
+
+    pub fn mk_exists<T: ToString>(&mut self, vars: Vec<(T, MarId)>, body: MarId) -> MarId {
+        let vars = self.fold_pairs(vars);
+        self.add(Marlang::Exists([vars, body]))
+    }
+
+    pub fn mk_annotate(&mut self, term: MarId, attributes: Vec<MarId>) -> MarId {
+        let attributes = self.fold(attributes);
+        self.add(Marlang::Annotate([term, attributes]))
+    }
+
+    pub fn mk_pattern_attr(&mut self, terms: Vec<MarId>) -> MarId {
+        let terms = self.fold(terms);
+        self.add(Marlang::PatternAttr([terms]))
+    }
+
+    pub fn mk_named_attr<T: ToString>(&mut self, name: T) -> MarId {
+        let name = self.mk_symbol(name);
+        self.add(Marlang::NamedAttr([name]))
+    }
+
+    pub fn mk_named<T: ToString>(&mut self, term: MarId, name: T) -> MarId {
+        let attr = self.mk_named_attr(name);
+        self.mk_annotate(term, vec![attr])
+    }
+
+    pub fn mk_globally(&mut self, arg: MarId) -> MarId {
+        self.check_sort(arg, &MarSort::Bool);
+        self.add(Marlang::Globally([arg]))
+    }
+
+    pub fn mk_finally(&mut self, arg: MarId) -> MarId {
+        self.check_sort(arg, &MarSort::Bool);
+        self.add(Marlang::Finally([arg]))
+    }
+
+    pub fn mk_next(&mut self, arg: MarId) -> MarId {
+        self.check_sort(arg, &MarSort::Bool);
+        self.add(Marlang::Next([arg]))
+    }
+
+    pub fn mk_until(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_sort(x, &MarSort::Bool);
+        self.check_sort(y, &MarSort::Bool);
+        self.add(Marlang::Until([x, y]))
+    }
+
+    pub fn mk_release(&mut self, x: MarId, y: MarId) -> MarId {
+        self.check_sort(x, &MarSort::Bool);
+        self.check_sort(y, &MarSort::Bool);
+        self.add(Marlang::Release([x, y]))
+    }
+
+    pub fn mk_not(&mut self, arg: MarId) -> MarId {
+        self.add(Marlang::Not([arg]))
+    }
+
+    pub fn mk_implies(&mut self, x: MarId, y: MarId) -> MarId {
+        self.add(Marlang::Implies([x, y]))
+    }
+
+    pub fn mk_ite(&mut self, x: MarId, y: MarId, z: MarId) -> MarId {
+        self.add(Marlang::Ite([x, y, z]))
+    }
+
+    pub fn mk_set_logic(&mut self, logic: String) -> MarId {
+        let logic = self.mk_symbol(logic);
+        self.add(Marlang::SetLogic([logic]))
+    }
+
+    pub fn mk_check_sat(&mut self) -> MarId {
+        self.add(Marlang::CheckSat)
+    }
+
+    pub fn mk_assert(&mut self, expr: MarId) -> MarId {
+        self.add(Marlang::Assert([expr]))
+    }
+
+    pub fn mk_declare_const<T: ToString>(&mut self, name: T, sort: MarId) -> MarId {
+        let x = self.mk_symbol(name);
+        let empty = self.mk_nil();
+        self.add(Marlang::DeclareFun([x, empty, sort]))
+    }
+
+    pub fn mk_declare_fun<T: ToString>(
+        &mut self,
+        name: T,
+        params: Vec<MarId>,
+        sort: MarId,
+    ) -> MarId {
+        let params = self.fold(params);
+        let f = self.mk_symbol(name);
+        self.add(Marlang::DeclareFun([f, params, sort]))
+    }
+
+    pub fn mk_define_fun<T: ToString>(
+        &mut self,
+        name: T,
+        params: Vec<(T, MarId)>,
+        sort: MarId,
+        body: MarId,
+    ) -> MarId {
+        let params = self.fold_pairs(params);
+        let f = self.mk_symbol(name);
+        self.add(Marlang::DefineFun([f, params, sort, body]))
+    }
+
+    // Calls can point to the function before it is defined, which is how a body refers to its
+    // own function or to the others of a define-funs-rec
+    pub fn mk_rec_fun<T: ToString>(&mut self, name: T, sorts: Vec<MarId>, sort: MarId) -> MarId {
+        let sorts = self.fold(sorts);
+        let f = self.mk_symbol(name);
+        self.add(Marlang::RecFun([f, sorts, sort]))
+    }
+
+    pub fn mk_define_fun_rec<T: ToString>(
+        &mut self,
+        function: MarId,
+        params: Vec<(T, MarId)>,
+        body: MarId,
+    ) -> MarId {
+        let egraph = &self.runner.egraph;
+        let sorts = egraph[function]
+            .iter()
+            .find_map(|node| match node {
+                Marlang::RecFun([_, sorts, _]) => Some(decompose_using_graph(egraph, *sorts)),
+                _ => None,
+            })
+            .expect("define-fun-rec must point to a recursive function");
+        assert_eq!(
+            sorts.len(),
+            params.len(),
+            "function expects {} parameters but got {}",
+            sorts.len(),
+            params.len()
+        );
+        if let Some(s) = self.runner.egraph[function].data.sort.clone() {
+            self.check_sort(body, &s);
+        }
+        let params = self.fold_pairs(params);
+        self.add(Marlang::DefineFunRec([function, params, body]))
+    }
+
+    pub fn mk_define_funs_rec(&mut self, definitions: Vec<MarId>) -> MarId {
+        let definitions = self.fold(definitions);
+        self.add(Marlang::DefineFunsRec([definitions]))
+    }
+
+    pub fn mk_push(&mut self, n: u32) -> MarId {
+        let n = self.mk_symbol(n);
+        self.add(Marlang::Push([n]))
+    }
+
+    pub fn mk_pop(&mut self, n: u32) -> MarId {
+        let n = self.mk_symbol(n);
+        self.add(Marlang::Pop([n]))
+    }
+
+    pub fn mk_reset_assertions(&mut self) -> MarId {
+        self.add(Marlang::ResetAssertions)
+    }
+
+    pub fn mk_check_sat_assuming(&mut self, literals: Vec<MarId>) -> MarId {
+        let literals = self.fold(literals);
+        self.add(Marlang::CheckSatAssuming([literals]))
+    }
+
+    pub fn mk_get_model(&mut self) -> MarId {
+        self.add(Marlang::GetModel)
+    }
+
+    pub fn mk_get_unsat_core(&mut self) -> MarId {
+        self.add(Marlang::GetUnsatCore)
+    }
+
+    pub fn mk_get_value(&mut self, terms: Vec<MarId>) -> MarId {
+        let terms = self.fold(terms);
+        self.add(Marlang::GetValue([terms]))
+    }
+
+    // The keyword includes its colon and the value is printed as is, like :produce-models true
+    pub fn mk_set_option(&mut self, keyword: &str, value: &str) -> MarId {
+        let keyword = self.mk_symbol(keyword);
+        let value = self.mk_symbol(value);
+        self.add(Marlang::SetOption([keyword, value]))
+    }
+
+    pub fn mk_set_info(&mut self, keyword: &str, value: &str) -> MarId {
+        let keyword = self.mk_symbol(keyword);
+        let value = self.mk_symbol(value);
+        self.add(Marlang::SetInfo([keyword, value]))
+    }
+
+    pub fn mk_exit(&mut self) -> MarId {
+        self.add(Marlang::Exit)
+    }
+
+    pub fn mk_declare_datatype(&mut self, datatype: MarId) -> MarId {
+        self.add(Marlang::DeclareDatatype([datatype]))
+    }
+
+    pub fn mk_declare_datatypes(&mut self, datatypes: Vec<MarId>) -> MarId {
+        let datatypes = self.fold(datatypes);
+        self.add(Marlang::DeclareDatatypes([datatypes]))
+    }
+
+    pub fn mk_declare_sort<T: ToString>(&mut self, name: T, arity: u32) -> MarId {
+        let name = self.mk_symbol(name);
+        let arity = self.mk_symbol(arity);
+        self.add(Marlang::DeclareSort([name, arity]))
+    }
+
+    pub fn mk_define_sort<T: ToString>(&mut self, name: T, params: Vec<T>, body: MarId) -> MarId {
+        let name = self.mk_symbol(name);
+        let params: Vec<MarId> = params.into_iter().map(|p| self.mk_symbol(p)).collect();
+        let params = self.fold(params);
+        self.add(Marlang::DefineSort([name, params, body]))
+    }
+
+    pub fn mk_sort_apply(&mut self, def: MarId, args: Vec<MarId>) -> MarId {
+        let arity = self.sort_arity(def);
+        assert_eq!(
+            arity,
+            args.len(),
+            "sort constructor expects {} arguments but got {}",
+            arity,
+            args.len()
+        );
+        let args = self.fold(args);
+        self.add(Marlang::SortApply([def, args]))
+    }
+
+    // An empty grammar leaves the synthesizer free to use any term of the sort
+    pub fn mk_synth_fun<T: ToString>(
+        &mut self,
+        name: T,
+        params: Vec<(T, MarId)>,
+        sort: MarId,
+        grammar: Vec<MarId>,
+    ) -> MarId {
+        let params = self.fold_pairs(params);
+        let grammar = self.fold(grammar);
+        let f = self.mk_symbol(name);
+        self.add(Marlang::SynthFun([f, params, sort, grammar]))
+    }
+
+    pub fn mk_nonterminal<T: ToString>(
+        &mut self,
+        name: T,
+        sort: MarId,
+        rules: Vec<MarId>,
+    ) -> MarId {
+        let name = self.mk_symbol(name);
+        let rules = self.fold(rules);
+        self.add(Marlang::NonTerminal([name, sort, rules]))
+    }
+
+    pub fn mk_grammar_constant(&mut self, sort: MarId) -> MarId {
+        self.add(Marlang::GrammarConstant([sort]))
+    }
+
+    pub fn mk_grammar_variable(&mut self, sort: MarId) -> MarId {
+        self.add(Marlang::GrammarVariable([sort]))
+    }
+
+    pub fn mk_declare_var<T: ToString>(&mut self, name: T, sort: MarId) -> MarId {
+        let name = self.mk_symbol(name);
+        self.add(Marlang::DeclareVar([name, sort]))
+    }
+
+    pub fn mk_constraint(&mut self, term: MarId) -> MarId {
+        self.check_sort(term, &MarSort::Bool);
+        self.add(Marlang::Constraint([term]))
+    }
+
+    pub fn mk_check_synth(&mut self) -> MarId {
+        self.add(Marlang::CheckSynth)
+    }
+
+    pub fn mk_declare_rel<T: ToString>(&mut self, name: T, sorts: Vec<MarId>) -> MarId {
+        let sorts = self.fold(sorts);
+        let name = self.mk_symbol(name);
+        self.add(Marlang::DeclareRel([name, sorts]))
+    }
+
+    // The head holds whenever every term of the body does, for all values of the variables. An
+    // empty body makes the rule a fact.
+    pub fn mk_rule<T: ToString>(
+        &mut self,
+        vars: Vec<(T, MarId)>,
+        body: Vec<MarId>,
+        head: MarId,
+    ) -> MarId {
+        self.check_sort(head, &MarSort::Bool);
+        let clause = match body.len() {
+            0 => head,
+            _ => {
+                let body = self.horn_body(body);
+                self.mk_implies(body, head)
+            }
+        };
+        let clause = match vars.len() {
+            0 => clause,
+            _ => self.mk_forall(vars, clause),
+        };
+        self.add(Marlang::Rule([clause]))
+    }
+
+    // Asks whether every term of the body can hold together for some values of the variables
+    pub fn mk_query<T: ToString>(&mut self, vars: Vec<(T, MarId)>, body: Vec<MarId>) -> MarId {
+        let body = self.horn_body(body);
+        let query = match vars.len() {
+            0 => body,
+            _ => self.mk_exists(vars, body),
+        };
+        self.add(Marlang::Query([query]))
+    }
+
+    pub fn mk_minimize(&mut self, objective: MarId) -> MarId {
+        self.check_objective(objective);
+        self.add(Marlang::Minimize([objective]))
+    }
+
+    pub fn mk_maximize(&mut self, objective: MarId) -> MarId {
+        self.check_objective(objective);
+        self.add(Marlang::Maximize([objective]))
+    }
+
+    pub fn mk_get_objectives(&mut self) -> MarId {
+        self.add(Marlang::GetObjectives)
+    }
+
+    pub fn mk_bool_sort(&mut self) -> MarId {
+        self.add(Marlang::BoolSort)
+    }
+
+    pub fn mk_int_sort(&mut self) -> MarId {
+        self.add(Marlang::IntSort)
+    }
+
+    pub fn mk_real_sort(&mut self) -> MarId {
+        self.add(Marlang::RealSort)
+    }
+
+    pub fn mk_string_sort(&mut self) -> MarId {
+        self.add(Marlang::StringSort)
+    }
+
+    pub fn mk_reglan_sort(&mut self) -> MarId {
+        self.add(Marlang::RegLanSort)
+    }
+
+    pub fn mk_bitvec_sort(&mut self, width: u32) -> MarId {
+        assert!(width > 0, "bit-vectors must have a positive width");
+        let width = self.mk_symbol(width);
+        self.add(Marlang::BitVecSort([width]))
+    }
+
+    pub fn mk_array_sort(&mut self, index: MarId, element: MarId) -> MarId {
+        self.add(Marlang::ArraySort([index, element]))
+    }
+
+    pub fn mk_fp_sort(&mut self, eb: u32, sb: u32) -> MarId {
+        let (eb, sb) = self.mk_fp_widths(eb, sb);
+        self.add(Marlang::FpSort([eb, sb]))
+    }
+
+    pub fn mk_rounding_mode_sort(&mut self) -> MarId {
+        self.add(Marlang::RoundingModeSort)
+    }
+
+    pub fn mk_datatype_sort<T: ToString>(&mut self, name: T) -> MarId {
+        let name = self.mk_symbol(name);
+        self.add(Marlang::DatatypeSort([name]))
+    }
+
+    pub fn mk_bool_val(&mut self, i: bool) -> MarId {
+        let i = self.mk_symbol(i.to_string());
+        self.add(Marlang::BoolVal([i]))
+    }
+
+    pub fn mk_int_val<T: ToString>(&mut self, i: T) -> MarId {
+        let i = self.mk_symbol(i.to_string());
+        self.add(Marlang::IntVal([i]))
+    }
+
+    pub fn mk_real_val<T: ToString>(&mut self, i: T) -> MarId {
+        let i = self.mk_symbol(i.to_string());
+        self.add(Marlang::RealVal([i]))
+    }
+
+    pub fn mk_bv_val<T: ToString>(&mut self, i: T, width: u32) -> MarId {
+        assert!(width > 0, "bit-vectors must have a positive width");
+        let i = i.to_string();
+        let value = Integer::from_str_radix(&i, 10).expect("bit-vector values are decimal");
+        assert!(
+            value >= 0 && value.significant_bits() <= width,
+            "{} does not fit in {} bits",
+            i,
+            width
+        );
+        let i = self.mk_symbol(value);
+        let width = self.mk_symbol(width);
+        self.add(Marlang::BvVal([i, width]))
+    }
+
+    // Accepts the SMT-LIB literals #b0101 and #x0f
+    pub fn mk_bv_literal(&mut self, literal: &str) -> MarId {
+        let (radix, digits, bits) = if let Some(digits) = literal.strip_prefix("#b") {
+            (2, digits, 1)
+        } else if let Some(digits) = literal.strip_prefix("#x") {
+            (16, digits, 4)
+        } else {
+            panic!("{} is not a bit-vector literal", literal)
+        };
+        let value = Integer::from_str_radix(digits, radix).expect("malformed bit-vector literal");
+        self.mk_bv_val(value, bits * digits.len() as u32)
+    }
+
+    // Accepts the short and the long SMT-LIB names, like RNE and roundNearestTiesToEven
+    pub fn mk_rounding_mode(&mut self, name: &str) -> MarId {
+        let rm =
+            RoundingMode::parse(name).unwrap_or_else(|| panic!("{} is not a rounding mode", name));
+        let rm = self.mk_symbol(rm.name());
+        self.add(Marlang::RmVal([rm]))
+    }
+
+    pub fn mk_string_val(&mut self, i: String) -> MarId {
+        let s = self.mk_symbol(i);
+        self.add(Marlang::StringVal([s]))
+    }
+
+    // Accepts SMT-LIB string literals, quotes and escape sequences included
+    pub fn mk_string_literal(&mut self, literal: &str) -> MarId {
+        let i = smtlib::unescape_string(literal)
+            .unwrap_or_else(|| panic!("{} is not a string literal", literal));
+        self.mk_string_val(i)
+    }
+
+    pub fn mk_symbol<T: ToString>(&mut self, name: T) -> MarId {
+        self.add(Marlang::Symbol(name.to_string()))
+    }
+
+    pub fn mk_cons(&mut self, x: MarId, y: MarId) -> MarId {
+        self.add(Marlang::Cons([x, y]))
+    }
+
+    pub fn mk_nil(&mut self) -> MarId {
+        self.add(Marlang::Nil)
+    }
+
+    pub fn mk_rest(&mut self) -> MarId {
+        self.mk_symbol("?MARLANG_REST_PATTERN")
+    }
+}
+
+impl MarContext {
+    pub fn set_logic(&mut self, logic: String) -> MarId {
+        let c = self.mk_set_logic(logic);
+        self.commit(c);
+        c
+    }
+
+    pub fn check_sat(&mut self) -> MarId {
+        let c = self.mk_check_sat();
+        self.commit(c);
+        c
+    }
+
+    pub fn assert_named<T: ToString>(&mut self, expr: MarId, name: T) -> MarId {
+        let named = self.mk_named(expr, name);
+        self.assert(named)
+    }
+
+    pub fn assert(&mut self, expr: MarId) -> MarId {
+        let c = self.mk_assert(expr);
+        self.commit(c);
+        c
+    }
+
+    pub fn declare_const<T: ToString>(&mut self, name: T, sort: MarId) -> MarId {
+        let c = self.mk_declare_const(name, sort);
+        self.commit(c);
+        c
+    }
+
+    pub fn define_fun<T: ToString>(
+        &mut self,
+        name: T,
+        params: Vec<(T, MarId)>,
+        sort: MarId,
+        body: MarId,
+    ) -> MarId {
+        let c = self.mk_define_fun(name, params, sort, body);
+        self.commit(c);
+        c
+    }
+
+    pub fn define_fun_rec<T: ToString>(
+        &mut self,
+        function: MarId,
+        params: Vec<(T, MarId)>,
+        body: MarId,
+    ) -> MarId {
+        let c = self.mk_define_fun_rec(function, params, body);
+        self.commit(c);
+        c
+    }
+
+    pub fn define_funs_rec(&mut self, definitions: Vec<MarId>) -> MarId {
+        let c = self.mk_define_funs_rec(definitions);
+        self.commit(c);
+        c
+    }
+
+    pub fn synth_fun<T: ToString>(
+        &mut self,
+        name: T,
+        params: Vec<(T, MarId)>,
+        sort: MarId,
+        grammar: Vec<MarId>,
+    ) -> MarId {
+        let c = self.mk_synth_fun(name, params, sort, grammar);
+        self.commit(c);
+        c
+    }
+
+    pub fn declare_var<T: ToString>(&mut self, name: T, sort: MarId) -> MarId {
+        let c = self.mk_declare_var(name, sort);
+        self.commit(c);
+        c
+    }
+
+    pub fn constraint(&mut self, term: MarId) -> MarId {
+        let c = self.mk_constraint(term);
+        self.commit(c);
+        c
+    }
+
+    pub fn check_synth(&mut self) -> MarId {
+        let c = self.mk_check_synth();
+        self.commit(c);
+        c
+    }
+
+    pub fn declare_rel<T: ToString>(&mut self, name: T, sorts: Vec<MarId>) -> MarId {
+        let c = self.mk_declare_rel(name, sorts);
+        self.commit(c);
+        c
+    }
+
+    pub fn rule<T: ToString>(
+        &mut self,
+        vars: Vec<(T, MarId)>,
+        body: Vec<MarId>,
+        head: MarId,
+    ) -> MarId {
+        let c = self.mk_rule(vars, body, head);
+        self.commit(c);
+        c
+    }
+
+    pub fn query<T: ToString>(&mut self, vars: Vec<(T, MarId)>, body: Vec<MarId>) -> MarId {
+        let c = self.mk_query(vars, body);
+        self.commit(c);
+        c
+    }
+
+    pub fn minimize(&mut self, objective: MarId) -> MarId {
+        let c = self.mk_minimize(objective);
+        self.commit(c);
+        c
+    }
+
+    pub fn maximize(&mut self, objective: MarId) -> MarId {
+        let c = self.mk_maximize(objective);
+        self.commit(c);
+        c
+    }
+
+    pub fn get_objectives(&mut self) -> MarId {
+        let c = self.mk_get_objectives();
+        self.commit(c);
+        c
+    }
+
+    // The active minimize and maximize commands, in order of priority
+    pub fn objectives(&self) -> Vec<MarId> {
+        self.active_commands()
+            .into_iter()
+            .filter(|c| {
+                self.runner.egraph[*c]
+                    .iter()
+                    .any(|node| matches!(node, Marlang::Minimize(_) | Marlang::Maximize(_)))
+            })
+            .collect()
+    }
+
+    // The active rules and queries, in the order they were given
+    pub fn horn_clauses(&self) -> Vec<MarId> {
+        self.active_commands()
+            .into_iter()
+            .filter(|c| {
+                self.runner.egraph[*c]
+                    .iter()
+                    .any(|node| matches!(node, Marlang::Rule(_) | Marlang::Query(_)))
+            })
+            .collect()
+    }
+
+    pub fn declare_datatype(&mut self, datatype: MarId) -> MarId {
+        let c = self.mk_declare_datatype(datatype);
+        self.commit(c);
+        c
+    }
+
+    pub fn declare_datatypes(&mut self, datatypes: Vec<MarId>) -> MarId {
+        let c = self.mk_declare_datatypes(datatypes);
+        self.commit(c);
+        c
+    }
+
+    pub fn declare_sort<T: ToString>(&mut self, name: T, arity: u32) -> MarId {
+        let c = self.mk_declare_sort(name, arity);
+        self.commit(c);
+        c
+    }
+
+    pub fn define_sort<T: ToString>(&mut self, name: T, params: Vec<T>, body: MarId) -> MarId {
+        let c = self.mk_define_sort(name, params, body);
+        self.commit(c);
+        c
+    }
+
+    pub fn push(&mut self, n: u32) -> MarId {
+        let c = self.mk_push(n);
+        self.commit(c);
+        c
+    }
+
+    pub fn pop(&mut self, n: u32) -> MarId {
+        let c = self.mk_pop(n);
+        self.commit(c);
+        c
+    }
+
+    pub fn reset_assertions(&mut self) -> MarId {
+        let c = self.mk_reset_assertions();
+        self.commit(c);
+        c
+    }
+
+    pub fn check_sat_assuming(&mut self, literals: Vec<MarId>) -> MarId {
+        let c = self.mk_check_sat_assuming(literals);
+        self.commit(c);
+        c
+    }
+
+    pub fn get_model(&mut self) -> MarId {
+        let c = self.mk_get_model();
+        self.commit(c);
+        c
+    }
+
+    pub fn get_unsat_core(&mut self) -> MarId {
+        let c = self.mk_get_unsat_core();
+        self.commit(c);
+        c
+    }
+
+    pub fn get_value(&mut self, terms: Vec<MarId>) -> MarId {
+        let c = self.mk_get_value(terms);
+        self.commit(c);
+        c
+    }
+
+    pub fn set_option(&mut self, keyword: &str, value: &str) -> MarId {
+        let c = self.mk_set_option(keyword, value);
+        self.commit(c);
+        c
+    }
+
+    pub fn set_info(&mut self, keyword: &str, value: &str) -> MarId {
+        let c = self.mk_set_info(keyword, value);
+        self.commit(c);
+        c
+    }
+
+    pub fn exit(&mut self) -> MarId {
+        let c = self.mk_exit();
+        self.commit(c);
+        c
+    }
+
+    // Every command stays in the log that gets printed, but only the declarations and assertions
+    // of the scopes that are still open are active.
+    pub fn commit(&mut self, command: MarId) {
+        self.commands.push(command);
+        let node = self.runner.egraph[command].nodes[0].clone();
+        match node {
+            Marlang::Push([n]) => {
+                for _ in 0..self.scope_size(n) {
+                    self.scopes.push(vec![]);
+                }
+            }
+            Marlang::Pop([n]) => {
+                let n = self.scope_size(n);
+                assert!(
+                    n < self.scopes.len(),
+                    "cannot pop {} scopes out of {}",
+                    n,
+                    self.scopes.len() - 1
+                );
+                self.scopes.truncate(self.scopes.len() - n);
+            }
+            Marlang::ResetAssertions => self.scopes = vec![vec![]],
+            Marlang::Assert(_)
+            | Marlang::DeclareFun(_)
+            | Marlang::DefineFun(_)
+            | Marlang::DefineFunRec(_)
+            | Marlang::DefineFunsRec(_)
+            | Marlang::DeclareDatatype(_)
+            | Marlang::DeclareDatatypes(_)
+            | Marlang::DeclareSort(_)
+            | Marlang::DefineSort(_)
+            | Marlang::SynthFun(_)
+            | Marlang::DeclareVar(_)
+            | Marlang::Constraint(_)
+            | Marlang::DeclareRel(_)
+            | Marlang::Rule(_)
+            | Marlang::Query(_)
+            | Marlang::Minimize(_)
+            | Marlang::Maximize(_) => self.scopes.last_mut().unwrap().push(command),
+            _ => (),
+        }
+    }
+
+    // The active assertions carrying a :named attribute, so that the names in an unsat core
+    // can be mapped back to the assert commands
+    pub fn named_assertions(&self) -> Vec<(String, MarId)> {
+        let egraph = &self.runner.egraph;
+        let mut named = vec![];
+        for command in self.active_commands() {
+            for node in egraph[command].iter() {
+                if let Marlang::Assert([expr]) = node {
+                    for name in self.names(*expr) {
+                        named.push((name, command));
+                    }
+                }
+            }
+        }
+        named
+    }
+
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len() - 1
+    }
+
+    // The declarations and assertions of the open scopes, oldest first
+    pub fn active_commands(&self) -> Vec<MarId> {
+        self.scopes.iter().flatten().cloned().collect()
+    }
+
+    // Every command committed so far, including those of popped scopes
+    pub fn commands(&self) -> Vec<MarId> {
+        self.commands.clone()
+    }
+
+    pub fn active_assertions(&self) -> Vec<MarId> {
+        self.active_commands()
+            .into_iter()
+            .filter_map(|c| {
+                self.runner.egraph[c].iter().find_map(|node| match node {
+                    Marlang::Assert([expr]) => Some(*expr),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    fn names(&self, expr: MarId) -> Vec<String> {
+        let egraph = &self.runner.egraph;
+        let mut names = vec![];
+        for node in egraph[expr].iter() {
+            if let Marlang::Annotate([_, attributes]) = node {
+                for attr in decompose_using_graph(egraph, *attributes) {
+                    for attr in egraph[attr].iter() {
+                        if let Marlang::NamedAttr([n]) = attr {
+                            names.extend(symbol(egraph, *n));
+                        }
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    fn scope_size(&self, n: MarId) -> usize {
+        symbol(&self.runner.egraph, n)
+            .and_then(|n| n.parse().ok())
+            .expect("push and pop take a numeral")
+    }
+}
+
+impl MarContext {
+    pub fn new() -> Self {
+        let mgraph = MarGraph::default().with_explanations_enabled();
+        Self {
+            runner: MarRunner::default().with_egraph(mgraph),
+            commands: vec![],
+            scopes: vec![vec![]],
+            rewrites: vec![],
+            assume: false,
+            assumed: vec![],
+            mixed: false,
+        }
+    }
+
+    pub fn with_assume_mode(mut self) -> Self {
+        self.assume = true;
+        self
+    }
+
+    // Int terms given to Real operators get wrapped in to_real, like in AUFLIRA scripts
+    pub fn with_mixed_arithmetic(mut self) -> Self {
+        self.mixed = true;
+        self
+    }
+
+    pub fn asg(&mut self) -> MarId {
+        self.fold(self.commands.clone())
+    }
+
+    pub fn extract_best(&mut self) -> MarRecExpr {
+        let asg = self.asg();
+        self.runner.egraph.rebuild();
+        let extractor = egg::Extractor::new(&self.runner.egraph, egg::AstSize);
+        if self.assumed.is_empty() {
+            let (_, best_expr) = extractor.find_best(asg);
+            return best_expr;
+        }
+        // assumed assertions are printed as they were before their equalities were merged,
+        // since the merged arguments would extract to the same term
+        let mut out = MarRecExpr::default();
+        let mut commands = vec![];
+        for command in &self.commands {
+            let best = match self.assumed.iter().find(|(c, _)| c == command) {
+                Some((_, expr)) => expr.clone(),
+                None => extractor.find_best(*command).1,
+            };
+            let mut ids: Vec<MarId> = vec![];
+            for node in best.as_ref() {
+                let id = out.add(node.clone().map_children(|c| ids[usize::from(c)]));
+                ids.push(id);
+            }
+            commands.extend(ids.last());
+        }
+        let nil = out.add(Marlang::Nil);
+        commands
+            .into_iter()
+            .rev()
+            .fold(nil, |acc, x| out.add(Marlang::Cons([x, acc])));
+        out
+    }
+
+    pub fn extract_any(&mut self) -> MarRecExpr {
+        let asg = self.asg();
+        self.runner.egraph.rebuild();
+        self.get_expr(asg)
+    }
+
+    pub fn get_expr(&self, expr: MarId) -> MarRecExpr {
+        self.runner.egraph.id_to_expr(expr)
+    }
+
+    pub fn get_pattern(&self, expr: MarId, subs: Vec<MarId>) -> MarPattern {
+        let mut subs_map = HashMap::default();
+        for x in subs.into_iter() {
+            subs_map.insert(x, x);
+        }
+        let (p, _) = self.runner.egraph.id_to_pattern(expr, &subs_map);
+        p
+    }
+
+    pub fn add_rewrite(&mut self, name: String, left: MarPattern, right: MarPattern) {
+        self.rewrites.push(rewrites::binder_safe(name, left, right))
+    }
+
+    pub fn add_rewrites(&mut self, rewrites: Vec<MarRewrite>) {
+        self.rewrites.extend(rewrites)
+    }
+
+    pub fn simplify(mut self, iter_limit: usize) -> Self {
+        if self.assume {
+            // the assertions are simplified on their own before they are assumed
+            self = self.run_rewrites(iter_limit);
+            self.assume_assertions();
+        }
+        self.run_rewrites(iter_limit)
+    }
+
+    fn run_rewrites(mut self, iter_limit: usize) -> Self {
+        self.runner.egraph.rebuild();
+        if self.rewrites.len() > 0 {
+            let runner: MarRunner = MarRunner::default()
+                .with_egraph(self.runner.egraph)
+                .with_iter_limit(iter_limit)
+                .run(&self.rewrites);
+            Self { runner, ..self }
+        } else {
+            self
+        }
+    }
+
+    // Merges the arguments of the asserted equalities so that the rewrites can use them. Each
+    // union is justified by the assertion that caused it, and each such assertion is recorded
+    // as it was before the unions so that extraction keeps it.
+    fn assume_assertions(&mut self) {
+        let mut unions = vec![];
+        let extractor = egg::Extractor::new(&self.runner.egraph, egg::AstSize);
+        for command in self.active_commands() {
+            let equalities: Vec<Vec<MarId>> = self.runner.egraph[command]
+                .iter()
+                .filter_map(|node| match node {
+                    Marlang::Assert([expr]) => Some(self.asserted_equalities(*expr)),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            if equalities.is_empty() {
+                continue;
+            }
+            if !self.assumed.iter().any(|(c, _)| *c == command) {
+                self.assumed.push((command, extractor.find_best(command).1));
+            }
+            let reason = format!("assume {}", self.get_expr(command));
+            unions.push((reason, equalities));
+        }
+        for (reason, equalities) in unions {
+            for args in equalities {
+                for pair in args.windows(2) {
+                    self.runner.egraph.union_trusted(pair[0], pair[1], &reason);
+                }
+            }
+        }
+    }
+
+    // The arguments of each equality in an asserted term, looking through conjunctions and
+    // annotations. Terms with free variables are skipped, their equalities only hold per binding.
+    fn asserted_equalities(&self, expr: MarId) -> Vec<Vec<MarId>> {
+        if !self.runner.egraph[expr].data.free.is_empty() {
+            return vec![];
+        }
+        let node = self.runner.egraph[expr].iter().find(|node| {
+            matches!(
+                node,
+                Marlang::Eq(_) | Marlang::And(_) | Marlang::Annotate(_)
+            )
+        });
+        match node {
+            Some(Marlang::Annotate([t, _])) => self.asserted_equalities(*t),
+            Some(Marlang::Eq([args])) => vec![decompose_using_graph(&self.runner.egraph, *args)],
+            Some(Marlang::And([args])) => decompose_using_graph(&self.runner.egraph, *args)
+                .into_iter()
+                .flat_map(|arg| self.asserted_equalities(arg))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn equiv(&self, left: MarRecExpr, right: MarRecExpr) -> bool {
+        let equivs = self.runner.egraph.equivs(&left, &right);
+        equivs.len() > 0
+    }
+
+    pub fn explain_equivalence(&mut self, left: MarRecExpr, right: MarRecExpr) -> MarExplanation {
+        self.runner.egraph.explain_equivalence(&left, &right)
+    }
+}
+
+impl MarContext {
+    fn fold(&mut self, args: Vec<MarId>) -> MarId {
+        let (start, to_skip) = if args.last() == Some(&self.mk_rest()) {
+            (self.mk_rest(), 1)
+        } else {
+            (self.mk_nil(), 0)
+        };
+        args.iter()
+            .rev()
+            .skip(to_skip)
+            .fold(start, |acc, x| self.mk_cons(*x, acc))
+    }
+
+    // Unknown widths are allowed so that symbols can stand in for terms in patterns
+    fn bv_width(&self, x: MarId) -> Option<u32> {
+        match &self.runner.egraph[x].data.sort {
+            Some(MarSort::BitVec(w)) => Some(*w),
+            None => None,
+            Some(s) => panic!("expected a bit-vector but found a term of sort {:?}", s),
+        }
+    }
+
+    fn check_same_width(&self, args: &[MarId]) {
+        let mut widths = args.iter().filter_map(|x| self.bv_width(*x));
+        if let Some(w) = widths.next() {
+            for v in widths {
+                assert_eq!(w, v, "bit-vector width mismatch");
+            }
+        }
+    }
+
+    fn mk_fp_widths(&mut self, eb: u32, sb: u32) -> (MarId, MarId) {
+        assert!(
+            eb > 1 && sb > 1,
+            "floating-point formats need at least 2 bits for each part"
+        );
+        (self.mk_symbol(eb), self.mk_symbol(sb))
+    }
+
+    fn fp_format(&self, x: MarId) -> Option<(u32, u32)> {
+        match &self.runner.egraph[x].data.sort {
+            Some(MarSort::FloatingPoint(eb, sb)) => Some((*eb, *sb)),
+            None => None,
+            Some(s) => panic!(
+                "expected a floating-point number but found a term of sort {:?}",
+                s
+            ),
+        }
+    }
+
+    fn check_same_format(&self, args: &[MarId]) {
+        let mut formats = args.iter().filter_map(|x| self.fp_format(*x));
+        if let Some(f) = formats.next() {
+            for g in formats {
+                assert_eq!(f, g, "floating-point format mismatch");
+            }
+        }
+    }
+
+    fn array_sorts(&self, x: MarId) -> Option<(MarSort, MarSort)> {
+        match &self.runner.egraph[x].data.sort {
+            Some(MarSort::Array(i, e)) => Some((*i.clone(), *e.clone())),
+            None => None,
+            Some(s) => panic!("expected an array but found a term of sort {:?}", s),
+        }
+    }
+
+    fn sort_arity(&self, def: MarId) -> usize {
+        let egraph = &self.runner.egraph;
+        egraph[def]
+            .iter()
+            .find_map(|node| match node {
+                Marlang::DeclareSort([_, n]) => symbol(egraph, *n)?.parse().ok(),
+                Marlang::DefineSort([_, params, _]) => {
+                    Some(decompose_using_graph(egraph, *params).len())
+                }
+                _ => None,
+            })
+            .expect("expected a sort declaration or definition")
+    }
+
+    // Only declared, recursive and synthesized functions carry their parameter sorts, so other calls are
+    // not checked
+    fn check_call(&self, def: MarId, args: MarId) {
+        let egraph = &self.runner.egraph;
+        let params = egraph[def].iter().find_map(|node| match node {
+            Marlang::DeclareFun([_, params, _])
+            | Marlang::DeclareRel([_, params])
+            | Marlang::RecFun([_, params, _]) => Some(decompose_using_graph(egraph, *params)),
+            Marlang::SynthFun([_, params, _, _]) => Some(
+                decompose_using_graph(egraph, *params)
+                    .into_iter()
+                    .filter_map(|p| decompose_using_graph(egraph, p).get(1).copied())
+                    .collect(),
+            ),
+            Marlang::DeclareVar(_) => Some(vec![]),
+            _ => None,
+        });
+        if let Some(params) = params {
+            let args = decompose_using_graph(egraph, args);
+            assert_eq!(
+                params.len(),
+                args.len(),
+                "function expects {} arguments but got {}",
+                params.len(),
+                args.len()
+            );
+            for (p, a) in params.iter().zip(args) {
+                if let Some(s) = &egraph[*p].data.sort {
+                    self.check_sort(a, s);
+                }
+            }
+        }
+    }
+
+    fn check_objective(&self, objective: MarId) {
+        match &self.runner.egraph[objective].data.sort {
+            None | Some(MarSort::Int) | Some(MarSort::Real) => (),
+            Some(s) => panic!("objectives must be Int or Real but got {:?}", s),
+        }
+    }
+
+    fn horn_body(&mut self, body: Vec<MarId>) -> MarId {
+        for b in &body {
+            self.check_sort(*b, &MarSort::Bool);
+        }
+        match body[..] {
+            [b] => b,
+            _ => self.mk_and(body),
+        }
+    }
+
+    fn check_same_sort(&self, args: &[MarId]) {
+        let mut sorts = args
+            .iter()
+            .filter_map(|x| self.runner.egraph[*x].data.sort.as_ref());
+        if let Some(s) = sorts.next() {
+            for t in sorts {
+                assert_eq!(s, t, "sort mismatch");
+            }
+        }
+    }
+
+    fn check_sort(&self, x: MarId, expected: &MarSort) {
+        if let Some(s) = &self.runner.egraph[x].data.sort {
+            assert_eq!(expected, s, "sort mismatch");
+        }
+    }
+
+    fn coerce_to_real(&mut self, args: Vec<MarId>) -> Vec<MarId> {
+        if !self.mixed {
+            return args;
+        }
+        args.into_iter()
+            .map(|x| match self.runner.egraph[x].data.sort {
+                Some(MarSort::Int) => self.mk_to_real(x),
+                _ => x,
+            })
+            .collect()
+    }
+
+    // Equalities between Int and Real terms compare them as reals
+    fn coerce_mixed(&mut self, args: Vec<MarId>) -> Vec<MarId> {
+        let real = args
+            .iter()
+            .any(|x| self.runner.egraph[*x].data.sort == Some(MarSort::Real));
+        if real {
+            self.coerce_to_real(args)
+        } else {
+            args
+        }
+    }
+
+    fn fold_pairs<T: ToString>(&mut self, pairs: Vec<(T, MarId)>) -> MarId {
+        let pairs: Vec<MarId> = pairs
+            .into_iter()
+            .map(|(name, value)| {
+                let name = self.mk_symbol(name);
+                self.fold(vec![name, value])
+            })
+            .collect();
+        self.fold(pairs)
+    }
+
+    pub(crate) fn add(&mut self, x: Marlang) -> MarId {
+        let out = self.runner.egraph.add(x);
+        out
+    }
+
+    // Copies a term from another context. Declarations that `rename` maps are swapped for the
+    // given ones, so that a term can be instantiated with fresh variables.
+    pub fn import(&mut self, expr: &MarRecExpr, rename: impl Fn(&str) -> Option<MarId>) -> MarId {
+        let mut ids: Vec<MarId> = vec![];
+        for node in expr.as_ref() {
+            let renamed = match node {
+                Marlang::DeclareFun([n, _, _]) => match &expr[*n] {
+                    Marlang::Symbol(n) => rename(n),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let id = match renamed {
+                Some(id) => id,
+                None => {
+                    let node = node.clone().map_children(|c| ids[usize::from(c)]);
+                    self.add(node)
+                }
+            };
+            ids.push(id);
+        }
+        *ids.last().expect("cannot import an empty expression")
     }
 
     pub fn add_recexpr(&mut self, x: MarRecExpr) -> MarId {
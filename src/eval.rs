@@ -0,0 +1,1524 @@
+use std::{cmp::Ordering, fmt};
+
+use fxhash::FxHashMap as HashMap;
+use rug::{Integer, Rational};
+
+use crate::{
+    ast::{MarId, MarRecExpr, MarSort, Marlang},
+    float::{to_integer, Fp, FpFormat, RoundingMode},
+    smtlib::escape_string,
+    util::decompose_using_expr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MarValue {
+    Bool(bool),
+    Int(Integer),
+    Real(Rational),
+    String(String),
+    RegLan(Regex),
+    // the width and then the value, which is always between 0 and 2^width - 1
+    BitVec(u32, Integer),
+    // the exponent and significand widths and then the value
+    Float(u32, u32, Fp),
+    RoundingMode(RoundingMode),
+    // the sort, the value at every index that is not stored and then the stores
+    Array(MarSort, Box<MarValue>, Vec<(MarValue, MarValue)>),
+    // the constructor and then the selectors with their values
+    Datatype(String, Vec<(String, MarValue)>),
+    // an element of an uninterpreted sort, named the way solvers print them
+    Abstract(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Regex {
+    None,
+    All,
+    AllChar,
+    Str(Vec<char>),
+    Range(char, char),
+    Concat(Box<Regex>, Box<Regex>),
+    Union(Box<Regex>, Box<Regex>),
+    Inter(Box<Regex>, Box<Regex>),
+    Star(Box<Regex>),
+    Comp(Box<Regex>),
+    Loop(u32, u32, Box<Regex>),
+}
+
+// the explicit entries of a function and then the value everywhere else
+type MarTable = (Vec<(Vec<MarValue>, MarValue)>, Option<MarValue>);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarModel {
+    values: HashMap<String, MarValue>,
+    functions: HashMap<String, MarTable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    Unbound(String),
+    Undefined(String),
+    Unsupported(String),
+    Malformed(String),
+}
+
+type EvalResult = Result<MarValue, EvalError>;
+
+// nested calls of recursive definitions; an optimized build needs a few kilobytes of stack for each
+const MAX_CALL_DEPTH: usize = 500;
+
+// concat, extend and repeat widen their arguments by numerals from the script
+const MAX_BV_WIDTH: u32 = 1 << 16;
+
+pub fn eval(mexpr: &MarRecExpr, id: MarId, model: &MarModel) -> EvalResult {
+    Evaluator {
+        mexpr,
+        model,
+        env: vec![],
+        memo: HashMap::default(),
+        depth: 0,
+    }
+    .eval(id)
+}
+
+impl MarModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<T: ToString>(&mut self, name: T, value: MarValue) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn insert_entry<T: ToString>(&mut self, name: T, args: Vec<MarValue>, value: MarValue) {
+        let (entries, _) = self.functions.entry(name.to_string()).or_default();
+        entries.push((args, value));
+    }
+
+    pub fn insert_default<T: ToString>(&mut self, name: T, value: MarValue) {
+        let (_, default) = self.functions.entry(name.to_string()).or_default();
+        *default = Some(value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MarValue> {
+        self.values.get(name)
+    }
+
+    pub fn apply(&self, name: &str, args: &[MarValue]) -> Option<&MarValue> {
+        let (entries, default) = self.functions.get(name)?;
+        entries
+            .iter()
+            .find(|(xs, _)| xs[..] == args[..])
+            .map(|(_, v)| v)
+            .or(default.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &MarValue)> {
+        self.values.iter()
+    }
+
+    pub fn extend(&mut self, other: MarModel) {
+        self.values.extend(other.values);
+        self.functions.extend(other.functions);
+    }
+}
+
+struct Evaluator<'a> {
+    mexpr: &'a MarRecExpr,
+    model: &'a MarModel,
+    env: Vec<(String, MarValue)>,
+    memo: HashMap<MarId, MarValue>,
+    depth: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn eval(&mut self, id: MarId) -> EvalResult {
+        // under a binder the same node can stand for different values
+        if !self.env.is_empty() {
+            return self.eval_node(id);
+        }
+        if let Some(v) = self.memo.get(&id) {
+            return Ok(v.clone());
+        }
+        let v = self.eval_node(id)?;
+        self.memo.insert(id, v.clone());
+        Ok(v)
+    }
+
+    fn list(&mut self, id: MarId) -> Result<Vec<MarValue>, EvalError> {
+        decompose_using_expr(self.mexpr, id)
+            .into_iter()
+            .map(|x| self.eval(x))
+            .collect()
+    }
+
+    fn symbol(&self, id: MarId) -> Result<String, EvalError> {
+        match &self.mexpr[id] {
+            Marlang::Symbol(s) => Ok(s.clone()),
+            node => Err(EvalError::Malformed(format!("expected a symbol: {}", node))),
+        }
+    }
+
+    fn numeral(&self, id: MarId) -> Result<u32, EvalError> {
+        let s = self.symbol(id)?;
+        s.parse()
+            .map_err(|_| EvalError::Malformed(format!("expected a numeral: {}", s)))
+    }
+
+    fn bool(&mut self, id: MarId) -> Result<bool, EvalError> {
+        match self.eval(id)? {
+            MarValue::Bool(b) => Ok(b),
+            v => Err(ill_sorted("Bool", &v)),
+        }
+    }
+
+    fn int(&mut self, id: MarId) -> Result<Integer, EvalError> {
+        match self.eval(id)? {
+            MarValue::Int(i) => Ok(i),
+            v => Err(ill_sorted("Int", &v)),
+        }
+    }
+
+    fn real(&mut self, id: MarId) -> Result<Rational, EvalError> {
+        match self.eval(id)? {
+            MarValue::Real(r) => Ok(r),
+            MarValue::Int(i) => Ok(i.into()),
+            v => Err(ill_sorted("Real", &v)),
+        }
+    }
+
+    fn string(&mut self, id: MarId) -> Result<Vec<char>, EvalError> {
+        match self.eval(id)? {
+            MarValue::String(s) => Ok(s.chars().collect()),
+            v => Err(ill_sorted("String", &v)),
+        }
+    }
+
+    fn regex(&mut self, id: MarId) -> Result<Regex, EvalError> {
+        match self.eval(id)? {
+            MarValue::RegLan(r) => Ok(r),
+            v => Err(ill_sorted("RegLan", &v)),
+        }
+    }
+
+    fn bv(&mut self, id: MarId) -> Result<(u32, Integer), EvalError> {
+        match self.eval(id)? {
+            MarValue::BitVec(0, _) => Err(EvalError::Malformed("a bit-vector of width 0".into())),
+            MarValue::BitVec(w, v) => Ok((w, v)),
+            v => Err(ill_sorted("BitVec", &v)),
+        }
+    }
+
+    fn float(&mut self, id: MarId) -> Result<(FpFormat, Fp), EvalError> {
+        match self.eval(id)? {
+            MarValue::Float(eb, sb, x) => Ok((fp_format(eb, sb)?, x)),
+            v => Err(ill_sorted("FloatingPoint", &v)),
+        }
+    }
+
+    fn rounding_mode(&mut self, id: MarId) -> Result<RoundingMode, EvalError> {
+        match self.eval(id)? {
+            MarValue::RoundingMode(rm) => Ok(rm),
+            v => Err(ill_sorted("RoundingMode", &v)),
+        }
+    }
+
+    fn format(&self, eb: MarId, sb: MarId) -> Result<FpFormat, EvalError> {
+        fp_format(self.numeral(eb)?, self.numeral(sb)?)
+    }
+
+    // widths come straight from the script, so a malformed file can ask for zero bits
+    fn width(&self, id: MarId) -> Result<u32, EvalError> {
+        match self.numeral(id)? {
+            0 => Err(EvalError::Malformed("a bit-vector of width 0".into())),
+            w => Ok(w),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> EvalResult {
+        self.env
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .or_else(|| self.model.get(name).cloned())
+            .ok_or_else(|| EvalError::Unbound(name.into()))
+    }
+
+    // Declared constants are global, so names bound around their use cannot shadow them
+    fn global(&self, name: String) -> EvalResult {
+        self.model
+            .get(&name)
+            .cloned()
+            .ok_or(EvalError::Unbound(name))
+    }
+
+    // Bodies of definitions only see their parameters, and not the bindings around the call
+    fn with_params(&mut self, params: Vec<(String, MarValue)>, body: MarId) -> EvalResult {
+        let outer = std::mem::replace(&mut self.env, params);
+        let out = self.eval(body);
+        self.env = outer;
+        out
+    }
+
+    fn with_bindings(&mut self, bindings: Vec<(String, MarValue)>, body: MarId) -> EvalResult {
+        let depth = self.env.len();
+        self.env.extend(bindings);
+        let out = self.eval(body);
+        self.env.truncate(depth);
+        out
+    }
+
+    fn eval_node(&mut self, id: MarId) -> EvalResult {
+        let node = self.mexpr[id].clone();
+        match node {
+            Marlang::Symbol(s) => self.lookup(&s),
+            Marlang::Call([def, args]) => {
+                let args = self.list(args)?;
+                self.call(def, args)
+            }
+            Marlang::Let([bindings, body]) => {
+                let mut values = vec![];
+                for b in decompose_using_expr(self.mexpr, bindings) {
+                    match decompose_using_expr(self.mexpr, b)[..] {
+                        [name, value] => values.push((self.symbol(name)?, self.eval(value)?)),
+                        _ => return Err(EvalError::Malformed("let binding".into())),
+                    }
+                }
+                self.with_bindings(values, body)
+            }
+            Marlang::Annotate([t, _]) => self.eval(t),
+            Marlang::Forall(_) | Marlang::Exists(_) => {
+                Err(EvalError::Unsupported("quantifiers".into()))
+            }
+
+            Marlang::BoolVal([v]) => match self.symbol(v)?.as_str() {
+                "true" => Ok(MarValue::Bool(true)),
+                "false" => Ok(MarValue::Bool(false)),
+                s => Err(EvalError::Malformed(format!("Boolean value {}", s))),
+            },
+            Marlang::IntVal([v]) => {
+                let s = self.symbol(v)?;
+                s.parse()
+                    .map(MarValue::Int)
+                    .map_err(|_| EvalError::Malformed(format!("integer value {}", s)))
+            }
+            Marlang::RealVal([v]) => {
+                let s = self.symbol(v)?;
+                parse_decimal(&s)
+                    .map(MarValue::Real)
+                    .ok_or_else(|| EvalError::Malformed(format!("real value {}", s)))
+            }
+            Marlang::StringVal([v]) => Ok(MarValue::String(self.symbol(v)?)),
+            Marlang::BvVal([v, w]) => {
+                let s = self.symbol(v)?;
+                let v: Integer = s
+                    .parse()
+                    .map_err(|_| EvalError::Malformed(format!("bit-vector value {}", s)))?;
+                let w = self.width(w)?;
+                Ok(MarValue::BitVec(w, v.keep_bits(w)))
+            }
+
+            Marlang::Not([x]) => Ok(MarValue::Bool(!self.bool(x)?)),
+            // these stop at the first argument that decides them, like ite, so that recursive
+            // definitions can guard their calls with them
+            Marlang::And([xs]) => {
+                for x in decompose_using_expr(self.mexpr, xs) {
+                    if !self.bool(x)? {
+                        return Ok(MarValue::Bool(false));
+                    }
+                }
+                Ok(MarValue::Bool(true))
+            }
+            Marlang::Or([xs]) => {
+                for x in decompose_using_expr(self.mexpr, xs) {
+                    if self.bool(x)? {
+                        return Ok(MarValue::Bool(true));
+                    }
+                }
+                Ok(MarValue::Bool(false))
+            }
+            Marlang::Xor([xs]) => {
+                let mut out = false;
+                for x in decompose_using_expr(self.mexpr, xs) {
+                    out ^= self.bool(x)?;
+                }
+                Ok(MarValue::Bool(out))
+            }
+            Marlang::Implies([x, y]) => Ok(MarValue::Bool(!self.bool(x)? || self.bool(y)?)),
+            Marlang::Ite([c, x, y]) => {
+                if self.bool(c)? {
+                    self.eval(x)
+                } else {
+                    self.eval(y)
+                }
+            }
+            Marlang::Eq([xs]) => {
+                let xs = self.list(xs)?;
+                Ok(MarValue::Bool(xs.windows(2).all(|p| p[0] == p[1])))
+            }
+            Marlang::Distinct([xs]) => {
+                let xs = self.list(xs)?;
+                let distinct = xs
+                    .iter()
+                    .enumerate()
+                    .all(|(i, x)| xs[i + 1..].iter().all(|y| x != y));
+                Ok(MarValue::Bool(distinct))
+            }
+            node => self.eval_arith(node),
+        }
+    }
+
+    // the match is split by theory, since one frame for every operator is too large to nest
+    // deeply in unoptimized builds
+    fn eval_arith(&mut self, node: Marlang) -> EvalResult {
+        match node {
+            Marlang::IntAdd([xs]) => {
+                let xs = self.ints(xs)?;
+                Ok(MarValue::Int(xs.into_iter().sum()))
+            }
+            Marlang::IntMul([xs]) => {
+                let xs = self.ints(xs)?;
+                Ok(MarValue::Int(xs.into_iter().product()))
+            }
+            Marlang::IntSub([xs]) => {
+                let mut xs = self.ints(xs)?.into_iter();
+                let first = xs.next().ok_or_else(|| arity("-"))?;
+                let rest: Vec<Integer> = xs.collect();
+                if rest.is_empty() {
+                    Ok(MarValue::Int(-first))
+                } else {
+                    Ok(MarValue::Int(first - rest.into_iter().sum::<Integer>()))
+                }
+            }
+            Marlang::IntGt([xs]) => self.chain(xs, |o| o == Ordering::Greater),
+            Marlang::IntGe([xs]) => self.chain(xs, |o| o != Ordering::Less),
+            Marlang::IntLt([xs]) => self.chain(xs, |o| o == Ordering::Less),
+            Marlang::IntLe([xs]) => self.chain(xs, |o| o != Ordering::Greater),
+
+            Marlang::RealAdd([xs]) => {
+                let xs = self.reals(xs)?;
+                Ok(MarValue::Real(xs.into_iter().sum()))
+            }
+            Marlang::RealMul([xs]) => {
+                let xs = self.reals(xs)?;
+                Ok(MarValue::Real(xs.into_iter().product()))
+            }
+            Marlang::RealSub([xs]) => {
+                let mut xs = self.reals(xs)?.into_iter();
+                let first = xs.next().ok_or_else(|| arity("-"))?;
+                let rest: Vec<Rational> = xs.collect();
+                if rest.is_empty() {
+                    Ok(MarValue::Real(-first))
+                } else {
+                    Ok(MarValue::Real(first - rest.into_iter().sum::<Rational>()))
+                }
+            }
+            Marlang::RealDiv([xs]) => {
+                let mut xs = self.reals(xs)?.into_iter();
+                let mut out = xs.next().ok_or_else(|| arity("/"))?;
+                for x in xs {
+                    if x == 0 {
+                        return Err(EvalError::Undefined("division by zero".into()));
+                    }
+                    out /= x;
+                }
+                Ok(MarValue::Real(out))
+            }
+            Marlang::RealGt([xs]) => self.chain(xs, |o| o == Ordering::Greater),
+            Marlang::RealGe([xs]) => self.chain(xs, |o| o != Ordering::Less),
+            Marlang::RealLt([xs]) => self.chain(xs, |o| o == Ordering::Less),
+            Marlang::RealLe([xs]) => self.chain(xs, |o| o != Ordering::Greater),
+
+            Marlang::IntDiv([xs]) => {
+                let mut xs = self.ints(xs)?.into_iter();
+                let mut out = xs.next().ok_or_else(|| arity("div"))?;
+                for x in xs {
+                    out = euclidean(out, x)?.0;
+                }
+                Ok(MarValue::Int(out))
+            }
+            Marlang::IntMod([x, y]) => {
+                let x = self.int(x)?;
+                let y = self.int(y)?;
+                Ok(MarValue::Int(euclidean(x, y)?.1))
+            }
+            Marlang::IntAbs([x]) => Ok(MarValue::Int(self.int(x)?.abs())),
+            Marlang::IntNeg([x]) => Ok(MarValue::Int(-self.int(x)?)),
+            Marlang::RealNeg([x]) => Ok(MarValue::Real(-self.real(x)?)),
+            Marlang::ToReal([x]) => Ok(MarValue::Real(self.int(x)?.into())),
+            Marlang::ToInt([x]) => Ok(MarValue::Int(self.real(x)?.floor().into_numer_denom().0)),
+            Marlang::IsInt([x]) => Ok(MarValue::Bool(*self.real(x)?.denom() == 1)),
+            node => self.eval_theory(node),
+        }
+    }
+
+    fn eval_theory(&mut self, node: Marlang) -> EvalResult {
+        match node {
+            Marlang::Concat([xs]) => {
+                let mut out = String::new();
+                for x in decompose_using_expr(self.mexpr, xs) {
+                    out.extend(self.string(x)?);
+                }
+                Ok(MarValue::String(out))
+            }
+            Marlang::StrLt([xs]) => self.chain(xs, |o| o == Ordering::Less),
+            Marlang::StrLe([xs]) => self.chain(xs, |o| o != Ordering::Greater),
+            Marlang::StrLen([s]) => Ok(MarValue::Int(self.string(s)?.len().into())),
+            Marlang::StrAt([s, i]) => {
+                let s = self.string(s)?;
+                let i = self.int(i)?;
+                Ok(string(substr(&s, &i, &Integer::from(1))))
+            }
+            Marlang::StrSubstr([s, i, n]) => {
+                let s = self.string(s)?;
+                let i = self.int(i)?;
+                let n = self.int(n)?;
+                Ok(string(substr(&s, &i, &n)))
+            }
+            Marlang::StrContains([s, t]) => {
+                let s = self.string(s)?;
+                let t = self.string(t)?;
+                Ok(MarValue::Bool(find(&s, &t, 0).is_some()))
+            }
+            Marlang::StrIndexOf([s, t, i]) => {
+                let s = self.string(s)?;
+                let t = self.string(t)?;
+                let i = self.int(i)?;
+                let found = match i.to_usize() {
+                    Some(i) if i <= s.len() => find(&s, &t, i),
+                    _ => None,
+                };
+                Ok(MarValue::Int(
+                    found.map_or(Integer::from(-1), Integer::from),
+                ))
+            }
+            Marlang::StrReplace([s, t, u]) => {
+                let s = self.string(s)?;
+                let t = self.string(t)?;
+                let u = self.string(u)?;
+                let out = match find(&s, &t, 0) {
+                    Some(i) => [&s[..i], &u[..], &s[i + t.len()..]].concat(),
+                    None => s,
+                };
+                Ok(string(out))
+            }
+            Marlang::StrReplaceAll([s, t, u]) => {
+                let s = self.string(s)?;
+                let t = self.string(t)?;
+                let u = self.string(u)?;
+                if t.is_empty() {
+                    return Ok(string(s));
+                }
+                let mut out = vec![];
+                let mut i = 0;
+                while let Some(j) = find(&s, &t, i) {
+                    out.extend_from_slice(&s[i..j]);
+                    out.extend_from_slice(&u);
+                    i = j + t.len();
+                }
+                out.extend_from_slice(&s[i..]);
+                Ok(string(out))
+            }
+            Marlang::StrPrefixOf([s, t]) => {
+                let s = self.string(s)?;
+                let t = self.string(t)?;
+                Ok(MarValue::Bool(t.starts_with(&s)))
+            }
+            Marlang::StrSuffixOf([s, t]) => {
+                let s = self.string(s)?;
+                let t = self.string(t)?;
+                Ok(MarValue::Bool(t.ends_with(&s)))
+            }
+            Marlang::StrIsDigit([s]) => {
+                let s = self.string(s)?;
+                Ok(MarValue::Bool(matches!(s[..], ['0'..='9'])))
+            }
+            Marlang::StrToCode([s]) => match self.string(s)?[..] {
+                [c] => Ok(MarValue::Int((c as u32).into())),
+                _ => Ok(MarValue::Int((-1).into())),
+            },
+            Marlang::StrFromCode([n]) => match self.int(n)?.to_u32().filter(|c| *c <= 0x2FFFF) {
+                // surrogates are valid code points in SMT-LIB but cannot be held in a char
+                Some(c @ 0xD800..=0xDFFF) => Err(EvalError::Unsupported(format!(
+                    "str.from_code of the surrogate {:#X}",
+                    c
+                ))),
+                c => Ok(string(c.and_then(char::from_u32).into_iter().collect())),
+            },
+            Marlang::StrToInt([s]) => {
+                let s = self.string(s)?;
+                if !s.is_empty() && s.iter().all(|c| c.is_ascii_digit()) {
+                    let s: String = s.into_iter().collect();
+                    Ok(MarValue::Int(s.parse().unwrap()))
+                } else {
+                    Ok(MarValue::Int((-1).into()))
+                }
+            }
+            Marlang::StrFromInt([n]) => {
+                let n = self.int(n)?;
+                if n < 0 {
+                    Ok(MarValue::String("".into()))
+                } else {
+                    Ok(MarValue::String(n.to_string()))
+                }
+            }
+            Marlang::StrInRe([s, r]) => {
+                let s = self.string(s)?;
+                let r = self.regex(r)?;
+                Ok(MarValue::Bool(r.matches(&s)))
+            }
+            Marlang::StrToRe([s]) => Ok(MarValue::RegLan(Regex::Str(self.string(s)?))),
+
+            Marlang::ReNone => Ok(MarValue::RegLan(Regex::None)),
+            Marlang::ReAll => Ok(MarValue::RegLan(Regex::All)),
+            Marlang::ReAllChar => Ok(MarValue::RegLan(Regex::AllChar)),
+            Marlang::ReConcat([rs]) => self.regexes(rs, Regex::Str(vec![]), Regex::concat),
+            Marlang::ReUnion([rs]) => self.regexes(rs, Regex::None, Regex::union),
+            Marlang::ReInter([rs]) => self.regexes(rs, Regex::All, Regex::inter),
+            Marlang::ReStar([r]) => Ok(MarValue::RegLan(Regex::Star(Box::new(self.regex(r)?)))),
+            Marlang::RePlus([r]) => {
+                let r = self.regex(r)?;
+                let star = Regex::Star(Box::new(r.clone()));
+                Ok(MarValue::RegLan(Regex::concat(r, star)))
+            }
+            Marlang::ReOpt([r]) => {
+                let r = self.regex(r)?;
+                Ok(MarValue::RegLan(Regex::union(Regex::Str(vec![]), r)))
+            }
+            Marlang::ReComp([r]) => Ok(MarValue::RegLan(Regex::Comp(Box::new(self.regex(r)?)))),
+            Marlang::ReDiff([r, s]) => {
+                let r = self.regex(r)?;
+                let s = Regex::Comp(Box::new(self.regex(s)?));
+                Ok(MarValue::RegLan(Regex::inter(r, s)))
+            }
+            Marlang::ReRange([lo, hi]) => {
+                let range = match (&self.string(lo)?[..], &self.string(hi)?[..]) {
+                    ([lo], [hi]) if lo <= hi => Regex::Range(*lo, *hi),
+                    _ => Regex::None,
+                };
+                Ok(MarValue::RegLan(range))
+            }
+            Marlang::ReLoop([lo, hi, r]) => {
+                let lo = self.numeral(lo)?;
+                let hi = self.numeral(hi)?;
+                let r = self.regex(r)?;
+                Ok(MarValue::RegLan(Regex::Loop(lo, hi, Box::new(r))))
+            }
+            Marlang::RePower([n, r]) => {
+                let n = self.numeral(n)?;
+                let r = self.regex(r)?;
+                Ok(MarValue::RegLan(Regex::Loop(n, n, Box::new(r))))
+            }
+
+            Marlang::BvAdd([xs]) => self.bv_fold(xs, |x, y| x + y),
+            Marlang::BvMul([xs]) => self.bv_fold(xs, |x, y| x * y),
+            Marlang::BvAnd([xs]) => self.bv_fold(xs, |x, y| x & y),
+            Marlang::BvOr([xs]) => self.bv_fold(xs, |x, y| x | y),
+            Marlang::BvXor([xs]) => self.bv_fold(xs, |x, y| x ^ y),
+            Marlang::BvNot([x]) => {
+                let (w, x) = self.bv(x)?;
+                Ok(bv(w, !x))
+            }
+            Marlang::BvNeg([x]) => {
+                let (w, x) = self.bv(x)?;
+                Ok(bv(w, -x))
+            }
+            Marlang::BvSub([x, y]) => self.bv_binary(x, y, |_, x, y| x - y),
+            Marlang::BvNand([x, y]) => self.bv_binary(x, y, |_, x, y| !(x & y)),
+            Marlang::BvNor([x, y]) => self.bv_binary(x, y, |_, x, y| !(x | y)),
+            Marlang::BvXnor([x, y]) => self.bv_binary(x, y, |_, x, y| !(x ^ y)),
+            Marlang::BvUdiv([x, y]) => self.bv_binary(x, y, udiv),
+            Marlang::BvUrem([x, y]) => self.bv_binary(x, y, |_, x, y| urem(x, y)),
+            Marlang::BvSdiv([x, y]) => self.bv_binary(x, y, sdiv),
+            Marlang::BvSrem([x, y]) => self.bv_binary(x, y, srem),
+            Marlang::BvSmod([x, y]) => self.bv_binary(x, y, smod),
+            Marlang::BvShl([x, y]) => self.bv_binary(x, y, |w, x, y| match y.to_u32() {
+                Some(y) if y < w => x << y,
+                _ => Integer::new(),
+            }),
+            Marlang::BvLshr([x, y]) => self.bv_binary(x, y, |w, x, y| match y.to_u32() {
+                Some(y) if y < w => x >> y,
+                _ => Integer::new(),
+            }),
+            Marlang::BvAshr([x, y]) => self.bv_binary(x, y, |w, x, y| {
+                let x = signed(w, x);
+                match y.to_u32() {
+                    Some(y) if y < w => x >> y,
+                    _ if x < 0 => Integer::from(-1),
+                    _ => Integer::new(),
+                }
+            }),
+            Marlang::BvComp([x, y]) => {
+                let (_, x) = self.bv(x)?;
+                let (_, y) = self.bv(y)?;
+                Ok(bv(1, Integer::from(x == y)))
+            }
+            Marlang::BvUlt([x, y]) => self.bv_compare(x, y, false, |o| o == Ordering::Less),
+            Marlang::BvUle([x, y]) => self.bv_compare(x, y, false, |o| o != Ordering::Greater),
+            Marlang::BvUgt([x, y]) => self.bv_compare(x, y, false, |o| o == Ordering::Greater),
+            Marlang::BvUge([x, y]) => self.bv_compare(x, y, false, |o| o != Ordering::Less),
+            Marlang::BvSlt([x, y]) => self.bv_compare(x, y, true, |o| o == Ordering::Less),
+            Marlang::BvSle([x, y]) => self.bv_compare(x, y, true, |o| o != Ordering::Greater),
+            Marlang::BvSgt([x, y]) => self.bv_compare(x, y, true, |o| o == Ordering::Greater),
+            Marlang::BvSge([x, y]) => self.bv_compare(x, y, true, |o| o != Ordering::Less),
+            Marlang::BvConcat([x, y]) => {
+                let (wx, x) = self.bv(x)?;
+                let (wy, y) = self.bv(y)?;
+                Ok(bv(widened(wx.checked_add(wy))?, (x << wy) | y))
+            }
+            Marlang::BvExtract([i, j, x]) => {
+                let i = self.numeral(i)?;
+                let j = self.numeral(j)?;
+                let (w, x) = self.bv(x)?;
+                if j > i || i >= w {
+                    return Err(EvalError::Malformed(format!(
+                        "extract {} {} of a bit-vector of width {}",
+                        i, j, w
+                    )));
+                }
+                Ok(bv(i - j + 1, x >> j))
+            }
+            Marlang::BvZeroExtend([i, x]) => {
+                let i = self.numeral(i)?;
+                let (w, x) = self.bv(x)?;
+                Ok(bv(widened(w.checked_add(i))?, x))
+            }
+            Marlang::BvSignExtend([i, x]) => {
+                let i = self.numeral(i)?;
+                let (w, x) = self.bv(x)?;
+                Ok(bv(widened(w.checked_add(i))?, signed(w, x)))
+            }
+            Marlang::BvRepeat([i, x]) => {
+                let i = self.numeral(i)?;
+                let (w, x) = self.bv(x)?;
+                let width = widened(w.checked_mul(i))?;
+                let mut out = Integer::new();
+                for _ in 0..i {
+                    out = (out << w) | &x;
+                }
+                Ok(bv(width, out))
+            }
+            Marlang::BvRotateLeft([i, x]) => {
+                let (w, x) = self.bv(x)?;
+                let i = self.numeral(i)? % w;
+                Ok(bv(w, (x.clone() << i) | (x >> (w - i))))
+            }
+            Marlang::BvRotateRight([i, x]) => {
+                let (w, x) = self.bv(x)?;
+                let i = self.numeral(i)? % w;
+                Ok(bv(w, (x.clone() >> i) | (x << (w - i))))
+            }
+
+            Marlang::Select([a, i]) => match self.eval(a)? {
+                MarValue::Array(_, default, entries) => {
+                    let i = self.eval(i)?;
+                    Ok(entries
+                        .into_iter()
+                        .rev()
+                        .find(|(j, _)| *j == i)
+                        .map_or(*default, |(_, v)| v))
+                }
+                v => Err(ill_sorted("Array", &v)),
+            },
+            Marlang::Store([a, i, v]) => match self.eval(a)? {
+                MarValue::Array(s, default, mut entries) => {
+                    let i = self.eval(i)?;
+                    let v = self.eval(v)?;
+                    entries.retain(|(j, _)| *j != i);
+                    if v != *default {
+                        entries.push((i, v));
+                        entries.sort();
+                    }
+                    Ok(MarValue::Array(s, default, entries))
+                }
+                v => Err(ill_sorted("Array", &v)),
+            },
+            Marlang::ConstArray([s, v]) => {
+                let sort = sort(self.mexpr, s)?;
+                Ok(MarValue::Array(sort, Box::new(self.eval(v)?), vec![]))
+            }
+
+            Marlang::RmVal([v]) => {
+                let s = self.symbol(v)?;
+                RoundingMode::parse(&s)
+                    .map(MarValue::RoundingMode)
+                    .ok_or_else(|| EvalError::Malformed(format!("rounding mode {}", s)))
+            }
+            Marlang::FpLit([sign, e, m]) => {
+                let (_, sign) = self.bv(sign)?;
+                let (eb, e) = self.bv(e)?;
+                let (mb, m) = self.bv(m)?;
+                let format = fp_format(eb, mb + 1)?;
+                Ok(float(format, format.from_bits(sign == 1, &e, &m)))
+            }
+            Marlang::FpPosZero([e, s]) => Ok(float(self.format(e, s)?, Fp::Zero(false))),
+            Marlang::FpNegZero([e, s]) => Ok(float(self.format(e, s)?, Fp::Zero(true))),
+            Marlang::FpPosInf([e, s]) => Ok(float(self.format(e, s)?, Fp::Infinity(false))),
+            Marlang::FpNegInf([e, s]) => Ok(float(self.format(e, s)?, Fp::Infinity(true))),
+            Marlang::FpNaN([e, s]) => Ok(float(self.format(e, s)?, Fp::NaN)),
+            Marlang::FpAbs([x]) => {
+                let (format, x) = self.float(x)?;
+                Ok(float(format, x.abs()))
+            }
+            Marlang::FpNeg([x]) => {
+                let (format, x) = self.float(x)?;
+                Ok(float(format, x.neg()))
+            }
+            Marlang::FpAdd([rm, x, y]) => self.fp_binary(rm, x, y, FpFormat::add),
+            Marlang::FpSub([rm, x, y]) => self.fp_binary(rm, x, y, FpFormat::sub),
+            Marlang::FpMul([rm, x, y]) => self.fp_binary(rm, x, y, FpFormat::mul),
+            Marlang::FpDiv([rm, x, y]) => self.fp_binary(rm, x, y, FpFormat::div),
+            Marlang::FpFma([rm, x, y, z]) => {
+                let rm = self.rounding_mode(rm)?;
+                let (format, x) = self.float(x)?;
+                let (_, y) = self.float(y)?;
+                let (_, z) = self.float(z)?;
+                Ok(float(format, format.fma(rm, &x, &y, &z)))
+            }
+            Marlang::FpSqrt([rm, x]) => {
+                let rm = self.rounding_mode(rm)?;
+                let (format, x) = self.float(x)?;
+                Ok(float(format, format.sqrt(rm, &x)))
+            }
+            Marlang::FpRoundToIntegral([rm, x]) => {
+                let rm = self.rounding_mode(rm)?;
+                let (format, x) = self.float(x)?;
+                Ok(float(format, format.round_to_integral(rm, &x)))
+            }
+            Marlang::FpRem([x, y]) => {
+                let (format, x) = self.float(x)?;
+                let (_, y) = self.float(y)?;
+                Ok(float(format, format.rem(&x, &y)))
+            }
+            Marlang::FpMin([x, y]) => {
+                let (format, x) = self.float(x)?;
+                let (_, y) = self.float(y)?;
+                Ok(float(format, format.min(&x, &y)))
+            }
+            Marlang::FpMax([x, y]) => {
+                let (format, x) = self.float(x)?;
+                let (_, y) = self.float(y)?;
+                Ok(float(format, format.max(&x, &y)))
+            }
+            Marlang::FpLeq([x, y]) => self.fp_compare(x, y, |o| o != Ordering::Greater),
+            Marlang::FpLt([x, y]) => self.fp_compare(x, y, |o| o == Ordering::Less),
+            Marlang::FpGeq([x, y]) => self.fp_compare(x, y, |o| o != Ordering::Less),
+            Marlang::FpGt([x, y]) => self.fp_compare(x, y, |o| o == Ordering::Greater),
+            Marlang::FpEq([x, y]) => self.fp_compare(x, y, |o| o == Ordering::Equal),
+            Marlang::FpIsNormal([x]) => {
+                let (format, x) = self.float(x)?;
+                Ok(MarValue::Bool(format.is_normal(&x)))
+            }
+            Marlang::FpIsSubnormal([x]) => {
+                let (format, x) = self.float(x)?;
+                Ok(MarValue::Bool(format.is_subnormal(&x)))
+            }
+            Marlang::FpIsZero([x]) => Ok(MarValue::Bool(matches!(self.float(x)?.1, Fp::Zero(_)))),
+            Marlang::FpIsInfinite([x]) => {
+                Ok(MarValue::Bool(matches!(self.float(x)?.1, Fp::Infinity(_))))
+            }
+            Marlang::FpIsNaN([x]) => Ok(MarValue::Bool(self.float(x)?.1.is_nan())),
+            Marlang::FpIsNegative([x]) => Ok(MarValue::Bool(self.float(x)?.1.is_negative())),
+            Marlang::FpIsPositive([x]) => {
+                let (_, x) = self.float(x)?;
+                Ok(MarValue::Bool(!x.is_nan() && !x.is_negative()))
+            }
+            Marlang::FpFromReal([e, s, rm, x]) => {
+                let format = self.format(e, s)?;
+                let rm = self.rounding_mode(rm)?;
+                let x = self.real(x)?;
+                Ok(float(format, format.round(&x, rm)))
+            }
+            Marlang::FpFromBv([e, s, x]) => {
+                let format = self.format(e, s)?;
+                let (w, x) = self.bv(x)?;
+                if w != format.eb + format.sb {
+                    return Err(EvalError::Malformed(format!("to_fp of width {}", w)));
+                }
+                let sign = x.get_bit(w - 1);
+                let exponent = Integer::from(&x >> (format.sb - 1)).keep_bits(format.eb);
+                let significand = x.keep_bits(format.sb - 1);
+                Ok(float(
+                    format,
+                    format.from_bits(sign, &exponent, &significand),
+                ))
+            }
+            Marlang::FpFromSbv([e, s, rm, x]) => {
+                let format = self.format(e, s)?;
+                let rm = self.rounding_mode(rm)?;
+                let (w, x) = self.bv(x)?;
+                Ok(float(format, format.round(&signed(w, x).into(), rm)))
+            }
+            Marlang::FpFromUbv([e, s, rm, x]) => {
+                let format = self.format(e, s)?;
+                let rm = self.rounding_mode(rm)?;
+                let (_, x) = self.bv(x)?;
+                Ok(float(format, format.round(&x.into(), rm)))
+            }
+            Marlang::FpFromFp([e, s, rm, x]) => {
+                let format = self.format(e, s)?;
+                let rm = self.rounding_mode(rm)?;
+                let (_, x) = self.float(x)?;
+                Ok(float(format, format.convert(&x, rm)))
+            }
+            Marlang::FpToUbv([m, rm, x]) => {
+                let m = self.width(m)?;
+                let n = self.fp_to_integer(rm, x)?;
+                if n >= 0 && n.significant_bits() <= m {
+                    Ok(bv(m, n))
+                } else {
+                    Err(EvalError::Undefined("fp.to_ubv out of range".into()))
+                }
+            }
+            Marlang::FpToSbv([m, rm, x]) => {
+                let m = self.width(m)?;
+                let n = self.fp_to_integer(rm, x)?;
+                let bound = Integer::from(1) << (m - 1);
+                if n >= -bound.clone() && n < bound {
+                    Ok(bv(m, n))
+                } else {
+                    Err(EvalError::Undefined("fp.to_sbv out of range".into()))
+                }
+            }
+            Marlang::FpToReal([x]) => match self.float(x)?.1.to_rational() {
+                Some(r) => Ok(MarValue::Real(r)),
+                None => Err(EvalError::Undefined("fp.to_real of a special value".into())),
+            },
+
+            node => Err(EvalError::Unsupported(node.to_string())),
+        }
+    }
+
+    fn call(&mut self, def: MarId, args: Vec<MarValue>) -> EvalResult {
+        match self.mexpr[def].clone() {
+            Marlang::DeclareFun([name, _, _]) | Marlang::DeclareRel([name, _]) => {
+                let name = self.symbol(name)?;
+                if args.is_empty() {
+                    self.global(name)
+                } else {
+                    self.model
+                        .apply(&name, &args)
+                        .cloned()
+                        .ok_or(EvalError::Unbound(name))
+                }
+            }
+            // recursive functions without a definition in the expression are left to the model
+            Marlang::RecFun([name, _, _]) => {
+                let definition = self.mexpr.as_ref().iter().find_map(|node| match node {
+                    Marlang::DefineFunRec([f, params, body]) if *f == def => Some((*params, *body)),
+                    _ => None,
+                });
+                match definition {
+                    Some((params, body)) => {
+                        let mut bindings = vec![];
+                        for (p, v) in decompose_using_expr(self.mexpr, params)
+                            .into_iter()
+                            .zip(args)
+                        {
+                            let name = decompose_using_expr(self.mexpr, p)[0];
+                            bindings.push((self.symbol(name)?, v));
+                        }
+                        if self.depth == MAX_CALL_DEPTH {
+                            let name = self.symbol(name)?;
+                            return Err(EvalError::Unsupported(format!(
+                                "{} calls deeper than {}",
+                                name, MAX_CALL_DEPTH
+                            )));
+                        }
+                        self.depth += 1;
+                        let out = self.with_params(bindings, body);
+                        self.depth -= 1;
+                        out
+                    }
+                    None => {
+                        let name = self.symbol(name)?;
+                        self.model
+                            .apply(&name, &args)
+                            .cloned()
+                            .ok_or(EvalError::Unbound(name))
+                    }
+                }
+            }
+            // functions still to be synthesized are interpreted by the model, like declared ones
+            Marlang::SynthFun([name, _, _, _]) => {
+                let name = self.symbol(name)?;
+                self.model
+                    .apply(&name, &args)
+                    .cloned()
+                    .ok_or(EvalError::Unbound(name))
+            }
+            Marlang::DeclareVar([name, _]) => {
+                let name = self.symbol(name)?;
+                self.global(name)
+            }
+            Marlang::DefineFun([_, params, _, body]) => {
+                let mut bindings = vec![];
+                for (p, v) in decompose_using_expr(self.mexpr, params)
+                    .into_iter()
+                    .zip(args)
+                {
+                    let name = decompose_using_expr(self.mexpr, p)[0];
+                    bindings.push((self.symbol(name)?, v));
+                }
+                self.with_params(bindings, body)
+            }
+            Marlang::Constructor([_, name, selectors]) => {
+                let mut fields = vec![];
+                for (s, v) in decompose_using_expr(self.mexpr, selectors)
+                    .into_iter()
+                    .zip(args)
+                {
+                    match self.mexpr[s] {
+                        Marlang::Selector([_, n, _]) => fields.push((self.symbol(n)?, v)),
+                        _ => return Err(EvalError::Malformed("constructor".into())),
+                    }
+                }
+                Ok(MarValue::Datatype(self.symbol(name)?, fields))
+            }
+            Marlang::Selector([_, name, _]) => {
+                let name = self.symbol(name)?;
+                match &args[..] {
+                    [MarValue::Datatype(_, fields)] => fields
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, v)| v.clone())
+                        .ok_or_else(|| {
+                            EvalError::Undefined(format!("{} of other constructor", name))
+                        }),
+                    _ => Err(EvalError::Malformed(format!("selector {}", name))),
+                }
+            }
+            Marlang::Tester([c]) => match (&self.mexpr[c], &args[..]) {
+                (Marlang::Constructor([_, n, _]), [MarValue::Datatype(m, _)]) => {
+                    Ok(MarValue::Bool(self.symbol(*n)? == *m))
+                }
+                _ => Err(EvalError::Malformed("tester".into())),
+            },
+            Marlang::Symbol(s) if args.is_empty() => self.lookup(&s),
+            node => Err(EvalError::Unsupported(format!("call to {}", node))),
+        }
+    }
+
+    fn fp_binary(
+        &mut self,
+        rm: MarId,
+        x: MarId,
+        y: MarId,
+        f: fn(&FpFormat, RoundingMode, &Fp, &Fp) -> Fp,
+    ) -> EvalResult {
+        let rm = self.rounding_mode(rm)?;
+        let (format, x) = self.float(x)?;
+        let (_, y) = self.float(y)?;
+        Ok(float(format, f(&format, rm, &x, &y)))
+    }
+
+    fn fp_compare(&mut self, x: MarId, y: MarId, ok: fn(Ordering) -> bool) -> EvalResult {
+        let (_, x) = self.float(x)?;
+        let (_, y) = self.float(y)?;
+        Ok(MarValue::Bool(x.compare(&y).is_some_and(ok)))
+    }
+
+    fn fp_to_integer(&mut self, rm: MarId, x: MarId) -> Result<Integer, EvalError> {
+        let rm = self.rounding_mode(rm)?;
+        match self.float(x)?.1.to_rational() {
+            Some(r) => Ok(to_integer(&r, rm)),
+            None => Err(EvalError::Undefined("conversion of a special value".into())),
+        }
+    }
+
+    fn ints(&mut self, xs: MarId) -> Result<Vec<Integer>, EvalError> {
+        decompose_using_expr(self.mexpr, xs)
+            .into_iter()
+            .map(|x| self.int(x))
+            .collect()
+    }
+
+    fn reals(&mut self, xs: MarId) -> Result<Vec<Rational>, EvalError> {
+        decompose_using_expr(self.mexpr, xs)
+            .into_iter()
+            .map(|x| self.real(x))
+            .collect()
+    }
+
+    fn chain(&mut self, xs: MarId, ok: fn(Ordering) -> bool) -> EvalResult {
+        let xs = self.list(xs)?;
+        let mut out = true;
+        for p in xs.windows(2) {
+            out &= ok(compare(&p[0], &p[1])?);
+        }
+        Ok(MarValue::Bool(out))
+    }
+
+    fn regexes(&mut self, rs: MarId, unit: Regex, f: fn(Regex, Regex) -> Regex) -> EvalResult {
+        let mut out: Option<Regex> = None;
+        for r in decompose_using_expr(self.mexpr, rs) {
+            let r = self.regex(r)?;
+            out = Some(match out {
+                Some(o) => f(o, r),
+                None => r,
+            });
+        }
+        Ok(MarValue::RegLan(out.unwrap_or(unit)))
+    }
+
+    fn bv_fold(&mut self, xs: MarId, f: fn(Integer, Integer) -> Integer) -> EvalResult {
+        let mut out: Option<(u32, Integer)> = None;
+        for x in decompose_using_expr(self.mexpr, xs) {
+            let (w, x) = self.bv(x)?;
+            out = Some(match out {
+                Some((_, o)) => (w, f(o, x).keep_bits(w)),
+                None => (w, x),
+            });
+        }
+        let (w, x) = out.ok_or_else(|| arity("bit-vector operator"))?;
+        Ok(bv(w, x))
+    }
+
+    fn bv_binary(
+        &mut self,
+        x: MarId,
+        y: MarId,
+        f: fn(u32, Integer, Integer) -> Integer,
+    ) -> EvalResult {
+        let (w, x) = self.bv(x)?;
+        let (_, y) = self.bv(y)?;
+        Ok(bv(w, f(w, x, y)))
+    }
+
+    fn bv_compare(
+        &mut self,
+        x: MarId,
+        y: MarId,
+        signed_compare: bool,
+        ok: fn(Ordering) -> bool,
+    ) -> EvalResult {
+        let (w, x) = self.bv(x)?;
+        let (_, y) = self.bv(y)?;
+        let o = if signed_compare {
+            signed(w, x).cmp(&signed(w, y))
+        } else {
+            x.cmp(&y)
+        };
+        Ok(MarValue::Bool(ok(o)))
+    }
+}
+
+fn compare(x: &MarValue, y: &MarValue) -> Result<Ordering, EvalError> {
+    match (x, y) {
+        (MarValue::Int(x), MarValue::Int(y)) => Ok(x.cmp(y)),
+        (MarValue::Real(x), MarValue::Real(y)) => Ok(x.cmp(y)),
+        (MarValue::Int(x), MarValue::Real(y)) => Ok(Rational::from(x).cmp(y)),
+        (MarValue::Real(x), MarValue::Int(y)) => Ok(x.cmp(&Rational::from(y))),
+        (MarValue::String(x), MarValue::String(y)) => Ok(x.chars().cmp(y.chars())),
+        (x, _) => Err(ill_sorted("a number or a string", x)),
+    }
+}
+
+fn ill_sorted(expected: &str, v: &MarValue) -> EvalError {
+    EvalError::Malformed(format!("expected {} but found {}", expected, v))
+}
+
+fn arity(op: &str) -> EvalError {
+    EvalError::Malformed(format!("{} needs arguments", op))
+}
+
+fn sort(mexpr: &MarRecExpr, id: MarId) -> Result<MarSort, EvalError> {
+    sort_with(mexpr, id, &[])
+}
+
+// Sort aliases are expanded by binding their parameters to the argument sorts
+fn sort_with(
+    mexpr: &MarRecExpr,
+    id: MarId,
+    params: &[(String, MarSort)],
+) -> Result<MarSort, EvalError> {
+    let sort = |id| sort_with(mexpr, id, params);
+    match &mexpr[id] {
+        Marlang::BoolSort => Ok(MarSort::Bool),
+        Marlang::IntSort => Ok(MarSort::Int),
+        Marlang::RealSort => Ok(MarSort::Real),
+        Marlang::StringSort => Ok(MarSort::String),
+        Marlang::RegLanSort => Ok(MarSort::RegLan),
+        Marlang::BitVecSort([w]) => match &mexpr[*w] {
+            Marlang::Symbol(w) => match w.parse() {
+                Ok(0) | Err(_) => Err(EvalError::Malformed(format!("width {}", w))),
+                Ok(w) => Ok(MarSort::BitVec(w)),
+            },
+            _ => Err(EvalError::Malformed("bit-vector sort".into())),
+        },
+        Marlang::ArraySort([i, e]) => Ok(MarSort::Array(Box::new(sort(*i)?), Box::new(sort(*e)?))),
+        Marlang::DatatypeSort([n]) => match &mexpr[*n] {
+            Marlang::Symbol(n) => Ok(MarSort::Datatype(n.clone())),
+            _ => Err(EvalError::Malformed("datatype sort".into())),
+        },
+        Marlang::FpSort([e, s]) => match (&mexpr[*e], &mexpr[*s]) {
+            (Marlang::Symbol(e), Marlang::Symbol(s)) => match (e.parse(), s.parse()) {
+                (Ok(e), Ok(s)) => Ok(MarSort::FloatingPoint(e, s)),
+                _ => Err(EvalError::Malformed(format!("widths {} {}", e, s))),
+            },
+            _ => Err(EvalError::Malformed("floating-point sort".into())),
+        },
+        Marlang::RoundingModeSort => Ok(MarSort::RoundingMode),
+        Marlang::SortApply([def, args]) => {
+            let args = decompose_using_expr(mexpr, *args)
+                .into_iter()
+                .map(sort)
+                .collect::<Result<Vec<_>, _>>()?;
+            match &mexpr[*def] {
+                Marlang::DeclareSort([n, _]) => match &mexpr[*n] {
+                    Marlang::Symbol(n) => Ok(MarSort::Uninterpreted(n.clone(), args)),
+                    _ => Err(EvalError::Malformed("sort declaration".into())),
+                },
+                Marlang::DefineSort([_, ps, body]) => {
+                    let params: Vec<(String, MarSort)> = decompose_using_expr(mexpr, *ps)
+                        .into_iter()
+                        .filter_map(|p| match &mexpr[p] {
+                            Marlang::Symbol(p) => Some(p.clone()),
+                            _ => None,
+                        })
+                        .zip(args)
+                        .collect();
+                    sort_with(mexpr, *body, &params)
+                }
+                _ => Err(EvalError::Malformed("sort application".into())),
+            }
+        }
+        Marlang::Symbol(p) => params
+            .iter()
+            .find(|(q, _)| q == p)
+            .map(|(_, s)| s.clone())
+            .ok_or_else(|| EvalError::Malformed(format!("unknown sort parameter {}", p))),
+        node => Err(EvalError::Malformed(format!("expected a sort: {}", node))),
+    }
+}
+
+fn fp_format(eb: u32, sb: u32) -> Result<FpFormat, EvalError> {
+    if (2..64).contains(&eb) && sb > 1 {
+        Ok(FpFormat::new(eb, sb))
+    } else {
+        Err(EvalError::Malformed(format!(
+            "(_ FloatingPoint {} {})",
+            eb, sb
+        )))
+    }
+}
+
+fn float(format: FpFormat, x: Fp) -> MarValue {
+    MarValue::Float(format.eb, format.sb, x)
+}
+
+fn string(s: Vec<char>) -> MarValue {
+    MarValue::String(s.into_iter().collect())
+}
+
+fn substr(s: &[char], i: &Integer, n: &Integer) -> Vec<char> {
+    match (i.to_usize(), n.to_usize()) {
+        (Some(i), Some(n)) if i < s.len() && n > 0 => s[i..s.len().min(i + n)].to_vec(),
+        _ => vec![],
+    }
+}
+
+fn find(s: &[char], t: &[char], from: usize) -> Option<usize> {
+    (from..=s.len().checked_sub(t.len())?).find(|i| s[*i..].starts_with(t))
+}
+
+fn parse_decimal(s: &str) -> Option<Rational> {
+    if let Some(s) = s.strip_prefix('-') {
+        return parse_decimal(s).map(|r| -r);
+    }
+    if let Some((n, d)) = s.split_once('/') {
+        let d = parse_decimal(d)?;
+        return if d == 0 {
+            None
+        } else {
+            Some(parse_decimal(n)? / d)
+        };
+    }
+    match s.split_once('.') {
+        Some((whole, fraction)) => {
+            let digits: Integer = format!("{}{}", whole, fraction).parse().ok()?;
+            let scale = Integer::from(Integer::u_pow_u(10, fraction.len() as u32));
+            Some(Rational::from((digits, scale)))
+        }
+        None => s.parse().ok(),
+    }
+}
+
+// SMT-LIB div and mod: the remainder is never negative
+fn euclidean(x: Integer, y: Integer) -> Result<(Integer, Integer), EvalError> {
+    if y == 0 {
+        Err(EvalError::Undefined("division by zero".into()))
+    } else {
+        Ok(x.div_rem_euc(y))
+    }
+}
+
+fn widened(w: Option<u32>) -> Result<u32, EvalError> {
+    match w {
+        Some(0) => Err(EvalError::Malformed("a bit-vector of width 0".into())),
+        Some(w) if w <= MAX_BV_WIDTH => Ok(w),
+        Some(w) => Err(EvalError::Unsupported(format!(
+            "a bit-vector of width {}",
+            w
+        ))),
+        None => Err(EvalError::Malformed(
+            "a bit-vector width that overflows".into(),
+        )),
+    }
+}
+
+fn bv(w: u32, x: Integer) -> MarValue {
+    MarValue::BitVec(w, x.keep_bits(w))
+}
+
+fn signed(w: u32, x: Integer) -> Integer {
+    if x.get_bit(w - 1) {
+        x - (Integer::from(1) << w)
+    } else {
+        x
+    }
+}
+
+fn udiv(w: u32, x: Integer, y: Integer) -> Integer {
+    if y == 0 {
+        (Integer::from(1) << w) - 1
+    } else {
+        x / y
+    }
+}
+
+fn urem(x: Integer, y: Integer) -> Integer {
+    if y == 0 {
+        x
+    } else {
+        x % y
+    }
+}
+
+fn sdiv(w: u32, x: Integer, y: Integer) -> Integer {
+    let (x, y) = (signed(w, x), signed(w, y));
+    let q = udiv(w, x.clone().abs(), y.clone().abs());
+    if (x < 0) != (y < 0) {
+        -q
+    } else {
+        q
+    }
+}
+
+fn srem(w: u32, x: Integer, y: Integer) -> Integer {
+    let (x, y) = (signed(w, x), signed(w, y));
+    let r = urem(x.clone().abs(), y.abs());
+    if x < 0 {
+        -r
+    } else {
+        r
+    }
+}
+
+fn smod(w: u32, x: Integer, y: Integer) -> Integer {
+    let (x, y) = (signed(w, x), signed(w, y));
+    let u = urem(x.clone().abs(), y.clone().abs());
+    if u == 0 || (x >= 0 && y >= 0) {
+        u
+    } else if x < 0 && y >= 0 {
+        y - u
+    } else if x >= 0 {
+        u + y
+    } else {
+        -u
+    }
+}
+
+impl Regex {
+    fn concat(r: Regex, s: Regex) -> Regex {
+        match (r, s) {
+            (Regex::None, _) | (_, Regex::None) => Regex::None,
+            (Regex::Str(r), s) if r.is_empty() => s,
+            (r, Regex::Str(s)) if s.is_empty() => r,
+            (r, s) => Regex::Concat(Box::new(r), Box::new(s)),
+        }
+    }
+
+    fn union(r: Regex, s: Regex) -> Regex {
+        match (r, s) {
+            (Regex::None, x) | (x, Regex::None) => x,
+            (r, s) if r == s => r,
+            (r, s) => Regex::Union(Box::new(r), Box::new(s)),
+        }
+    }
+
+    fn inter(r: Regex, s: Regex) -> Regex {
+        match (r, s) {
+            (Regex::None, _) | (_, Regex::None) => Regex::None,
+            (Regex::All, x) | (x, Regex::All) => x,
+            (r, s) if r == s => r,
+            (r, s) => Regex::Inter(Box::new(r), Box::new(s)),
+        }
+    }
+
+    fn nullable(&self) -> bool {
+        match self {
+            Regex::None | Regex::AllChar | Regex::Range(_, _) => false,
+            Regex::All | Regex::Star(_) => true,
+            Regex::Str(s) => s.is_empty(),
+            Regex::Concat(r, s) | Regex::Inter(r, s) => r.nullable() && s.nullable(),
+            Regex::Union(r, s) => r.nullable() || s.nullable(),
+            Regex::Comp(r) => !r.nullable(),
+            Regex::Loop(lo, hi, r) => lo <= hi && (*lo == 0 || r.nullable()),
+        }
+    }
+
+    // The Brzozowski derivative: the words w such that c w matches self
+    fn derive(&self, c: char) -> Regex {
+        match self {
+            Regex::None => Regex::None,
+            Regex::All => Regex::All,
+            Regex::AllChar => Regex::Str(vec![]),
+            Regex::Str(s) => match s.split_first() {
+                Some((d, rest)) if *d == c => Regex::Str(rest.to_vec()),
+                _ => Regex::None,
+            },
+            Regex::Range(lo, hi) if *lo <= c && c <= *hi => Regex::Str(vec![]),
+            Regex::Range(_, _) => Regex::None,
+            Regex::Concat(r, s) => {
+                let left = Regex::concat(r.derive(c), (**s).clone());
+                if r.nullable() {
+                    Regex::union(left, s.derive(c))
+                } else {
+                    left
+                }
+            }
+            Regex::Union(r, s) => Regex::union(r.derive(c), s.derive(c)),
+            Regex::Inter(r, s) => Regex::inter(r.derive(c), s.derive(c)),
+            Regex::Star(r) => Regex::concat(r.derive(c), self.clone()),
+            Regex::Comp(r) => Regex::Comp(Box::new(r.derive(c))),
+            Regex::Loop(lo, hi, r) if lo <= hi && *hi > 0 => {
+                let rest = Regex::Loop(lo.saturating_sub(1), hi - 1, r.clone());
+                Regex::concat(r.derive(c), rest)
+            }
+            Regex::Loop(_, _, _) => Regex::None,
+        }
+    }
+
+    pub fn matches(&self, s: &[char]) -> bool {
+        let mut r = self.clone();
+        for c in s {
+            r = r.derive(*c);
+        }
+        r.nullable()
+    }
+}
+
+impl fmt::Display for MarValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarValue::Bool(b) => write!(f, "{}", b),
+            MarValue::Abstract(n) => write!(f, "{}", n),
+            MarValue::Int(i) if *i < 0 => write!(f, "(- {})", i.clone().abs()),
+            MarValue::Int(i) => write!(f, "{}", i),
+            MarValue::Real(r) => {
+                let (n, d) = r.clone().into_numer_denom();
+                let sign = if n < 0 { "(- " } else { "" };
+                let close = if n < 0 { ")" } else { "" };
+                if d == 1 {
+                    write!(f, "{}{}.0{}", sign, n.abs(), close)
+                } else {
+                    write!(f, "{}(/ {}.0 {}.0){}", sign, n.abs(), d, close)
+                }
+            }
+            MarValue::String(s) => write!(f, "{}", escape_string(s)),
+            MarValue::RegLan(r) => write!(f, "{}", r),
+            MarValue::BitVec(w, v) => write!(f, "#b{:0>width$b}", v, width = *w as usize),
+            MarValue::Float(eb, sb, Fp::NaN) => write!(f, "(_ NaN {} {})", eb, sb),
+            MarValue::Float(eb, sb, x) => {
+                let (sign, e, m) = FpFormat::new(*eb, *sb).to_bits(x);
+                write!(
+                    f,
+                    "(fp #b{} #b{:0>ew$b} #b{:0>mw$b})",
+                    sign as u8,
+                    e,
+                    m,
+                    ew = *eb as usize,
+                    mw = *sb as usize - 1
+                )
+            }
+            MarValue::RoundingMode(rm) => write!(f, "{}", rm.name()),
+            MarValue::Array(s, default, entries) => {
+                let mut out = format!("((as const {}) {})", s, default);
+                for (i, v) in entries {
+                    out = format!("(store {} {} {})", out, i, v);
+                }
+                write!(f, "{}", out)
+            }
+            MarValue::Datatype(c, fields) if fields.is_empty() => write!(f, "{}", c),
+            MarValue::Datatype(c, fields) => {
+                let fields: Vec<String> = fields.iter().map(|(_, v)| v.to_string()).collect();
+                write!(f, "({} {})", c, fields.join(" "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Regex::None => write!(f, "re.none"),
+            Regex::All => write!(f, "re.all"),
+            Regex::AllChar => write!(f, "re.allchar"),
+            Regex::Str(s) => write!(
+                f,
+                "(str.to_re {})",
+                escape_string(&s.iter().collect::<String>())
+            ),
+            Regex::Range(lo, hi) => write!(
+                f,
+                "(re.range {} {})",
+                escape_string(&lo.to_string()),
+                escape_string(&hi.to_string())
+            ),
+            Regex::Concat(r, s) => write!(f, "(re.++ {} {})", r, s),
+            Regex::Union(r, s) => write!(f, "(re.union {} {})", r, s),
+            Regex::Inter(r, s) => write!(f, "(re.inter {} {})", r, s),
+            Regex::Star(r) => write!(f, "(re.* {})", r),
+            Regex::Comp(r) => write!(f, "(re.comp {})", r),
+            Regex::Loop(lo, hi, r) => write!(f, "((_ re.loop {} {}) {})", lo, hi, r),
+        }
+    }
+}
+
+impl fmt::Display for MarSort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarSort::Bool => write!(f, "Bool"),
+            MarSort::Int => write!(f, "Int"),
+            MarSort::Real => write!(f, "Real"),
+            MarSort::String => write!(f, "String"),
+            MarSort::RegLan => write!(f, "RegLan"),
+            MarSort::BitVec(w) => write!(f, "(_ BitVec {})", w),
+            MarSort::Array(i, e) => write!(f, "(Array {} {})", i, e),
+            MarSort::Datatype(n) => write!(f, "{}", n),
+            MarSort::FloatingPoint(e, s) => write!(f, "(_ FloatingPoint {} {})", e, s),
+            MarSort::RoundingMode => write!(f, "RoundingMode"),
+            MarSort::Uninterpreted(n, args) if args.is_empty() => write!(f, "{}", n),
+            MarSort::Uninterpreted(n, args) => {
+                write!(f, "({}", n)?;
+                for a in args {
+                    write!(f, " {}", a)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::Unbound(s) => write!(f, "no value for {}", s),
+            EvalError::Undefined(s) => write!(f, "undefined: {}", s),
+            EvalError::Unsupported(s) => write!(f, "cannot evaluate {}", s),
+            EvalError::Malformed(s) => write!(f, "malformed term: {}", s),
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::cmp::Ordering;
+
+use rug::{Integer, Rational};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RoundingMode {
+    NearestEven,
+    NearestAway,
+    TowardPositive,
+    TowardNegative,
+    TowardZero,
+}
+
+// Zeros and infinities remember their sign; finite values are exact and always representable
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Fp {
+    NaN,
+    Infinity(bool),
+    Zero(bool),
+    Finite(Rational),
+}
+
+// The number of exponent bits and the number of significand bits, hidden bit included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FpFormat {
+    pub eb: u32,
+    pub sb: u32,
+}
+
+impl RoundingMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "RNE" | "roundNearestTiesToEven" => Some(RoundingMode::NearestEven),
+            "RNA" | "roundNearestTiesToAway" => Some(RoundingMode::NearestAway),
+            "RTP" | "roundTowardPositive" => Some(RoundingMode::TowardPositive),
+            "RTN" | "roundTowardNegative" => Some(RoundingMode::TowardNegative),
+            "RTZ" | "roundTowardZero" => Some(RoundingMode::TowardZero),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoundingMode::NearestEven => "RNE",
+            RoundingMode::NearestAway => "RNA",
+            RoundingMode::TowardPositive => "RTP",
+            RoundingMode::TowardNegative => "RTN",
+            RoundingMode::TowardZero => "RTZ",
+        }
+    }
+
+    // Rounds a non-negative magnitude to an integer; the sign matters for the directed modes
+    fn round(&self, m: Rational, negative: bool) -> Integer {
+        let (frac, floor) = m.fract_floor(Integer::new());
+        if frac == 0 {
+            return floor;
+        }
+        let half = Rational::from((1, 2));
+        let up = match self {
+            RoundingMode::NearestEven => frac > half || (frac == half && floor.is_odd()),
+            RoundingMode::NearestAway => frac >= half,
+            RoundingMode::TowardPositive => !negative,
+            RoundingMode::TowardNegative => negative,
+            RoundingMode::TowardZero => false,
+        };
+        if up {
+            floor + 1
+        } else {
+            floor
+        }
+    }
+}
+
+impl Fp {
+    pub fn is_nan(&self) -> bool {
+        matches!(self, Fp::NaN)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Fp::NaN => false,
+            Fp::Infinity(negative) | Fp::Zero(negative) => *negative,
+            Fp::Finite(r) => *r < 0,
+        }
+    }
+
+    pub fn to_rational(&self) -> Option<Rational> {
+        match self {
+            Fp::Zero(_) => Some(Rational::new()),
+            Fp::Finite(r) => Some(r.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn abs(&self) -> Fp {
+        match self {
+            Fp::NaN => Fp::NaN,
+            Fp::Infinity(_) => Fp::Infinity(false),
+            Fp::Zero(_) => Fp::Zero(false),
+            Fp::Finite(r) => Fp::Finite(r.clone().abs()),
+        }
+    }
+
+    pub fn neg(&self) -> Fp {
+        match self {
+            Fp::NaN => Fp::NaN,
+            Fp::Infinity(negative) => Fp::Infinity(!negative),
+            Fp::Zero(negative) => Fp::Zero(!negative),
+            Fp::Finite(r) => Fp::Finite(-r.clone()),
+        }
+    }
+
+    // The IEEE ordering: NaN is unordered and the two zeros are equal
+    pub fn compare(&self, other: &Fp) -> Option<Ordering> {
+        let key = |x: &Fp| match x {
+            Fp::NaN => None,
+            Fp::Infinity(true) => Some((-1, Rational::new())),
+            Fp::Infinity(false) => Some((1, Rational::new())),
+            x => Some((0, x.to_rational()?)),
+        };
+        Some(key(self)?.cmp(&key(other)?))
+    }
+}
+
+impl FpFormat {
+    pub fn new(eb: u32, sb: u32) -> Self {
+        assert!(
+            eb > 1 && sb > 1,
+            "floating-point formats need at least 2 bits for each part"
+        );
+        Self { eb, sb }
+    }
+
+    fn emax(&self) -> i64 {
+        (1 << (self.eb - 1)) - 1
+    }
+
+    fn emin(&self) -> i64 {
+        1 - self.emax()
+    }
+
+    pub fn max_finite(&self) -> Rational {
+        (Rational::from(2) - pow2(1 - self.sb as i64)) * pow2(self.emax())
+    }
+
+    pub fn min_normal(&self) -> Rational {
+        pow2(self.emin())
+    }
+
+    pub fn round(&self, x: &Rational, rm: RoundingMode) -> Fp {
+        if *x == 0 {
+            return Fp::Zero(false);
+        }
+        let negative = *x < 0;
+        let magnitude = x.clone().abs();
+        let e = floor_log2(&magnitude).max(self.emin());
+        let quantum = pow2(e - self.sb as i64 + 1);
+        let m = rm.round(magnitude / &quantum, negative);
+        let v = Rational::from(m) * quantum;
+        if v == 0 {
+            Fp::Zero(negative)
+        } else if v > self.max_finite() {
+            let infinite = match rm {
+                RoundingMode::NearestEven | RoundingMode::NearestAway => true,
+                RoundingMode::TowardPositive => !negative,
+                RoundingMode::TowardNegative => negative,
+                RoundingMode::TowardZero => false,
+            };
+            match (infinite, negative) {
+                (true, _) => Fp::Infinity(negative),
+                (false, true) => Fp::Finite(-self.max_finite()),
+                (false, false) => Fp::Finite(self.max_finite()),
+            }
+        } else if negative {
+            Fp::Finite(-v)
+        } else {
+            Fp::Finite(v)
+        }
+    }
+
+    pub fn from_bits(&self, negative: bool, exponent: &Integer, significand: &Integer) -> Fp {
+        let all_ones = (Integer::from(1) << self.eb) - 1;
+        let value = if *exponent == all_ones {
+            return if *significand == 0 {
+                Fp::Infinity(negative)
+            } else {
+                Fp::NaN
+            };
+        } else if *exponent == 0 {
+            Rational::from(significand) * pow2(self.emin() - self.sb as i64 + 1)
+        } else {
+            let e = exponent.to_i64().unwrap() - self.emax();
+            let hidden = Integer::from(1) << (self.sb - 1);
+            Rational::from(hidden + significand) * pow2(e - self.sb as i64 + 1)
+        };
+        match (value == 0, negative) {
+            (true, _) => Fp::Zero(negative),
+            (false, true) => Fp::Finite(-value),
+            (false, false) => Fp::Finite(value),
+        }
+    }
+
+    pub fn to_bits(&self, x: &Fp) -> (bool, Integer, Integer) {
+        let all_ones = (Integer::from(1) << self.eb) - 1;
+        match x {
+            Fp::NaN => (false, all_ones, Integer::from(1) << (self.sb - 2)),
+            Fp::Infinity(negative) => (*negative, all_ones, Integer::new()),
+            Fp::Zero(negative) => (*negative, Integer::new(), Integer::new()),
+            Fp::Finite(r) => {
+                let magnitude = r.clone().abs();
+                if magnitude < self.min_normal() {
+                    let quantum = pow2(self.emin() - self.sb as i64 + 1);
+                    let significand = (magnitude / quantum).into_numer_denom().0;
+                    (*r < 0, Integer::new(), significand)
+                } else {
+                    let e = floor_log2(&magnitude);
+                    let quantum = pow2(e - self.sb as i64 + 1);
+                    let hidden = Integer::from(1) << (self.sb - 1);
+                    let significand = (magnitude / quantum).into_numer_denom().0 - hidden;
+                    (*r < 0, Integer::from(e + self.emax()), significand)
+                }
+            }
+        }
+    }
+
+    pub fn is_normal(&self, x: &Fp) -> bool {
+        match x {
+            Fp::Finite(r) => r.clone().abs() >= self.min_normal(),
+            _ => false,
+        }
+    }
+
+    pub fn is_subnormal(&self, x: &Fp) -> bool {
+        matches!(x, Fp::Finite(_)) && !self.is_normal(x)
+    }
+
+    pub fn convert(&self, x: &Fp, rm: RoundingMode) -> Fp {
+        match x {
+            Fp::Finite(r) => self.round(r, rm),
+            x => x.clone(),
+        }
+    }
+
+    pub fn add(&self, rm: RoundingMode, x: &Fp, y: &Fp) -> Fp {
+        match (x, y) {
+            (Fp::NaN, _) | (_, Fp::NaN) => Fp::NaN,
+            (Fp::Infinity(a), Fp::Infinity(b)) if a != b => Fp::NaN,
+            (Fp::Infinity(a), _) | (_, Fp::Infinity(a)) => Fp::Infinity(*a),
+            (Fp::Zero(a), Fp::Zero(b)) if a == b => Fp::Zero(*a),
+            _ => {
+                let sum = x.to_rational().unwrap() + y.to_rational().unwrap();
+                self.round_exact(&sum, rm)
+            }
+        }
+    }
+
+    pub fn sub(&self, rm: RoundingMode, x: &Fp, y: &Fp) -> Fp {
+        self.add(rm, x, &y.neg())
+    }
+
+    pub fn mul(&self, rm: RoundingMode, x: &Fp, y: &Fp) -> Fp {
+        let negative = x.is_negative() != y.is_negative();
+        match (x, y) {
+            (Fp::NaN, _) | (_, Fp::NaN) => Fp::NaN,
+            (Fp::Infinity(_), Fp::Zero(_)) | (Fp::Zero(_), Fp::Infinity(_)) => Fp::NaN,
+            (Fp::Infinity(_), _) | (_, Fp::Infinity(_)) => Fp::Infinity(negative),
+            (Fp::Zero(_), _) | (_, Fp::Zero(_)) => Fp::Zero(negative),
+            (Fp::Finite(a), Fp::Finite(b)) => self.round(&Rational::from(a * b), rm),
+        }
+    }
+
+    pub fn div(&self, rm: RoundingMode, x: &Fp, y: &Fp) -> Fp {
+        let negative = x.is_negative() != y.is_negative();
+        match (x, y) {
+            (Fp::NaN, _) | (_, Fp::NaN) => Fp::NaN,
+            (Fp::Infinity(_), Fp::Infinity(_)) | (Fp::Zero(_), Fp::Zero(_)) => Fp::NaN,
+            (Fp::Infinity(_), _) | (_, Fp::Zero(_)) => Fp::Infinity(negative),
+            (_, Fp::Infinity(_)) | (Fp::Zero(_), _) => Fp::Zero(negative),
+            (Fp::Finite(a), Fp::Finite(b)) => self.round(&Rational::from(a / b), rm),
+        }
+    }
+
+    pub fn fma(&self, rm: RoundingMode, x: &Fp, y: &Fp, z: &Fp) -> Fp {
+        let product = match (x, y) {
+            (Fp::Infinity(_), Fp::Zero(_)) | (Fp::Zero(_), Fp::Infinity(_)) => Fp::NaN,
+            (Fp::Infinity(_), _) | (_, Fp::Infinity(_)) => {
+                Fp::Infinity(x.is_negative() != y.is_negative())
+            }
+            _ => Fp::Zero(x.is_negative() != y.is_negative()),
+        };
+        match (x, y, z, &product) {
+            (Fp::NaN, _, _, _) | (_, Fp::NaN, _, _) | (_, _, Fp::NaN, _) | (_, _, _, Fp::NaN) => {
+                Fp::NaN
+            }
+            (_, _, _, Fp::Infinity(_)) => self.add(rm, &product, z),
+            (_, _, Fp::Infinity(a), _) => Fp::Infinity(*a),
+            (Fp::Zero(_), _, Fp::Zero(_), _) | (_, Fp::Zero(_), Fp::Zero(_), _) => {
+                self.add(rm, &product, z)
+            }
+            _ => {
+                let exact =
+                    x.to_rational().unwrap() * y.to_rational().unwrap() + z.to_rational().unwrap();
+                self.round_exact(&exact, rm)
+            }
+        }
+    }
+
+    pub fn sqrt(&self, rm: RoundingMode, x: &Fp) -> Fp {
+        match x {
+            Fp::NaN => Fp::NaN,
+            Fp::Zero(negative) => Fp::Zero(*negative),
+            Fp::Infinity(false) => Fp::Infinity(false),
+            Fp::Infinity(true) => Fp::NaN,
+            Fp::Finite(r) if *r < 0 => Fp::NaN,
+            Fp::Finite(r) => {
+                // every rounding boundary is a multiple of 2^-k, so when the root is irrational
+                // it rounds like any point strictly between two consecutive multiples around it
+                let k = (self.emax() + self.sb as i64 + 2) as u32;
+                let scaled = Rational::from(r * (Integer::from(1) << (2 * k)));
+                let (numer, denom) = scaled.clone().into_numer_denom();
+                let root = (numer / denom).sqrt();
+                let approx = if Integer::from(&root * &root) == scaled {
+                    Rational::from((root, Integer::from(1) << k))
+                } else {
+                    Rational::from((2 * root + 1, Integer::from(1) << (k + 1)))
+                };
+                self.round(&approx, rm)
+            }
+        }
+    }
+
+    pub fn rem(&self, x: &Fp, y: &Fp) -> Fp {
+        match (x, y) {
+            (Fp::NaN, _) | (_, Fp::NaN) | (Fp::Infinity(_), _) | (_, Fp::Zero(_)) => Fp::NaN,
+            (_, Fp::Infinity(_)) | (Fp::Zero(_), _) => x.clone(),
+            (Fp::Finite(a), Fp::Finite(b)) => {
+                let q = Rational::from(a / b);
+                let negative = q < 0;
+                let n = RoundingMode::NearestEven.round(q.abs(), negative);
+                let n = if negative { -n } else { n };
+                let r = a.clone() - b.clone() * n;
+                if r == 0 {
+                    Fp::Zero(x.is_negative())
+                } else {
+                    self.round(&r, RoundingMode::NearestEven)
+                }
+            }
+        }
+    }
+
+    pub fn round_to_integral(&self, rm: RoundingMode, x: &Fp) -> Fp {
+        match x {
+            Fp::Finite(r) => match to_integer(r, rm) {
+                n if n == 0 => Fp::Zero(*r < 0),
+                n => Fp::Finite(n.into()),
+            },
+            x => x.clone(),
+        }
+    }
+
+    pub fn min(&self, x: &Fp, y: &Fp) -> Fp {
+        match x.compare(y) {
+            None if x.is_nan() => y.clone(),
+            None => x.clone(),
+            Some(Ordering::Equal) if x.is_negative() => x.clone(),
+            Some(Ordering::Equal) => y.clone(),
+            Some(Ordering::Less) => x.clone(),
+            Some(Ordering::Greater) => y.clone(),
+        }
+    }
+
+    pub fn max(&self, x: &Fp, y: &Fp) -> Fp {
+        match x.compare(y) {
+            None if x.is_nan() => y.clone(),
+            None => x.clone(),
+            Some(Ordering::Equal) if x.is_negative() => y.clone(),
+            Some(Ordering::Equal) => x.clone(),
+            Some(Ordering::Less) => y.clone(),
+            Some(Ordering::Greater) => x.clone(),
+        }
+    }
+
+    // An exact result of zero from operands that are not both zero is +0, or -0 when rounding
+    // toward negative
+    fn round_exact(&self, x: &Rational, rm: RoundingMode) -> Fp {
+        if *x == 0 {
+            Fp::Zero(rm == RoundingMode::TowardNegative)
+        } else {
+            self.round(x, rm)
+        }
+    }
+}
+
+pub fn to_integer(x: &Rational, rm: RoundingMode) -> Integer {
+    let negative = *x < 0;
+    let n = rm.round(x.clone().abs(), negative);
+    if negative {
+        -n
+    } else {
+        n
+    }
+}
+
+fn pow2(e: i64) -> Rational {
+    if e >= 0 {
+        Rational::from(Integer::from(1) << e as u32)
+    } else {
+        Rational::from((1, Integer::from(1) << (-e) as u32))
+    }
+}
+
+// The exponent of the largest power of two no greater than x, which must be positive
+fn floor_log2(x: &Rational) -> i64 {
+    let e = x.numer().significant_bits() as i64 - x.denom().significant_bits() as i64;
+    if *x < pow2(e) {
+        e - 1
+    } else {
+        e
+    }
+}
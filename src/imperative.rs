@@ -0,0 +1,678 @@
+use std::fmt;
+
+use fxhash::FxHashMap as HashMap;
+
+use crate::{ast::MarId, context::MarContext};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceError {
+    pub location: Location,
+    pub message: String,
+}
+
+// A verification condition, valid iff the assertion at its location always holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vc {
+    pub location: Location,
+    pub term: MarId,
+}
+
+// The script declares the inputs, defines every SSA version and then checks each verification
+// condition in its own scope, in the order of `vcs`
+pub struct Compiled {
+    pub context: MarContext,
+    pub vcs: Vec<Vc>,
+}
+
+pub fn compile(source: &str) -> Result<Compiled, SourceError> {
+    let tokens = lex(source)?;
+    let stmts = Parser { tokens, pos: 0 }.program()?;
+    let mut compiler = Compiler {
+        context: MarContext::new(),
+        vars: HashMap::default(),
+        versions: HashMap::default(),
+        path: vec![],
+        assumptions: vec![],
+        vcs: vec![],
+    };
+    compiler.block(&stmts)?;
+    Ok(Compiled {
+        context: compiler.context,
+        vcs: compiler.vcs,
+    })
+}
+
+fn error<T, S: ToString>(location: Location, message: S) -> Result<T, SourceError> {
+    Err(SourceError {
+        location,
+        message: message.to_string(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(String),
+    Real(String),
+    Punct(&'static str),
+    End,
+}
+
+const PUNCTUATION: [&str; 22] = [
+    "==", "!=", "<=", ">=", "&&", "||", "..", "<", ">", "=", "!", "+", "-", "*", "/", "%", "(",
+    ")", "{", "}", ";", ":",
+];
+
+fn lex(source: &str) -> Result<Vec<(Token, Location)>, SourceError> {
+    let mut tokens = vec![];
+    for (l, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let location = Location {
+                line: l + 1,
+                column: i + 1,
+            };
+            let c = chars[i];
+            let rest: String = chars[i..].iter().collect();
+            if c.is_whitespace() {
+                i += 1;
+            } else if rest.starts_with("//") {
+                break;
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), location));
+            } else if c.is_ascii_digit() {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let fraction =
+                    i + 1 < chars.len() && chars[i] == '.' && chars[i + 1].is_ascii_digit();
+                if fraction {
+                    i += 1;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    tokens.push((Token::Real(chars[start..i].iter().collect()), location));
+                } else {
+                    tokens.push((Token::Int(chars[start..i].iter().collect()), location));
+                }
+            } else if let Some(p) = PUNCTUATION.iter().find(|p| rest.starts_with(*p)) {
+                i += p.len();
+                tokens.push((Token::Punct(p), location));
+            } else {
+                return error(location, format!("unexpected character '{}'", c));
+            }
+        }
+    }
+    let end = Location {
+        line: source.lines().count() + 1,
+        column: 1,
+    };
+    tokens.push((Token::End, end));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Int,
+    Bool,
+    Real,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Int(String),
+    Real(String),
+    Bool(bool),
+    Var(String),
+    Unary(&'static str, Box<Located<Expr>>),
+    Binary(&'static str, Box<Located<Expr>>, Box<Located<Expr>>),
+}
+
+type Located<T> = (T, Location);
+
+#[derive(Debug)]
+enum Stmt {
+    Var(String, Type, Option<Located<Expr>>),
+    Assign(String, Located<Expr>),
+    If(Located<Expr>, Vec<Located<Stmt>>, Vec<Located<Stmt>>),
+    For(String, i64, i64, Vec<Located<Stmt>>),
+    Assume(Located<Expr>),
+    Assert(Located<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<(Token, Location)>,
+    pos: usize,
+}
+
+// Binary operators from the loosest to the tightest
+const LEVELS: [&[&str]; 6] = [
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn location(&self) -> Location {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> (Token, Location) {
+        let t = self.tokens[self.pos].clone();
+        if t.0 != Token::End {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.peek(), Token::Punct(q) if *q == p)
+    }
+
+    fn is_keyword(&self, k: &str) -> bool {
+        matches!(self.peek(), Token::Ident(i) if i == k)
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), SourceError> {
+        if self.is_punct(p) {
+            self.next();
+            Ok(())
+        } else {
+            self.unexpected(&format!("'{}'", p))
+        }
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, SourceError> {
+        let found = match self.peek() {
+            Token::Ident(s) | Token::Int(s) | Token::Real(s) => format!("'{}'", s),
+            Token::Punct(p) => format!("'{}'", p),
+            Token::End => "the end of the input".into(),
+        };
+        error(
+            self.location(),
+            format!("expected {} but found {}", expected, found),
+        )
+    }
+
+    fn ident(&mut self) -> Result<String, SourceError> {
+        match self.peek().clone() {
+            Token::Ident(i) if !KEYWORDS.contains(&i.as_str()) => {
+                self.next();
+                Ok(i)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    fn program(mut self) -> Result<Vec<Located<Stmt>>, SourceError> {
+        let mut stmts = vec![];
+        while *self.peek() != Token::End {
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn block(&mut self) -> Result<Vec<Located<Stmt>>, SourceError> {
+        self.expect("{")?;
+        let mut stmts = vec![];
+        while !self.is_punct("}") {
+            if *self.peek() == Token::End {
+                return self.unexpected("'}'");
+            }
+            stmts.push(self.stmt()?);
+        }
+        self.next();
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Located<Stmt>, SourceError> {
+        let location = self.location();
+        let stmt = if self.is_keyword("var") {
+            self.next();
+            let name = self.ident()?;
+            self.expect(":")?;
+            let ty = self.ty()?;
+            let init = if self.is_punct("=") {
+                self.next();
+                Some(self.expr()?)
+            } else {
+                None
+            };
+            self.expect(";")?;
+            Stmt::Var(name, ty, init)
+        } else if self.is_keyword("if") {
+            self.if_stmt()?
+        } else if self.is_keyword("for") {
+            self.next();
+            let name = self.ident()?;
+            if !self.is_keyword("in") {
+                return self.unexpected("'in'");
+            }
+            self.next();
+            let from = self.bound()?;
+            self.expect("..")?;
+            let to = self.bound()?;
+            Stmt::For(name, from, to, self.block()?)
+        } else if self.is_keyword("assume") || self.is_keyword("assert") {
+            let assume = self.is_keyword("assume");
+            self.next();
+            let e = self.expr()?;
+            self.expect(";")?;
+            if assume {
+                Stmt::Assume(e)
+            } else {
+                Stmt::Assert(e)
+            }
+        } else {
+            let name = self.ident()?;
+            self.expect("=")?;
+            let e = self.expr()?;
+            self.expect(";")?;
+            Stmt::Assign(name, e)
+        };
+        Ok((stmt, location))
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt, SourceError> {
+        self.next();
+        let cond = self.expr()?;
+        let then = self.block()?;
+        let otherwise = if self.is_keyword("else") {
+            self.next();
+            if self.is_keyword("if") {
+                let location = self.location();
+                vec![(self.if_stmt()?, location)]
+            } else {
+                self.block()?
+            }
+        } else {
+            vec![]
+        };
+        Ok(Stmt::If(cond, then, otherwise))
+    }
+
+    fn ty(&mut self) -> Result<Type, SourceError> {
+        let ty = match self.peek() {
+            Token::Ident(t) if t == "int" => Type::Int,
+            Token::Ident(t) if t == "bool" => Type::Bool,
+            Token::Ident(t) if t == "real" => Type::Real,
+            _ => return self.unexpected("a type"),
+        };
+        self.next();
+        Ok(ty)
+    }
+
+    fn bound(&mut self) -> Result<i64, SourceError> {
+        let negative = self.is_punct("-");
+        if negative {
+            self.next();
+        }
+        let location = self.location();
+        match self.next().0 {
+            Token::Int(i) => match i.parse::<i64>() {
+                Ok(i) if negative => Ok(-i),
+                Ok(i) => Ok(i),
+                Err(_) => error(location, format!("loop bound {} is too large", i)),
+            },
+            _ => {
+                self.pos -= 1;
+                self.unexpected("a constant loop bound")
+            }
+        }
+    }
+
+    fn expr(&mut self) -> Result<Located<Expr>, SourceError> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Located<Expr>, SourceError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = LEVELS[level].iter().find(|op| self.is_punct(op)) {
+            let location = self.location();
+            self.next();
+            let right = self.binary(level + 1)?;
+            left = (Expr::Binary(op, Box::new(left), Box::new(right)), location);
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Located<Expr>, SourceError> {
+        let location = self.location();
+        for op in ["!", "-"] {
+            if self.is_punct(op) {
+                self.next();
+                let e = self.unary()?;
+                return Ok((Expr::Unary(op, Box::new(e)), location));
+            }
+        }
+        let e = match self.next().0 {
+            Token::Int(i) => Expr::Int(i),
+            Token::Real(r) => Expr::Real(r),
+            Token::Ident(b) if b == "true" || b == "false" => Expr::Bool(b == "true"),
+            Token::Ident(i) if !KEYWORDS.contains(&i.as_str()) => Expr::Var(i),
+            Token::Punct("(") => {
+                let (e, _) = self.expr()?;
+                self.expect(")")?;
+                e
+            }
+            _ => {
+                self.pos -= 1;
+                return self.unexpected("an expression");
+            }
+        };
+        Ok((e, location))
+    }
+}
+
+const KEYWORDS: [&str; 12] = [
+    "var", "if", "else", "for", "in", "assume", "assert", "true", "false", "int", "bool", "real",
+];
+
+struct Variable {
+    ty: Type,
+    term: MarId,
+    // loop counters are constants
+    mutable: bool,
+}
+
+struct Compiler {
+    context: MarContext,
+    vars: HashMap<String, Variable>,
+    versions: HashMap<String, usize>,
+    path: Vec<MarId>,
+    assumptions: Vec<MarId>,
+    vcs: Vec<Vc>,
+}
+
+impl Compiler {
+    fn block(&mut self, stmts: &[Located<Stmt>]) -> Result<(), SourceError> {
+        let outer: Vec<String> = self.vars.keys().cloned().collect();
+        for stmt in stmts {
+            self.stmt(stmt)?;
+        }
+        // declarations end with their block
+        self.vars.retain(|name, _| outer.contains(name));
+        Ok(())
+    }
+
+    fn stmt(&mut self, (stmt, location): &Located<Stmt>) -> Result<(), SourceError> {
+        match stmt {
+            Stmt::Var(name, ty, init) => {
+                if self.vars.contains_key(name) {
+                    return error(*location, format!("{} is already declared", name));
+                }
+                let value = match init {
+                    Some(e) => Some(self.typed(e, *ty)?),
+                    None => None,
+                };
+                let term = self.version(name, *ty, value);
+                self.vars.insert(
+                    name.clone(),
+                    Variable {
+                        ty: *ty,
+                        term,
+                        mutable: true,
+                    },
+                );
+            }
+            Stmt::Assign(name, e) => {
+                let ty = match self.vars.get(name) {
+                    Some(v) if !v.mutable => {
+                        return error(*location, format!("cannot assign to loop counter {}", name))
+                    }
+                    Some(v) => v.ty,
+                    None => return error(*location, format!("{} is not declared", name)),
+                };
+                let value = self.typed(e, ty)?;
+                let term = self.version(name, ty, Some(value));
+                self.vars.get_mut(name).unwrap().term = term;
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let c = self.typed(cond, Type::Bool)?;
+                let before: Vec<(String, MarId)> = self.terms();
+
+                self.path.push(c);
+                self.block(then)?;
+                self.path.pop();
+                let after_then = self.terms();
+                self.restore(&before);
+
+                let not_c = self.context.mk_not(c);
+                self.path.push(not_c);
+                self.block(otherwise)?;
+                self.path.pop();
+
+                for ((name, t), (_, e)) in after_then.into_iter().zip(self.terms()) {
+                    if t != e {
+                        let ty = self.vars[&name].ty;
+                        let merged = self.context.mk_ite(c, t, e);
+                        let term = self.version(&name, ty, Some(merged));
+                        self.vars.get_mut(&name).unwrap().term = term;
+                    }
+                }
+            }
+            Stmt::For(name, from, to, body) => {
+                if self.vars.contains_key(name) {
+                    return error(*location, format!("{} is already declared", name));
+                }
+                for i in *from..*to {
+                    let term = self.context.mk_int_val(i);
+                    self.vars.insert(
+                        name.clone(),
+                        Variable {
+                            ty: Type::Int,
+                            term,
+                            mutable: false,
+                        },
+                    );
+                    self.block(body)?;
+                    self.vars.remove(name);
+                }
+            }
+            Stmt::Assume(e) => {
+                let e = self.typed(e, Type::Bool)?;
+                let assumption = self.under_path(e);
+                self.assumptions.push(assumption);
+            }
+            Stmt::Assert(e) => {
+                let e = self.typed(e, Type::Bool)?;
+                let mut premises = self.assumptions.clone();
+                premises.extend(self.path.iter().cloned());
+                let term = match premises.len() {
+                    0 => e,
+                    1 => self.context.mk_implies(premises[0], e),
+                    _ => {
+                        let premises = self.context.mk_and(premises);
+                        self.context.mk_implies(premises, e)
+                    }
+                };
+                self.context.push(1);
+                let negated = self.context.mk_not(term);
+                self.context.assert(negated);
+                self.context.check_sat();
+                self.context.pop(1);
+                self.vcs.push(Vc {
+                    location: *location,
+                    term,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // The current terms of the variables, in a fixed order
+    fn terms(&self) -> Vec<(String, MarId)> {
+        let mut terms: Vec<(String, MarId)> = self
+            .vars
+            .iter()
+            .map(|(name, v)| (name.clone(), v.term))
+            .collect();
+        terms.sort();
+        terms
+    }
+
+    fn restore(&mut self, terms: &[(String, MarId)]) {
+        for (name, term) in terms {
+            self.vars.get_mut(name).unwrap().term = *term;
+        }
+    }
+
+    fn under_path(&mut self, e: MarId) -> MarId {
+        match self.path.len() {
+            0 => e,
+            1 => self.context.mk_implies(self.path[0], e),
+            _ => {
+                let path = self.context.mk_and(self.path.clone());
+                self.context.mk_implies(path, e)
+            }
+        }
+    }
+
+    // Defines the next SSA version of the variable, or declares it if it has no value yet
+    fn version(&mut self, name: &str, ty: Type, value: Option<MarId>) -> MarId {
+        let version = self.versions.entry(name.to_string()).or_insert(0);
+        let ssa = format!("{}@{}", name, version);
+        *version += 1;
+        let sort = match ty {
+            Type::Int => self.context.mk_int_sort(),
+            Type::Bool => self.context.mk_bool_sort(),
+            Type::Real => self.context.mk_real_sort(),
+        };
+        let def = match value {
+            Some(value) => self.context.define_fun(ssa, vec![], sort, value),
+            None => self.context.declare_const(ssa, sort),
+        };
+        let empty = self.context.mk_nil();
+        self.context.mk_call(def, empty)
+    }
+
+    fn typed(&mut self, e: &Located<Expr>, expected: Type) -> Result<MarId, SourceError> {
+        let (term, ty) = self.expr(e)?;
+        if ty != expected {
+            return error(e.1, format!("expected {} but found {}", expected, ty));
+        }
+        Ok(term)
+    }
+
+    fn expr(&mut self, (e, location): &Located<Expr>) -> Result<(MarId, Type), SourceError> {
+        let location = *location;
+        let c = &mut self.context;
+        Ok(match e {
+            Expr::Int(i) => (c.mk_int_val(i), Type::Int),
+            Expr::Real(r) => (c.mk_real_val(r), Type::Real),
+            Expr::Bool(b) => (c.mk_bool_val(*b), Type::Bool),
+            Expr::Var(name) => match self.vars.get(name) {
+                Some(v) => (v.term, v.ty),
+                None => return error(location, format!("{} is not declared", name)),
+            },
+            Expr::Unary("!", x) => {
+                let x = self.typed(x, Type::Bool)?;
+                (self.context.mk_not(x), Type::Bool)
+            }
+            Expr::Unary(_, x) => match self.expr(x)? {
+                (x, Type::Int) => (self.context.mk_int_neg(x), Type::Int),
+                (x, Type::Real) => (self.context.mk_real_neg(x), Type::Real),
+                (_, ty) => return error(x.1, format!("cannot negate a {}", ty)),
+            },
+            Expr::Binary(op @ ("&&" | "||"), x, y) => {
+                let x = self.typed(x, Type::Bool)?;
+                let y = self.typed(y, Type::Bool)?;
+                let c = &mut self.context;
+                let t = if *op == "&&" {
+                    c.mk_and(vec![x, y])
+                } else {
+                    c.mk_or(vec![x, y])
+                };
+                (t, Type::Bool)
+            }
+            Expr::Binary(op, x, y) => {
+                let (x, ty) = self.expr(x)?;
+                let y_location = y.1;
+                let (y, other) = self.expr(y)?;
+                if ty != other {
+                    return error(y_location, format!("expected {} but found {}", ty, other));
+                }
+                self.arithmetic(op, x, y, ty, location)?
+            }
+        })
+    }
+
+    fn arithmetic(
+        &mut self,
+        op: &str,
+        x: MarId,
+        y: MarId,
+        ty: Type,
+        location: Location,
+    ) -> Result<(MarId, Type), SourceError> {
+        let c = &mut self.context;
+        let args = vec![x, y];
+        Ok(match (op, ty) {
+            ("==", _) => (c.mk_eq(args), Type::Bool),
+            ("!=", _) => (c.mk_distinct(args), Type::Bool),
+            (_, Type::Bool) => return error(location, format!("{} does not apply to bool", op)),
+            ("+", Type::Int) => (c.mk_int_add(args), ty),
+            ("-", Type::Int) => (c.mk_int_sub(args), ty),
+            ("*", Type::Int) => (c.mk_int_mul(args), ty),
+            ("/", Type::Int) => (c.mk_int_div(args), ty),
+            ("%", Type::Int) => (c.mk_int_mod(x, y), ty),
+            ("<", Type::Int) => (c.mk_int_lt(args), Type::Bool),
+            ("<=", Type::Int) => (c.mk_int_le(args), Type::Bool),
+            (">", Type::Int) => (c.mk_int_gt(args), Type::Bool),
+            (">=", Type::Int) => (c.mk_int_ge(args), Type::Bool),
+            ("+", _) => (c.mk_real_add(args), ty),
+            ("-", _) => (c.mk_real_sub(args), ty),
+            ("*", _) => (c.mk_real_mul(args), ty),
+            ("/", _) => (c.mk_real_div(args), ty),
+            ("<", _) => (c.mk_real_lt(args), Type::Bool),
+            ("<=", _) => (c.mk_real_le(args), Type::Bool),
+            (">", _) => (c.mk_real_gt(args), Type::Bool),
+            (">=", _) => (c.mk_real_ge(args), Type::Bool),
+            _ => return error(location, format!("{} does not apply to real", op)),
+        })
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Real => write!(f, "real"),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
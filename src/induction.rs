@@ -0,0 +1,150 @@
+use crate::{
+    ast::MarId,
+    context::MarContext,
+    eval::MarModel,
+    solver::{Backend, SatResult},
+    transition::{disjunction, Path, TransitionSystem},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    // the properties hold, shown by induction with the given depth
+    Proved(usize),
+    Counterexample(Vec<MarModel>),
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inductiveness {
+    Inductive,
+    // an initial state that breaks the invariant
+    NotInitial(MarModel),
+    // a state satisfying the invariant and a successor that does not
+    NotInductive(MarModel, MarModel),
+    Unknown,
+}
+
+// Satisfiable iff a property fails within k steps of an initial state
+pub fn base_case(system: &TransitionSystem, k: usize) -> MarContext {
+    system.unroll(k)
+}
+
+// Satisfiable iff there is a loop-free path of k steps where the properties hold until the
+// last state, which breaks them. Steps count as distinct when their states or inputs differ,
+// since properties may read the inputs.
+pub fn inductive_step(system: &TransitionSystem, k: usize) -> MarContext {
+    let mut script = MarContext::new();
+    let path = system.declare_path(&mut script, k);
+    for i in 0..k {
+        let p = system.property_at(&mut script, &path, i);
+        script.assert(p);
+        let next = system.next_at(&mut script, &path, i);
+        script.assert(next);
+    }
+    for j in 1..=k {
+        for i in 0..j {
+            let distinct = distinct_states(system, &mut script, &path, i, j);
+            script.assert(distinct);
+        }
+    }
+    let p = system.property_at(&mut script, &path, k);
+    let bad = script.mk_not(p);
+    script.assert(bad);
+    script.check_sat();
+    script
+}
+
+pub fn k_induction<B: Backend>(
+    system: &TransitionSystem,
+    backend: &mut B,
+    max_k: usize,
+) -> Verdict {
+    for k in 1..=max_k {
+        match backend.check(&mut base_case(system, k - 1)) {
+            SatResult::Sat(model) => {
+                return Verdict::Counterexample(system.decode_trace(&model, k - 1))
+            }
+            SatResult::Unknown => return Verdict::Unknown,
+            SatResult::Unsat => (),
+        }
+        match backend.check(&mut inductive_step(system, k)) {
+            SatResult::Unsat => return Verdict::Proved(k),
+            SatResult::Unknown => return Verdict::Unknown,
+            SatResult::Sat(_) => (),
+        }
+    }
+    Verdict::Unknown
+}
+
+// Satisfiable iff an initial state breaks the invariant
+pub fn initiation(system: &TransitionSystem, invariant: MarId) -> MarContext {
+    let mut script = MarContext::new();
+    let path = system.declare_path(&mut script, 0);
+    let init = system.init_at(&mut script, &path, 0);
+    script.assert(init);
+    let inv = system.at(&mut script, &path, invariant, 0);
+    let bad = script.mk_not(inv);
+    script.assert(bad);
+    script.check_sat();
+    script
+}
+
+// Satisfiable iff a step can leave the invariant
+pub fn consecution(system: &TransitionSystem, invariant: MarId) -> MarContext {
+    let mut script = MarContext::new();
+    let path = system.declare_path(&mut script, 1);
+    let inv = system.at(&mut script, &path, invariant, 0);
+    script.assert(inv);
+    let next = system.next_at(&mut script, &path, 0);
+    script.assert(next);
+    let inv = system.at(&mut script, &path, invariant, 1);
+    let bad = script.mk_not(inv);
+    script.assert(bad);
+    script.check_sat();
+    script
+}
+
+pub fn check_invariant<B: Backend>(
+    system: &TransitionSystem,
+    invariant: MarId,
+    backend: &mut B,
+) -> Inductiveness {
+    match backend.check(&mut initiation(system, invariant)) {
+        SatResult::Sat(model) => {
+            let mut trace = system.decode_trace(&model, 0);
+            return Inductiveness::NotInitial(trace.remove(0));
+        }
+        SatResult::Unknown => return Inductiveness::Unknown,
+        SatResult::Unsat => (),
+    }
+    match backend.check(&mut consecution(system, invariant)) {
+        SatResult::Sat(model) => {
+            let mut trace = system.decode_trace(&model, 1);
+            let after = trace.remove(1);
+            Inductiveness::NotInductive(trace.remove(0), after)
+        }
+        SatResult::Unknown => Inductiveness::Unknown,
+        SatResult::Unsat => Inductiveness::Inductive,
+    }
+}
+
+fn distinct_states(
+    system: &TransitionSystem,
+    script: &mut MarContext,
+    path: &Path,
+    i: usize,
+    j: usize,
+) -> MarId {
+    let empty = script.mk_nil();
+    let differences: Vec<MarId> = system
+        .states()
+        .iter()
+        .chain(&system.inputs())
+        .map(|name| {
+            let x = script.mk_call(path.get(name, i).unwrap(), empty);
+            let y = script.mk_call(path.get(name, j).unwrap(), empty);
+            script.mk_distinct(vec![x, y])
+        })
+        .collect();
+    disjunction(script, differences)
+}
@@ -0,0 +1,406 @@
+use egg::Language;
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+
+use crate::{
+    ast::{symbol, MarId, MarRecExpr, Marlang},
+    context::MarContext,
+    util::{decompose_using_expr, decompose_using_graph},
+};
+
+// Copies terms into another context while substituting let bindings or the arguments of calls
+// to defined functions for the variables they bind. Binders that would capture a free variable
+// of a substituted value get their variable renamed.
+struct Expander<'a> {
+    source: &'a MarContext,
+    target: MarContext,
+    lets: bool,
+    definitions: bool,
+    pending: HashMap<MarId, bool>,
+    memo: HashMap<(MarId, Vec<(String, MarId)>), MarId>,
+    fresh: usize,
+}
+
+impl Expander<'_> {
+    // Whether there is anything left to expand below
+    fn has_pending(&mut self, id: MarId) -> bool {
+        if let Some(b) = self.pending.get(&id) {
+            return *b;
+        }
+        let node = self.source.graph()[id].nodes[0].clone();
+        let b = match &node {
+            Marlang::Let(_) => self.lets,
+            Marlang::Call([def, _]) => self.definitions && self.definition(*def).is_some(),
+            _ => false,
+        } || node.children().iter().any(|c| self.has_pending(*c));
+        self.pending.insert(id, b);
+        b
+    }
+
+    fn definition(&self, def: MarId) -> Option<(MarId, MarId)> {
+        self.source.graph()[def].iter().find_map(|node| match node {
+            Marlang::DefineFun([_, params, _, body]) => Some((*params, *body)),
+            _ => None,
+        })
+    }
+
+    fn expand(&mut self, id: MarId, subst: &[(String, MarId)]) -> MarId {
+        let source = self.source;
+        let egraph = source.graph();
+        let id = egraph.find(id);
+        // only the bindings of variables that are free here can change anything below
+        let subst: Vec<(String, MarId)> = subst
+            .iter()
+            .filter(|(n, _)| egraph[id].data.free.contains(n))
+            .cloned()
+            .collect();
+        if subst.is_empty() && !self.has_pending(id) {
+            return self.target.import(&source.get_expr(id), |_| None);
+        }
+        let key = (id, subst.clone());
+        if let Some(out) = self.memo.get(&key) {
+            return *out;
+        }
+
+        let out = match egraph[id].nodes[0].clone() {
+            Marlang::Symbol(s) => match subst.iter().find(|(n, _)| *n == s) {
+                Some((_, v)) => *v,
+                None => self.target.add(Marlang::Symbol(s)),
+            },
+            Marlang::Call([def, args]) if self.definitions && self.definition(def).is_some() => {
+                let (params, body) = self.definition(def).unwrap();
+                let mut inner = vec![];
+                for (p, a) in decompose_using_graph(egraph, params)
+                    .into_iter()
+                    .zip(decompose_using_graph(egraph, args))
+                {
+                    let name = decompose_using_graph(egraph, p)[0];
+                    let name = symbol(egraph, name).expect("parameters must be symbols");
+                    inner.push((name, self.expand(a, &subst)));
+                }
+                self.expand(body, &inner)
+            }
+            // the values are expanded outside of the bindings, since let binds in parallel
+            Marlang::Let([bindings, body]) if self.lets => {
+                let mut bound = vec![];
+                for b in decompose_using_graph(egraph, bindings) {
+                    if let [name, value] = decompose_using_graph(egraph, b)[..] {
+                        let name = symbol(egraph, name).expect("let must bind symbols");
+                        bound.push((name, self.expand(value, &subst)));
+                    }
+                }
+                let mut inner: Vec<(String, MarId)> = subst
+                    .into_iter()
+                    .filter(|(n, _)| bound.iter().all(|(m, _)| m != n))
+                    .collect();
+                inner.extend(bound);
+                self.expand(body, &inner)
+            }
+            Marlang::Let([bindings, body]) => {
+                let (bindings, body) =
+                    self.binder(bindings, body, &subst, |e, v| e.expand(v, &subst));
+                self.target.add(Marlang::Let([bindings, body]))
+            }
+            Marlang::Forall([vars, body]) => {
+                let (vars, body) = self.binder(vars, body, &subst, copy_sort);
+                self.target.add(Marlang::Forall([vars, body]))
+            }
+            Marlang::Exists([vars, body]) => {
+                let (vars, body) = self.binder(vars, body, &subst, copy_sort);
+                self.target.add(Marlang::Exists([vars, body]))
+            }
+            Marlang::DefineFun([f, vars, sort, body]) => {
+                let f = self.expand(f, &[]);
+                let sort = self.expand(sort, &[]);
+                let (vars, body) = self.binder(vars, body, &subst, copy_sort);
+                self.target.add(Marlang::DefineFun([f, vars, sort, body]))
+            }
+            Marlang::DefineFunRec([f, vars, body]) => {
+                let f = self.expand(f, &[]);
+                let (vars, body) = self.binder(vars, body, &subst, copy_sort);
+                self.target.add(Marlang::DefineFunRec([f, vars, body]))
+            }
+            node => {
+                let node = node.map_children(|c| self.expand(c, &subst));
+                self.target.add(node)
+            }
+        };
+        self.memo.insert(key, out);
+        out
+    }
+
+    // Variables come in pairs of a name and a sort, or a value for lets, which `second` copies
+    fn binder(
+        &mut self,
+        vars: MarId,
+        body: MarId,
+        subst: &[(String, MarId)],
+        second: impl Fn(&mut Self, MarId) -> MarId,
+    ) -> (MarId, MarId) {
+        let source = self.source;
+        let egraph = source.graph();
+        let vars = decompose_using_graph(egraph, vars);
+        let names: Vec<String> = vars
+            .iter()
+            .filter_map(|v| symbol(egraph, decompose_using_graph(egraph, *v)[0]))
+            .collect();
+        let mut inner: Vec<(String, MarId)> = subst
+            .iter()
+            .filter(|(n, _)| !names.contains(n))
+            .cloned()
+            .collect();
+        let mut captured: HashSet<String> = inner
+            .iter()
+            .flat_map(|(_, v)| self.target.graph()[*v].data.free.clone())
+            .collect();
+
+        let mut pairs = vec![];
+        for (v, name) in vars.iter().zip(names) {
+            let sort = decompose_using_graph(egraph, *v)[1];
+            let sort = second(self, sort);
+            let name = if captured.contains(&name) {
+                let fresh = self.fresh_name(&name, &captured, body);
+                captured.insert(fresh.clone());
+                let renamed = self.target.mk_symbol(&fresh);
+                inner.push((name, renamed));
+                fresh
+            } else {
+                name
+            };
+            pairs.push((name, sort));
+        }
+
+        let body = self.expand(body, &inner);
+        let mut list = self.target.mk_nil();
+        for (name, sort) in pairs.into_iter().rev() {
+            let name = self.target.mk_symbol(name);
+            let nil = self.target.mk_nil();
+            let sort = self.target.mk_cons(sort, nil);
+            let pair = self.target.mk_cons(name, sort);
+            list = self.target.mk_cons(pair, list);
+        }
+        (list, body)
+    }
+
+    fn fresh_name(&mut self, name: &str, taken: &HashSet<String>, body: MarId) -> String {
+        let free = &self.source.graph()[body].data.free;
+        loop {
+            self.fresh += 1;
+            let fresh = format!("{}!{}", name, self.fresh);
+            if !taken.contains(&fresh) && !free.contains(&fresh) {
+                return fresh;
+            }
+        }
+    }
+}
+
+fn copy_sort(expander: &mut Expander, sort: MarId) -> MarId {
+    expander.expand(sort, &[])
+}
+
+// Replaces every let with its body, where the bound variables stand for their values
+pub fn expand_lets(script: &MarContext) -> MarContext {
+    expand(script, true, false)
+}
+
+// Replaces every call to a defined function with the body of the definition, where the
+// parameters stand for the arguments. The definitions are dropped, since nothing calls them
+// any more.
+pub fn expand_definitions(script: &MarContext) -> MarContext {
+    expand(script, false, true)
+}
+
+fn expand(script: &MarContext, lets: bool, definitions: bool) -> MarContext {
+    let mut expander = Expander {
+        source: script,
+        target: MarContext::new(),
+        lets,
+        definitions,
+        pending: HashMap::default(),
+        memo: HashMap::default(),
+        fresh: 0,
+    };
+    for command in script.commands() {
+        let defined = script.graph()[command]
+            .iter()
+            .any(|node| matches!(node, Marlang::DefineFun(_)));
+        if definitions && defined {
+            continue;
+        }
+        let c = expander.expand(command, &[]);
+        expander.target.commit(c);
+    }
+    expander.target
+}
+
+// Extracts the script like extract_best, but binds the closed terms that a command uses more
+// than once to fresh variables, so that the printed script grows with the size of the DAG
+// rather than the size of the tree. Terms that mention bound variables are left in place.
+pub fn extract_with_lets(script: &mut MarContext) -> MarRecExpr {
+    let expr = script.extract_best();
+    let egraph = script.graph();
+    let mut classes: Vec<MarId> = vec![];
+    for node in expr.as_ref() {
+        let node = node.clone().map_children(|c| classes[usize::from(c)]);
+        classes.push(
+            egraph
+                .lookup(node)
+                .expect("extracted nodes must be in the e-graph"),
+        );
+    }
+    let taken: HashSet<String> = expr
+        .as_ref()
+        .iter()
+        .filter_map(|node| match node {
+            Marlang::Symbol(s) => Some(s.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let mut sharer = Sharer {
+        expr: &expr,
+        shareable: expr
+            .as_ref()
+            .iter()
+            .zip(&classes)
+            .map(|(node, c)| {
+                egraph[*c].data.sort.is_some()
+                    && egraph[*c].data.free.is_empty()
+                    && !trivial(&expr, node)
+            })
+            .collect(),
+        out: MarRecExpr::default(),
+        taken,
+        fresh: 0,
+    };
+    let root = MarId::from(expr.as_ref().len() - 1);
+    let mut commands = vec![];
+    for command in decompose_using_expr(&expr, root) {
+        let c = match expr[command] {
+            Marlang::Assert([t]) => Marlang::Assert([sharer.term(t)]),
+            Marlang::Constraint([t]) => Marlang::Constraint([sharer.term(t)]),
+            Marlang::Rule([t]) => Marlang::Rule([sharer.term(t)]),
+            Marlang::Query([t]) => Marlang::Query([sharer.term(t)]),
+            Marlang::Minimize([t]) => Marlang::Minimize([sharer.term(t)]),
+            Marlang::Maximize([t]) => Marlang::Maximize([sharer.term(t)]),
+            Marlang::DefineFun([f, ps, s, body]) => {
+                let body = sharer.term(body);
+                let mut memo = HashMap::default();
+                let [f, ps, s] = [f, ps, s].map(|c| sharer.copy(c, &HashMap::default(), &mut memo));
+                Marlang::DefineFun([f, ps, s, body])
+            }
+            _ => {
+                let c = sharer.copy(command, &HashMap::default(), &mut HashMap::default());
+                commands.push(c);
+                continue;
+            }
+        };
+        commands.push(sharer.out.add(c));
+    }
+    let mut list = sharer.out.add(Marlang::Nil);
+    for c in commands.into_iter().rev() {
+        list = sharer.out.add(Marlang::Cons([c, list]));
+    }
+    sharer.out
+}
+
+// Nodes that print no larger than a variable would, like constants and values, or that are not
+// terms at all
+fn trivial(expr: &MarRecExpr, node: &Marlang) -> bool {
+    match node {
+        Marlang::Call([_, args]) => matches!(expr[*args], Marlang::Nil),
+        Marlang::ArraySort(_) | Marlang::SortApply(_) | Marlang::Annotate(_) => true,
+        node => node
+            .children()
+            .iter()
+            .all(|c| matches!(expr[*c], Marlang::Symbol(_))),
+    }
+}
+
+struct Sharer<'a> {
+    expr: &'a MarRecExpr,
+    shareable: Vec<bool>,
+    out: MarRecExpr,
+    taken: HashSet<String>,
+    fresh: usize,
+}
+
+impl Sharer<'_> {
+    // Binds the shared terms below t with one let each, outermost first, so that every value
+    // only refers to variables bound further out
+    fn term(&mut self, t: MarId) -> MarId {
+        let mut uses: HashMap<MarId, usize> = HashMap::default();
+        let mut seen = HashSet::default();
+        let mut stack = vec![t];
+        while let Some(i) = stack.pop() {
+            if !seen.insert(i) {
+                continue;
+            }
+            let children = match &self.expr[i] {
+                Marlang::Call([_, args]) => vec![*args],
+                node => node.children().to_vec(),
+            };
+            for c in children {
+                *uses.entry(c).or_default() += 1;
+                stack.push(c);
+            }
+        }
+        let mut shared: Vec<MarId> = uses
+            .into_iter()
+            .filter(|(i, n)| *n > 1 && self.shareable[usize::from(*i)])
+            .map(|(i, _)| i)
+            .collect();
+        shared.sort();
+
+        let mut names = HashMap::default();
+        let mut bindings = vec![];
+        let mut memo = HashMap::default();
+        for i in shared {
+            let node = self.expr[i].clone();
+            let node = node.map_children(|c| self.copy(c, &names, &mut memo));
+            let value = self.out.add(node);
+            let name = self.fresh_name();
+            let name = self.out.add(Marlang::Symbol(name));
+            names.insert(i, name);
+            bindings.push((name, value));
+        }
+        let mut out = self.copy(t, &names, &mut memo);
+        for (name, value) in bindings.into_iter().rev() {
+            let nil = self.out.add(Marlang::Nil);
+            let value = self.out.add(Marlang::Cons([value, nil]));
+            let binding = self.out.add(Marlang::Cons([name, value]));
+            let nil = self.out.add(Marlang::Nil);
+            let bindings = self.out.add(Marlang::Cons([binding, nil]));
+            out = self.out.add(Marlang::Let([bindings, out]));
+        }
+        out
+    }
+
+    fn copy(
+        &mut self,
+        i: MarId,
+        names: &HashMap<MarId, MarId>,
+        memo: &mut HashMap<MarId, MarId>,
+    ) -> MarId {
+        if let Some(name) = names.get(&i) {
+            return *name;
+        }
+        if let Some(out) = memo.get(&i) {
+            return *out;
+        }
+        let node = self.expr[i].clone();
+        let node = node.map_children(|c| self.copy(c, names, memo));
+        let out = self.out.add(node);
+        memo.insert(i, out);
+        out
+    }
+
+    fn fresh_name(&mut self) -> String {
+        loop {
+            self.fresh += 1;
+            let name = format!("a!{}", self.fresh);
+            if !self.taken.contains(&name) {
+                return name;
+            }
+        }
+    }
+}
@@ -1,3 +1,22 @@
+#![recursion_limit = "512"]
+
 pub mod ast;
 pub mod context;
+pub mod eval;
+pub mod float;
+pub mod imperative;
+pub mod induction;
+pub mod lets;
+pub mod ltl;
+pub mod normal;
+pub mod optimize;
+pub mod partition;
+pub mod passes;
+pub mod recursion;
+pub mod rewrites;
+pub mod slicing;
+pub mod smtlib;
+pub mod solver;
+pub mod synthesis;
+pub mod transition;
 pub mod util;
@@ -0,0 +1,251 @@
+use fxhash::FxHashMap as HashMap;
+
+use crate::{
+    ast::{MarId, Marlang},
+    context::MarContext,
+    eval::MarModel,
+    transition::{conjunction, disjunction, Path, TransitionSystem},
+    util::decompose_using_graph,
+};
+
+#[derive(Clone, Copy)]
+enum Op {
+    Globally,
+    Finally,
+    Next,
+    Until,
+    Release,
+}
+
+// Translates LTL formulas over a k-step path into the bounded semantics of Biere et al.,
+// where the path either has no loop or loops back from step k to some step l
+struct Translation<'a> {
+    system: &'a TransitionSystem,
+    path: &'a Path,
+    k: usize,
+    memo: HashMap<(MarId, usize, Option<usize>, bool), MarId>,
+}
+
+// Satisfiable iff some path of k steps from an initial state satisfies the formula
+pub fn witness(system: &TransitionSystem, formula: MarId, k: usize) -> MarContext {
+    bounded_script(system, formula, k, false)
+}
+
+// Satisfiable iff some path of k steps from an initial state breaks the property, which is
+// how liveness properties get bounded counterexamples
+pub fn counterexample(system: &TransitionSystem, property: MarId, k: usize) -> MarContext {
+    bounded_script(system, property, k, true)
+}
+
+// The constraint that the declared path satisfies the formula at its first step
+pub fn lasso(
+    system: &TransitionSystem,
+    script: &mut MarContext,
+    path: &Path,
+    formula: MarId,
+    k: usize,
+) -> MarId {
+    lasso_with(system, script, path, formula, k, false)
+}
+
+// The step that the last state of a trace can loop back to, if any
+pub fn loop_start(system: &TransitionSystem, trace: &[MarModel]) -> Option<usize> {
+    let last = trace.last()?;
+    (0..trace.len()).find(|l| system.is_transition(last, &trace[*l]) == Ok(true))
+}
+
+fn bounded_script(
+    system: &TransitionSystem,
+    formula: MarId,
+    k: usize,
+    negated: bool,
+) -> MarContext {
+    let mut script = MarContext::new();
+    let path = system.declare_path(&mut script, k);
+    let init = system.init_at(&mut script, &path, 0);
+    script.assert(init);
+    for i in 0..k {
+        let next = system.next_at(&mut script, &path, i);
+        script.assert(next);
+    }
+    let constraint = lasso_with(system, &mut script, &path, formula, k, negated);
+    script.assert(constraint);
+    script.check_sat();
+    script
+}
+
+fn lasso_with(
+    system: &TransitionSystem,
+    script: &mut MarContext,
+    path: &Path,
+    formula: MarId,
+    k: usize,
+    negated: bool,
+) -> MarId {
+    let mut translation = Translation {
+        system,
+        path,
+        k,
+        memo: HashMap::default(),
+    };
+    let mut cases = vec![translation.formula(script, formula, 0, None, negated)];
+    for l in 0..=k {
+        let back = system.next_between(script, path, k, l);
+        let holds = translation.formula(script, formula, 0, Some(l), negated);
+        cases.push(script.mk_and(vec![back, holds]));
+    }
+    disjunction(script, cases)
+}
+
+impl<'a> Translation<'a> {
+    // The formula at step i, pushing negations down to the atoms as it goes
+    fn formula(
+        &mut self,
+        script: &mut MarContext,
+        id: MarId,
+        i: usize,
+        l: Option<usize>,
+        negated: bool,
+    ) -> MarId {
+        if let Some(t) = self.memo.get(&(id, i, l, negated)) {
+            return *t;
+        }
+        let t = self.translate(script, id, i, l, negated);
+        self.memo.insert((id, i, l, negated), t);
+        t
+    }
+
+    fn translate(
+        &mut self,
+        script: &mut MarContext,
+        id: MarId,
+        i: usize,
+        l: Option<usize>,
+        negated: bool,
+    ) -> MarId {
+        let graph = self.system.graph();
+        let node = graph[id].iter().find(|node| {
+            matches!(
+                node,
+                Marlang::Globally(_)
+                    | Marlang::Finally(_)
+                    | Marlang::Next(_)
+                    | Marlang::Until(_)
+                    | Marlang::Release(_)
+                    | Marlang::Not(_)
+                    | Marlang::And(_)
+                    | Marlang::Or(_)
+                    | Marlang::Implies(_)
+            )
+        });
+        let (op, f, g) = match node.cloned() {
+            Some(Marlang::Globally([f])) if negated => (Op::Finally, f, f),
+            Some(Marlang::Globally([f])) => (Op::Globally, f, f),
+            Some(Marlang::Finally([f])) if negated => (Op::Globally, f, f),
+            Some(Marlang::Finally([f])) => (Op::Finally, f, f),
+            Some(Marlang::Next([f])) => (Op::Next, f, f),
+            Some(Marlang::Until([f, g])) if negated => (Op::Release, f, g),
+            Some(Marlang::Until([f, g])) => (Op::Until, f, g),
+            Some(Marlang::Release([f, g])) if negated => (Op::Until, f, g),
+            Some(Marlang::Release([f, g])) => (Op::Release, f, g),
+            Some(Marlang::Not([f])) => return self.formula(script, f, i, l, !negated),
+            Some(Marlang::And([args])) | Some(Marlang::Or([args])) => {
+                let is_and = matches!(node, Some(Marlang::And(_)));
+                let args: Vec<MarId> = decompose_using_graph(graph, args)
+                    .into_iter()
+                    .map(|a| self.formula(script, a, i, l, negated))
+                    .collect();
+                return if is_and != negated {
+                    conjunction(script, args)
+                } else {
+                    disjunction(script, args)
+                };
+            }
+            Some(Marlang::Implies([f, g])) => {
+                let f = self.formula(script, f, i, l, !negated);
+                let g = self.formula(script, g, i, l, negated);
+                return if negated {
+                    conjunction(script, vec![f, g])
+                } else {
+                    disjunction(script, vec![f, g])
+                };
+            }
+            _ => {
+                let t = self.system.at(script, self.path, id, i);
+                return if negated { script.mk_not(t) } else { t };
+            }
+        };
+
+        let k = self.k;
+        match (op, l) {
+            (Op::Globally, None) => script.mk_bool_val(false),
+            (Op::Globally, Some(l)) => {
+                let all: Vec<MarId> = (i.min(l)..=k)
+                    .map(|j| self.formula(script, f, j, Some(l), negated))
+                    .collect();
+                conjunction(script, all)
+            }
+            (Op::Finally, _) => {
+                let start = l.map_or(i, |l| i.min(l));
+                let any: Vec<MarId> = (start..=k)
+                    .map(|j| self.formula(script, f, j, l, negated))
+                    .collect();
+                disjunction(script, any)
+            }
+            (Op::Next, None) if i < k => self.formula(script, f, i + 1, l, negated),
+            (Op::Next, None) => script.mk_bool_val(false),
+            (Op::Next, Some(l)) => {
+                let successor = if i < k { i + 1 } else { l };
+                self.formula(script, f, successor, Some(l), negated)
+            }
+            (Op::Until, _) | (Op::Release, _) => self.until(script, op, (f, g), i, l, negated),
+        }
+    }
+
+    // f U g needs g at some step with f holding before it, while f R g needs g to hold up to
+    // and including a step where f holds, or forever on a loop
+    fn until(
+        &mut self,
+        script: &mut MarContext,
+        op: Op,
+        (f, g): (MarId, MarId),
+        i: usize,
+        l: Option<usize>,
+        negated: bool,
+    ) -> MarId {
+        let k = self.k;
+        // for until the goal is g and the steps before must satisfy f, and the other way around
+        // for release, where the steps up to the goal are included
+        let (goal, before, inclusive) = match op {
+            Op::Until => (g, f, false),
+            _ => (f, g, true),
+        };
+        let mut cases = vec![];
+        for j in i..=k {
+            let mut terms = vec![self.formula(script, goal, j, l, negated)];
+            let end = if inclusive { j + 1 } else { j };
+            for n in i..end {
+                terms.push(self.formula(script, before, n, l, negated));
+            }
+            cases.push(conjunction(script, terms));
+        }
+        if let Some(l) = l {
+            // the goal comes after looping back to a step before i
+            for j in l..i {
+                let mut terms = vec![self.formula(script, goal, j, Some(l), negated)];
+                let end = if inclusive { j + 1 } else { j };
+                for n in (i..=k).chain(l..end) {
+                    terms.push(self.formula(script, before, n, Some(l), negated));
+                }
+                cases.push(conjunction(script, terms));
+            }
+            if inclusive {
+                let all: Vec<MarId> = (i.min(l)..=k)
+                    .map(|n| self.formula(script, before, n, Some(l), negated))
+                    .collect();
+                cases.push(conjunction(script, all));
+            }
+        }
+        disjunction(script, cases)
+    }
+}
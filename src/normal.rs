@@ -0,0 +1,432 @@
+use egg::Language;
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+
+use crate::{
+    ast::{symbol, MarId, Marlang},
+    context::MarContext,
+    util::decompose_using_graph,
+};
+
+// A term of the target that is not a connective, and whether it occurs positively
+type Literal = (MarId, bool);
+
+// The CNF or DNF of an assertion would need more clauses than the limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TooLarge {
+    pub command: MarId,
+    pub clauses: usize,
+}
+
+// One level of a Boolean term of the source under a polarity, where Node is a subterm under a
+// polarity and Atom a term that is not a connective
+enum Shape {
+    All(Vec<Shape>),
+    Any(Vec<Shape>),
+    Node(MarId, bool),
+    Constant(bool),
+    Atom(MarId, bool),
+}
+
+#[derive(Clone, Copy)]
+enum Form {
+    Nnf,
+    Cnf,
+    Dnf,
+    Prenex,
+}
+
+struct Normalizer<'a> {
+    source: &'a MarContext,
+    target: MarContext,
+    limit: usize,
+    nnf: HashMap<(MarId, bool), MarId>,
+    clauses: HashMap<(MarId, bool), Vec<Vec<Literal>>>,
+    atoms: HashMap<MarId, MarId>,
+    taken: HashSet<String>,
+    fresh: usize,
+}
+
+impl Normalizer<'_> {
+    fn shape(&self, id: MarId, positive: bool) -> Shape {
+        let egraph = self.source.graph();
+        let list = |xs: MarId, p: bool| -> Vec<Shape> {
+            decompose_using_graph(egraph, xs)
+                .into_iter()
+                .map(|x| Shape::Node(x, p))
+                .collect()
+        };
+        match &egraph[id].nodes[0] {
+            Marlang::Not([x]) => Shape::Node(*x, !positive),
+            Marlang::And([xs]) => both(positive, list(*xs, positive)),
+            Marlang::Or([xs]) => either(positive, list(*xs, positive)),
+            Marlang::Implies([x, y]) => either(
+                positive,
+                vec![Shape::Node(*x, !positive), Shape::Node(*y, positive)],
+            ),
+            Marlang::Ite([c, x, y]) => Shape::All(vec![
+                Shape::Any(vec![Shape::Node(*c, false), Shape::Node(*x, positive)]),
+                Shape::Any(vec![Shape::Node(*c, true), Shape::Node(*y, positive)]),
+            ]),
+            Marlang::Xor([xs]) => xor(&decompose_using_graph(egraph, *xs), positive),
+            Marlang::BoolVal([v]) => {
+                Shape::Constant((symbol(egraph, *v).as_deref() == Some("true")) == positive)
+            }
+            _ => Shape::Atom(id, positive),
+        }
+    }
+
+    fn import(&mut self, id: MarId) -> MarId {
+        if let Some(out) = self.atoms.get(&id) {
+            return *out;
+        }
+        let out = self.target.import(&self.source.get_expr(id), |_| None);
+        self.atoms.insert(id, out);
+        out
+    }
+
+    fn literal(&mut self, (atom, positive): Literal) -> MarId {
+        if positive {
+            atom
+        } else {
+            self.target.mk_not(atom)
+        }
+    }
+
+    // Negations only remain on atoms. Quantifiers and lets are kept, with their bodies in NNF.
+    fn nnf(&mut self, id: MarId, positive: bool) -> MarId {
+        let id = self.source.graph().find(id);
+        if let Some(out) = self.nnf.get(&(id, positive)) {
+            return *out;
+        }
+        let out = match self.source.graph()[id].nodes[0].clone() {
+            Marlang::Forall([vars, body]) => {
+                let (vars, body) = (self.import(vars), self.nnf(body, positive));
+                self.target.add(if positive {
+                    Marlang::Forall([vars, body])
+                } else {
+                    Marlang::Exists([vars, body])
+                })
+            }
+            Marlang::Exists([vars, body]) => {
+                let (vars, body) = (self.import(vars), self.nnf(body, positive));
+                self.target.add(if positive {
+                    Marlang::Exists([vars, body])
+                } else {
+                    Marlang::Forall([vars, body])
+                })
+            }
+            Marlang::Let([bindings, body]) => {
+                let (bindings, body) = (self.import(bindings), self.nnf(body, positive));
+                self.target.add(Marlang::Let([bindings, body]))
+            }
+            _ => {
+                let shape = self.shape(id, positive);
+                self.build(shape)
+            }
+        };
+        self.nnf.insert((id, positive), out);
+        out
+    }
+
+    fn build(&mut self, shape: Shape) -> MarId {
+        match shape {
+            Shape::All(parts) => {
+                let parts = parts.into_iter().map(|s| self.build(s)).collect();
+                self.target.mk_and(parts)
+            }
+            Shape::Any(parts) => {
+                let parts = parts.into_iter().map(|s| self.build(s)).collect();
+                self.target.mk_or(parts)
+            }
+            Shape::Node(id, positive) => self.nnf(id, positive),
+            Shape::Constant(b) => self.target.mk_bool_val(b),
+            Shape::Atom(id, positive) => {
+                let atom = self.import(id);
+                self.literal((atom, positive))
+            }
+        }
+    }
+
+    // The clauses of the CNF, each sorted and without complementary literals. Quantifiers and
+    // lets are atoms here. Fails with the number of clauses once there are more than the limit.
+    fn clauses(&mut self, id: MarId, positive: bool) -> Result<Vec<Vec<Literal>>, usize> {
+        let id = self.source.graph().find(id);
+        if let Some(out) = self.clauses.get(&(id, positive)) {
+            return Ok(out.clone());
+        }
+        let shape = match self.source.graph()[id].nodes[0] {
+            Marlang::Forall(_) | Marlang::Exists(_) | Marlang::Let(_) => Shape::Atom(id, positive),
+            _ => self.shape(id, positive),
+        };
+        let out = self.distribute(shape)?;
+        self.clauses.insert((id, positive), out.clone());
+        Ok(out)
+    }
+
+    fn distribute(&mut self, shape: Shape) -> Result<Vec<Vec<Literal>>, usize> {
+        match shape {
+            Shape::All(parts) => {
+                let mut out = vec![];
+                for part in parts {
+                    for clause in self.distribute(part)? {
+                        if !out.contains(&clause) {
+                            out.push(clause);
+                        }
+                    }
+                    if out.len() > self.limit {
+                        return Err(out.len());
+                    }
+                }
+                Ok(out)
+            }
+            Shape::Any(parts) => {
+                let mut out = vec![vec![]];
+                for part in parts {
+                    let clauses = self.distribute(part)?;
+                    let mut next = vec![];
+                    for a in &out {
+                        for b in &clauses {
+                            match merge(a, b) {
+                                Some(c) if !next.contains(&c) => next.push(c),
+                                _ => (),
+                            }
+                            if next.len() > self.limit {
+                                return Err(next.len());
+                            }
+                        }
+                    }
+                    out = next;
+                }
+                Ok(out)
+            }
+            Shape::Node(id, positive) => self.clauses(id, positive),
+            Shape::Constant(true) => Ok(vec![]),
+            Shape::Constant(false) => Ok(vec![vec![]]),
+            Shape::Atom(id, positive) => Ok(vec![vec![(self.import(id), positive)]]),
+        }
+    }
+
+    // A conjunction of disjunctions, or the other way around, without singleton connectives
+    fn flatten(&mut self, sets: Vec<Vec<Literal>>, conjunctive: bool) -> MarId {
+        let mut outer = vec![];
+        for set in sets {
+            let mut inner: Vec<MarId> = set.into_iter().map(|l| self.literal(l)).collect();
+            outer.push(match inner.len() {
+                0 => return self.target.mk_bool_val(!conjunctive),
+                1 => inner.pop().unwrap(),
+                _ if conjunctive => self.target.mk_or(inner),
+                _ => self.target.mk_and(inner),
+            });
+        }
+        match outer.len() {
+            0 => self.target.mk_bool_val(conjunctive),
+            1 => outer.pop().unwrap(),
+            _ if conjunctive => self.target.mk_and(outer),
+            _ => self.target.mk_or(outer),
+        }
+    }
+
+    // Pulls the quantifiers of a term of the target in NNF to the front, renaming every bound
+    // variable apart. Returns the prefix, with whether each variable is universal, and the matrix.
+    fn prenex(&mut self, t: MarId) -> (Vec<(bool, String, MarId)>, MarId) {
+        let node = self.target.graph()[t].nodes[0].clone();
+        match node {
+            Marlang::Forall([vars, body]) | Marlang::Exists([vars, body]) => {
+                let universal = matches!(node, Marlang::Forall(_));
+                let mut body = body;
+                let mut prefix = vec![];
+                for v in decompose_using_graph(self.target.graph(), vars) {
+                    let pair = decompose_using_graph(self.target.graph(), v);
+                    let name = symbol(self.target.graph(), pair[0]).expect("must bind symbols");
+                    let fresh = self.fresh_name(&name);
+                    let renamed = self.target.mk_symbol(&fresh);
+                    body = rename(&mut self.target, body, &name, renamed);
+                    prefix.push((universal, fresh, pair[1]));
+                }
+                let (inner, matrix) = self.prenex(body);
+                prefix.extend(inner);
+                (prefix, matrix)
+            }
+            Marlang::And([xs]) | Marlang::Or([xs]) => {
+                let mut prefix = vec![];
+                let mut matrices = vec![];
+                for x in decompose_using_graph(self.target.graph(), xs) {
+                    let (inner, matrix) = self.prenex(x);
+                    prefix.extend(inner);
+                    matrices.push(matrix);
+                }
+                let matrix = match node {
+                    Marlang::And(_) => self.target.mk_and(matrices),
+                    _ => self.target.mk_or(matrices),
+                };
+                (prefix, matrix)
+            }
+            // the renamed variables cannot occur in the values
+            Marlang::Let([bindings, body]) => {
+                let (prefix, matrix) = self.prenex(body);
+                (prefix, self.target.add(Marlang::Let([bindings, matrix])))
+            }
+            _ => (vec![], t),
+        }
+    }
+
+    fn fresh_name(&mut self, name: &str) -> String {
+        loop {
+            self.fresh += 1;
+            let fresh = format!("{}!{}", name, self.fresh);
+            if self.taken.insert(fresh.clone()) {
+                return fresh;
+            }
+        }
+    }
+}
+
+fn both(positive: bool, parts: Vec<Shape>) -> Shape {
+    if positive {
+        Shape::All(parts)
+    } else {
+        Shape::Any(parts)
+    }
+}
+
+fn either(positive: bool, parts: Vec<Shape>) -> Shape {
+    both(!positive, parts)
+}
+
+// xor is left associative, and (xor r x) is (and (or r x) (or (not r) (not x)))
+fn xor(xs: &[MarId], positive: bool) -> Shape {
+    match xs {
+        [] => Shape::Constant(!positive),
+        [x] => Shape::Node(*x, positive),
+        [rest @ .., x] => Shape::All(vec![
+            Shape::Any(vec![xor(rest, true), Shape::Node(*x, positive)]),
+            Shape::Any(vec![xor(rest, false), Shape::Node(*x, !positive)]),
+        ]),
+    }
+}
+
+// The union of two sorted clauses, unless it has both a literal and its complement
+fn merge(a: &[Literal], b: &[Literal]) -> Option<Vec<Literal>> {
+    let mut out: Vec<Literal> = a.iter().chain(b).cloned().collect();
+    out.sort();
+    out.dedup();
+    if out.windows(2).any(|w| w[0].0 == w[1].0) {
+        return None;
+    }
+    Some(out)
+}
+
+// Replaces the free occurrences of a variable with a fresh one. Since nothing else is called
+// that, renaming a binder of the same name along with its body is harmless.
+fn rename(target: &mut MarContext, t: MarId, name: &str, fresh: MarId) -> MarId {
+    if !target.graph()[t].data.free.contains(name) {
+        return t;
+    }
+    match target.graph()[t].nodes[0].clone() {
+        Marlang::Symbol(_) => fresh,
+        node => {
+            let node = node.map_children(|c| rename(target, c, name, fresh));
+            target.add(node)
+        }
+    }
+}
+
+fn normalize(script: &MarContext, form: Form, limit: usize) -> Result<MarContext, TooLarge> {
+    let taken = script
+        .graph()
+        .classes()
+        .flat_map(|class| class.iter())
+        .filter_map(|node| match node {
+            Marlang::Symbol(s) => Some(s.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut normalizer = Normalizer {
+        source: script,
+        target: MarContext::new(),
+        limit,
+        nnf: HashMap::default(),
+        clauses: HashMap::default(),
+        atoms: HashMap::default(),
+        taken,
+        fresh: 0,
+    };
+    for command in script.commands() {
+        let assertion = script.graph()[command].iter().find_map(|node| match node {
+            Marlang::Assert([t]) => Some(*t),
+            _ => None,
+        });
+        let t = match assertion {
+            Some(t) => t,
+            None => {
+                let c = normalizer.import(command);
+                normalizer.target.commit(c);
+                continue;
+            }
+        };
+        let too_large = |clauses| TooLarge { command, clauses };
+        let t = match form {
+            Form::Nnf => normalizer.nnf(t, true),
+            Form::Cnf => {
+                let clauses = normalizer.clauses(t, true).map_err(too_large)?;
+                normalizer.flatten(clauses, true)
+            }
+            // the cubes of a term are the clauses of its negation, with every literal flipped
+            Form::Dnf => {
+                let cubes = normalizer
+                    .clauses(t, false)
+                    .map_err(too_large)?
+                    .into_iter()
+                    .map(|c| c.into_iter().map(|(a, p)| (a, !p)).collect())
+                    .collect();
+                normalizer.flatten(cubes, false)
+            }
+            Form::Prenex => {
+                let t = normalizer.nnf(t, true);
+                let (prefix, mut matrix) = normalizer.prenex(t);
+                // consecutive variables of the same kind share a binder
+                let mut blocks: Vec<(bool, Vec<(String, MarId)>)> = vec![];
+                for (universal, name, sort) in prefix {
+                    match blocks.last_mut() {
+                        Some((u, vars)) if *u == universal => vars.push((name, sort)),
+                        _ => blocks.push((universal, vec![(name, sort)])),
+                    }
+                }
+                for (universal, vars) in blocks.into_iter().rev() {
+                    matrix = if universal {
+                        normalizer.target.mk_forall(vars, matrix)
+                    } else {
+                        normalizer.target.mk_exists(vars, matrix)
+                    };
+                }
+                matrix
+            }
+        };
+        let c = normalizer.target.add(Marlang::Assert([t]));
+        normalizer.target.commit(c);
+    }
+    Ok(normalizer.target)
+}
+
+// Pushes negations down to the atoms of every assertion, through and, or, =>, ite and xor, as
+// well as quantifiers and lets
+pub fn nnf(script: &MarContext) -> MarContext {
+    normalize(script, Form::Nnf, 0).expect("NNF has no size limit")
+}
+
+// Turns every assertion into a conjunction of clauses, with at most `limit` of them for each
+// assertion and along the way
+pub fn cnf(script: &MarContext, limit: usize) -> Result<MarContext, TooLarge> {
+    normalize(script, Form::Cnf, limit)
+}
+
+// Turns every assertion into a disjunction of cubes, with at most `limit` of them
+pub fn dnf(script: &MarContext, limit: usize) -> Result<MarContext, TooLarge> {
+    normalize(script, Form::Dnf, limit)
+}
+
+// Puts every assertion in NNF and then moves its quantifiers to the front. Each quantified
+// variable gets a fresh name, so that none of them capture each other.
+pub fn prenex(script: &MarContext) -> MarContext {
+    normalize(script, Form::Prenex, 0).expect("prenex form has no size limit")
+}
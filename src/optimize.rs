@@ -0,0 +1,150 @@
+use crate::{
+    ast::{MarId, Marlang},
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    solver::{Backend, SatResult},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Optimum {
+    // the best value of the objective and a model reaching it
+    Optimal(MarValue, MarModel),
+    // the best value found before running out of steps, which might still be improved on
+    Interrupted(MarValue, MarModel),
+    Infeasible,
+    Unknown,
+}
+
+// Optimizes the active objectives one after the other, keeping each at its optimum while the
+// later ones are optimized, like the lexicographic mode of z3. Stops at the first objective
+// without an optimum. The search runs on a copy of the active commands, so the script is left
+// as it was.
+pub fn optimize<B: Backend>(
+    script: &MarContext,
+    backend: &mut B,
+    max_steps: usize,
+) -> Vec<Optimum> {
+    let mut scratch = scratch(script);
+    let objectives: Vec<(MarId, bool)> = scratch
+        .objectives()
+        .into_iter()
+        .filter_map(|c| {
+            scratch.graph()[c].iter().find_map(|node| match node {
+                Marlang::Minimize([t]) => Some((*t, true)),
+                Marlang::Maximize([t]) => Some((*t, false)),
+                _ => None,
+            })
+        })
+        .collect();
+
+    let mut optima = vec![];
+    for (objective, minimize) in objectives {
+        let optimum = search(&mut scratch, backend, objective, minimize, max_steps);
+        let value = match &optimum {
+            Optimum::Optimal(value, _) => Some(value_term(&mut scratch, value)),
+            _ => None,
+        };
+        optima.push(optimum);
+        match value {
+            Some(value) => {
+                let fixed = scratch.mk_eq(vec![objective, value]);
+                scratch.assert(fixed);
+            }
+            None => break,
+        }
+    }
+    optima
+}
+
+// Linear search for QF_LRA and QF_LIA: each model found bounds the objective, and the next
+// check asks for a strictly better one until there is none. Like optimize, it works on a copy.
+pub fn improve<B: Backend>(
+    script: &MarContext,
+    backend: &mut B,
+    objective: MarId,
+    minimize: bool,
+    max_steps: usize,
+) -> Optimum {
+    let mut scratch = scratch(script);
+    let objective = scratch.import(&script.get_expr(objective), |_| None);
+    search(&mut scratch, backend, objective, minimize, max_steps)
+}
+
+fn scratch(script: &MarContext) -> MarContext {
+    let mut scratch = MarContext::new();
+    for command in script.active_commands() {
+        let c = scratch.import(&script.get_expr(command), |_| None);
+        scratch.commit(c);
+    }
+    scratch
+}
+
+fn search<B: Backend>(
+    script: &mut MarContext,
+    backend: &mut B,
+    objective: MarId,
+    minimize: bool,
+    max_steps: usize,
+) -> Optimum {
+    script.check_sat();
+    let mut best = match backend.check(script) {
+        SatResult::Sat(model) => model,
+        SatResult::Unsat => return Optimum::Infeasible,
+        SatResult::Unknown => return Optimum::Unknown,
+    };
+    let mut value = match value_of(script, objective, &best) {
+        Some(value) => value,
+        None => return Optimum::Unknown,
+    };
+
+    script.push(1);
+    let mut optimal = false;
+    for _ in 0..max_steps {
+        let bound = value_term(script, &value);
+        let better = match (&value, minimize) {
+            (MarValue::Int(_), true) => script.mk_int_lt(vec![objective, bound]),
+            (MarValue::Int(_), false) => script.mk_int_gt(vec![objective, bound]),
+            (_, true) => script.mk_real_lt(vec![objective, bound]),
+            (_, false) => script.mk_real_gt(vec![objective, bound]),
+        };
+        script.assert(better);
+        script.check_sat();
+        match backend.check(script) {
+            SatResult::Sat(model) => match value_of(script, objective, &model) {
+                Some(v) => {
+                    value = v;
+                    best = model;
+                }
+                None => break,
+            },
+            SatResult::Unsat => {
+                optimal = true;
+                break;
+            }
+            SatResult::Unknown => break,
+        }
+    }
+    script.pop(1);
+
+    if optimal {
+        Optimum::Optimal(value, best)
+    } else {
+        Optimum::Interrupted(value, best)
+    }
+}
+
+fn value_of(script: &MarContext, objective: MarId, model: &MarModel) -> Option<MarValue> {
+    let expr = script.get_expr(objective);
+    match eval(&expr, (expr.as_ref().len() - 1).into(), model) {
+        Ok(v @ MarValue::Int(_)) | Ok(v @ MarValue::Real(_)) => Some(v),
+        _ => None,
+    }
+}
+
+fn value_term(script: &mut MarContext, value: &MarValue) -> MarId {
+    match value {
+        MarValue::Int(i) => script.mk_int_val(i),
+        MarValue::Real(r) => script.mk_real_val(r),
+        v => panic!("objectives must be Int or Real but got {}", v),
+    }
+}
@@ -0,0 +1,105 @@
+use fxhash::FxHashMap as HashMap;
+
+use crate::{
+    ast::{MarId, Marlang},
+    context::MarContext,
+    eval::MarModel,
+    slicing::{slice, Dependencies},
+    solver::{Backend, SatResult},
+};
+
+// Groups the active assertions into components, where two assertions are connected if they
+// depend on the same uninterpreted symbol, directly or through definitions. Each part is the
+// slice of the script for one component, in the order of their first assertions.
+pub fn partition(script: &MarContext) -> Vec<MarContext> {
+    let dependencies = Dependencies::new(script);
+    let assertions: Vec<MarId> = script
+        .active_commands()
+        .into_iter()
+        .filter(|c| {
+            script.graph()[*c]
+                .iter()
+                .any(|node| matches!(node, Marlang::Assert(_)))
+        })
+        .collect();
+
+    let mut parent: Vec<usize> = (0..assertions.len()).collect();
+    let mut owner: HashMap<MarId, usize> = HashMap::default();
+    for (i, a) in assertions.iter().enumerate() {
+        for d in dependencies.cone(&[*a]) {
+            if !uninterpreted(script, d) {
+                continue;
+            }
+            match owner.get(&d) {
+                Some(j) => {
+                    let (ri, rj) = (root(&mut parent, i), root(&mut parent, *j));
+                    parent[ri.max(rj)] = ri.min(rj);
+                }
+                None => {
+                    owner.insert(d, i);
+                }
+            }
+        }
+    }
+
+    let mut components: Vec<(usize, Vec<MarId>)> = vec![];
+    for (i, a) in assertions.iter().enumerate() {
+        let r = root(&mut parent, i);
+        match components.iter_mut().find(|(s, _)| *s == r) {
+            Some((_, component)) => component.push(*a),
+            None => components.push((r, vec![*a])),
+        }
+    }
+    components
+        .into_iter()
+        .map(|(_, component)| slice(script, &component))
+        .collect()
+}
+
+// The parts share no symbols, so their models can simply be put together
+pub fn merge_models(models: Vec<MarModel>) -> MarModel {
+    let mut merged = MarModel::new();
+    for model in models {
+        merged.extend(model);
+    }
+    merged
+}
+
+// The whole script is unsat as soon as one part is, and sat with the merged model if every part
+// is
+pub fn check_parts<B: Backend>(parts: &mut [MarContext], backend: &mut B) -> SatResult {
+    let mut models = vec![];
+    let mut unknown = false;
+    for part in parts.iter_mut() {
+        match backend.check(part) {
+            SatResult::Sat(model) => models.push(model),
+            SatResult::Unsat => return SatResult::Unsat,
+            SatResult::Unknown => unknown = true,
+        }
+    }
+    if unknown {
+        SatResult::Unknown
+    } else {
+        SatResult::Sat(merge_models(models))
+    }
+}
+
+fn uninterpreted(script: &MarContext, command: MarId) -> bool {
+    script.graph()[command].iter().any(|node| {
+        matches!(
+            node,
+            Marlang::DeclareFun(_)
+                | Marlang::DeclareVar(_)
+                | Marlang::DeclareRel(_)
+                | Marlang::SynthFun(_)
+        )
+    })
+}
+
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
@@ -0,0 +1,268 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    ast::{MarRecExpr, MarRewrite},
+    context::MarContext,
+    eval::{eval, EvalError, MarModel, MarValue},
+    lets::{expand_definitions, expand_lets},
+    normal::{cnf, dnf, nnf, prenex},
+    recursion::unfold,
+    slicing::eliminate_dead_declarations,
+    solver::{Backend, SatResult},
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PassStats {
+    // counters particular to the pass, like the number of commands it dropped
+    pub counters: Vec<(String, usize)>,
+}
+
+// A transformation from one script to another that should keep it equisatisfiable
+pub trait Pass {
+    fn name(&self) -> String;
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassReport {
+    pub name: String,
+    pub stats: PassStats,
+    pub time: Duration,
+    // the number of nodes of the extracted script
+    pub size_before: usize,
+    pub size_after: usize,
+    // only known with a checker that decides both scripts
+    pub equisat: Option<bool>,
+}
+
+// Runs passes one after the other. With a checker, both sides of each pass are decided, and a
+// model of the result must not falsify any assertion of the input.
+#[derive(Default)]
+pub struct PassManager<'a> {
+    passes: Vec<Box<dyn Pass + 'a>>,
+    checker: Option<Box<dyn Backend + 'a>>,
+}
+
+impl<'a> PassManager<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pass<P: Pass + 'a>(mut self, pass: P) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn with_checker<B: Backend + 'a>(mut self, checker: B) -> Self {
+        self.checker = Some(Box::new(checker));
+        self
+    }
+
+    pub fn run(&mut self, mut script: MarContext) -> (MarContext, Vec<PassReport>) {
+        let mut reports = vec![];
+        for pass in self.passes.iter_mut() {
+            let size_before = script.extract_best().as_ref().len();
+            let assertions: Vec<_> = script
+                .active_assertions()
+                .into_iter()
+                .map(|a| script.get_expr(a))
+                .collect();
+            let before = self.checker.as_mut().map(|c| c.check(&mut script));
+
+            let start = Instant::now();
+            let (mut out, stats) = pass.run(script);
+            let time = start.elapsed();
+
+            let equisat = match (before, self.checker.as_mut()) {
+                (Some(before), Some(checker)) => match (before, checker.check(&mut out)) {
+                    (SatResult::Unsat, SatResult::Unsat) => Some(true),
+                    (SatResult::Sat(_), SatResult::Sat(model)) => satisfies(&assertions, &model),
+                    (SatResult::Unknown, _) | (_, SatResult::Unknown) => None,
+                    _ => Some(false),
+                },
+                _ => None,
+            };
+            reports.push(PassReport {
+                name: pass.name(),
+                stats,
+                time,
+                size_before,
+                size_after: out.extract_best().as_ref().len(),
+                equisat,
+            });
+            script = out;
+        }
+        (script, reports)
+    }
+}
+
+// Whether the model of the result satisfies the assertions of the input. A model that leaves
+// a constant of the input unbound does not, but assertions the evaluator cannot handle, like
+// quantified ones, leave the answer open.
+fn satisfies(assertions: &[MarRecExpr], model: &MarModel) -> Option<bool> {
+    let mut satisfied = Some(true);
+    for a in assertions {
+        match eval(a, (a.as_ref().len() - 1).into(), model) {
+            Ok(MarValue::Bool(true)) => (),
+            Err(EvalError::Unsupported(_)) => satisfied = None,
+            _ => return Some(false),
+        }
+    }
+    satisfied
+}
+
+// Runs families of rewrites, like rewrites::arrays, along with those that were added to the
+// script. Passes that build a new context do not carry the rewrites of their input over, so they
+// are given here.
+pub struct Simplify {
+    pub rewrites: Vec<fn() -> Vec<MarRewrite>>,
+    pub iter_limit: usize,
+}
+
+impl Pass for Simplify {
+    fn name(&self) -> String {
+        "simplify".into()
+    }
+
+    fn run(&mut self, mut script: MarContext) -> (MarContext, PassStats) {
+        for family in &self.rewrites {
+            script.add_rewrites(family());
+        }
+        let out = script.simplify(self.iter_limit);
+        let classes = out.graph().number_of_classes();
+        let stats = PassStats {
+            counters: vec![("e-classes".into(), classes)],
+        };
+        (out, stats)
+    }
+}
+
+pub struct ExpandLets;
+
+impl Pass for ExpandLets {
+    fn name(&self) -> String {
+        "expand-lets".into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        (expand_lets(&script), PassStats::default())
+    }
+}
+
+pub struct ExpandDefinitions;
+
+impl Pass for ExpandDefinitions {
+    fn name(&self) -> String {
+        "expand-definitions".into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        let out = expand_definitions(&script);
+        let stats = PassStats {
+            counters: vec![(
+                "dropped".into(),
+                script.commands().len() - out.commands().len(),
+            )],
+        };
+        (out, stats)
+    }
+}
+
+pub struct EliminateDeadDeclarations;
+
+impl Pass for EliminateDeadDeclarations {
+    fn name(&self) -> String {
+        "eliminate-dead-declarations".into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        let out = eliminate_dead_declarations(&script);
+        let stats = PassStats {
+            counters: vec![(
+                "dropped".into(),
+                script.commands().len() - out.commands().len(),
+            )],
+        };
+        (out, stats)
+    }
+}
+
+pub struct Unfold {
+    pub depth: usize,
+}
+
+impl Pass for Unfold {
+    fn name(&self) -> String {
+        format!("unfold-{}", self.depth)
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        (unfold(&script, self.depth), PassStats::default())
+    }
+}
+
+pub struct Nnf;
+
+impl Pass for Nnf {
+    fn name(&self) -> String {
+        "nnf".into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        (nnf(&script), PassStats::default())
+    }
+}
+
+// Leaves the script as it is when an assertion would need more than `limit` clauses
+pub struct Cnf {
+    pub limit: usize,
+}
+
+impl Pass for Cnf {
+    fn name(&self) -> String {
+        "cnf".into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        match cnf(&script, self.limit) {
+            Ok(out) => (out, PassStats::default()),
+            Err(e) => too_large(script, e.clauses),
+        }
+    }
+}
+
+pub struct Dnf {
+    pub limit: usize,
+}
+
+impl Pass for Dnf {
+    fn name(&self) -> String {
+        "dnf".into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        match dnf(&script, self.limit) {
+            Ok(out) => (out, PassStats::default()),
+            Err(e) => too_large(script, e.clauses),
+        }
+    }
+}
+
+fn too_large(script: MarContext, clauses: usize) -> (MarContext, PassStats) {
+    let stats = PassStats {
+        counters: vec![("too-large".into(), clauses)],
+    };
+    (script, stats)
+}
+
+pub struct Prenex;
+
+impl Pass for Prenex {
+    fn name(&self) -> String {
+        "prenex".into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        (prenex(&script), PassStats::default())
+    }
+}
@@ -0,0 +1,171 @@
+use egg::Language;
+use fxhash::FxHashMap as HashMap;
+
+use crate::{
+    ast::{symbol, MarId, MarRecExpr, Marlang},
+    context::MarContext,
+    util::{decompose_using_expr, decompose_using_graph},
+};
+
+struct Definition {
+    params: Vec<String>,
+    body: MarRecExpr,
+}
+
+// Copies terms while expanding the calls to recursive functions. Bodies are copied once per
+// function and depth and then shared, since the arguments are bound with a let around them.
+struct Unfolder<'a> {
+    definitions: &'a HashMap<String, Definition>,
+    out: MarRecExpr,
+    memo: HashMap<(String, usize), MarId>,
+}
+
+impl Unfolder<'_> {
+    fn copy(&mut self, expr: &MarRecExpr, depth: usize) -> MarId {
+        let mut ids: Vec<MarId> = vec![];
+        for node in expr.as_ref() {
+            let id = match node {
+                Marlang::Call([def, args]) => match &expr[*def] {
+                    Marlang::RecFun([n, sorts, sort]) => {
+                        let args = ids[usize::from(*args)];
+                        match (&expr[*n], depth) {
+                            (Marlang::Symbol(name), d) if d > 0 => self.expand(name, args, depth),
+                            _ => None,
+                        }
+                        .unwrap_or_else(|| {
+                            let stub = self.out.add(Marlang::DeclareFun([
+                                ids[usize::from(*n)],
+                                ids[usize::from(*sorts)],
+                                ids[usize::from(*sort)],
+                            ]));
+                            self.out.add(Marlang::Call([stub, args]))
+                        })
+                    }
+                    _ => self
+                        .out
+                        .add(node.clone().map_children(|c| ids[usize::from(c)])),
+                },
+                _ => self
+                    .out
+                    .add(node.clone().map_children(|c| ids[usize::from(c)])),
+            };
+            ids.push(id);
+        }
+        *ids.last().expect("cannot unfold an empty expression")
+    }
+
+    fn expand(&mut self, name: &str, args: MarId, depth: usize) -> Option<MarId> {
+        let definition = self.definitions.get(name)?;
+        let key = (name.to_string(), depth - 1);
+        let body = match self.memo.get(&key) {
+            Some(body) => *body,
+            None => {
+                let body = self.copy(&definition.body, depth - 1);
+                self.memo.insert(key, body);
+                body
+            }
+        };
+
+        let args = decompose_using_expr(&self.out, args);
+        let mut bindings = self.out.add(Marlang::Nil);
+        for (p, a) in definition.params.iter().zip(args).rev() {
+            let nil = self.out.add(Marlang::Nil);
+            let value = self.out.add(Marlang::Cons([a, nil]));
+            let p = self.out.add(Marlang::Symbol(p.clone()));
+            let binding = self.out.add(Marlang::Cons([p, value]));
+            bindings = self.out.add(Marlang::Cons([binding, bindings]));
+        }
+        Some(self.out.add(Marlang::Let([bindings, body])))
+    }
+}
+
+// Replaces every call to a recursive function with its body, up to the given depth, and the
+// calls that remain with calls to an uninterpreted function of the same name. The definitions
+// become declarations of these functions, so the result fits quantifier-free logics as long as
+// the rest of the script does.
+pub fn unfold(script: &MarContext, depth: usize) -> MarContext {
+    let mut definitions = HashMap::default();
+    for command in script.commands() {
+        for (name, params, body) in recursive_definitions(script, command) {
+            let definition = Definition {
+                params,
+                body: script.get_expr(body),
+            };
+            definitions.insert(name, definition);
+        }
+    }
+
+    let mut unfolded = MarContext::new();
+    for command in script.commands() {
+        let functions = recursive_functions(script, command);
+        if functions.is_empty() {
+            let mut unfolder = Unfolder {
+                definitions: &definitions,
+                out: MarRecExpr::default(),
+                memo: HashMap::default(),
+            };
+            unfolder.copy(&script.get_expr(command), depth);
+            let c = unfolded.import(&unfolder.out, |_| None);
+            unfolded.commit(c);
+        }
+        for f in functions {
+            let mut header = script.get_expr(f);
+            let root = (header.as_ref().len() - 1).into();
+            if let Marlang::RecFun([n, sorts, sort]) = header[root] {
+                header.add(Marlang::DeclareFun([n, sorts, sort]));
+            }
+            let c = unfolded.import(&header, |_| None);
+            unfolded.commit(c);
+        }
+    }
+    unfolded
+}
+
+// The definitions of a define-fun-rec or define-funs-rec command, in order
+fn definitions_of(script: &MarContext, command: MarId) -> Vec<MarId> {
+    let egraph = script.graph();
+    egraph[command]
+        .iter()
+        .find_map(|node| match node {
+            Marlang::DefineFunRec(_) => Some(vec![command]),
+            Marlang::DefineFunsRec([ds]) => Some(decompose_using_graph(egraph, *ds)),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn recursive_functions(script: &MarContext, command: MarId) -> Vec<MarId> {
+    definitions_of(script, command)
+        .into_iter()
+        .filter_map(|d| {
+            script.graph()[d].iter().find_map(|node| match node {
+                Marlang::DefineFunRec([f, _, _]) => Some(*f),
+                _ => None,
+            })
+        })
+        .collect()
+}
+
+// The name, parameter names and body of each recursive definition of a command
+fn recursive_definitions(script: &MarContext, command: MarId) -> Vec<(String, Vec<String>, MarId)> {
+    let egraph = script.graph();
+    let mut found = vec![];
+    for d in definitions_of(script, command) {
+        for node in egraph[d].iter() {
+            if let Marlang::DefineFunRec([f, params, body]) = node {
+                let name = egraph[*f].iter().find_map(|node| match node {
+                    Marlang::RecFun([n, _, _]) => symbol(egraph, *n),
+                    _ => None,
+                });
+                let params = decompose_using_graph(egraph, *params)
+                    .into_iter()
+                    .filter_map(|p| symbol(egraph, decompose_using_graph(egraph, p)[0]))
+                    .collect();
+                if let Some(name) = name {
+                    found.push((name, params, *body));
+                }
+            }
+        }
+    }
+    found
+}
@@ -0,0 +1,209 @@
+use egg::{rewrite as rw, Applier, ENodeOrVar, Language, Subst, Symbol, Var};
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+
+use crate::{
+    ast::{
+        bound_names, symbol, MarAnalysis, MarGraph, MarId, MarPattern, MarPatternAst, MarRewrite,
+        Marlang,
+    },
+    util::decompose_using_graph,
+};
+
+pub fn binder_safe(name: String, left: MarPattern, right: MarPattern) -> MarRewrite {
+    let applier = BinderSafe {
+        left: left.clone(),
+        right,
+    };
+    MarRewrite::new(name, left, applier).unwrap()
+}
+
+pub fn arrays() -> Vec<MarRewrite> {
+    vec![
+        rw!("array.read-over-write.same";
+            "(marlang.operator.array.select (marlang.operator.array.store ?a ?i ?v) ?i)" => "?v"),
+        rw!("array.read-over-write";
+            "(marlang.operator.array.select (marlang.operator.array.store ?a ?i ?v) ?j)" =>
+            "(marlang.operator.core.ite
+                (marlang.operator.core.= (marlang.meta.cons ?i (marlang.meta.cons ?j marlang.meta.nil)))
+                ?v
+                (marlang.operator.array.select ?a ?j))"),
+        rw!("array.read-over-const";
+            "(marlang.operator.array.select (marlang.operator.array.const ?s ?v) ?i)" => "?v"),
+        rw!("array.write-over-write";
+            "(marlang.operator.array.store (marlang.operator.array.store ?a ?i ?v) ?i ?w)" =>
+            "(marlang.operator.array.store ?a ?i ?w)"),
+        rw!("array.write-of-read";
+            "(marlang.operator.array.store ?a ?i (marlang.operator.array.select ?a ?i))" => "?a"),
+    ]
+}
+
+pub fn datatypes() -> Vec<MarRewrite> {
+    vec![
+        rw!("datatype.selector-of-constructor";
+            "(marlang.function.call ?s (marlang.meta.cons
+                (marlang.function.call (marlang.datatype.constructor ?d ?c ?sels) ?args)
+                marlang.meta.nil))" =>
+            { SelectField { selector: var("?s"), selectors: var("?sels"), args: var("?args") } }),
+        rw!("datatype.tester-of-constructor";
+            "(marlang.function.call (marlang.datatype.tester ?k) (marlang.meta.cons
+                (marlang.function.call ?k ?args)
+                marlang.meta.nil))" => "(marlang.value.bool true)"),
+        rw!("datatype.tester-of-other-constructor";
+            "(marlang.function.call (marlang.datatype.tester (marlang.datatype.constructor ?d ?c ?sels))
+                (marlang.meta.cons
+                    (marlang.function.call (marlang.datatype.constructor ?d ?e ?other) ?args)
+                    marlang.meta.nil))" => "(marlang.value.bool false)"
+            if distinct(var("?c"), var("?e"))),
+    ]
+}
+
+fn var(s: &str) -> Var {
+    s.parse().unwrap()
+}
+
+fn distinct(x: Var, y: Var) -> impl Fn(&mut MarGraph, MarId, &Subst) -> bool {
+    move |egraph, _, subst| egraph.find(subst[x]) != egraph.find(subst[y])
+}
+
+// Unions a selector applied to a constructor with the matching argument of the constructor
+struct SelectField {
+    selector: Var,
+    selectors: Var,
+    args: Var,
+}
+
+impl Applier<Marlang, MarAnalysis> for SelectField {
+    fn apply_one(
+        &self,
+        egraph: &mut MarGraph,
+        eclass: MarId,
+        subst: &Subst,
+        _searcher_ast: Option<&MarPatternAst>,
+        rule_name: Symbol,
+    ) -> Vec<MarId> {
+        let selector = egraph.find(subst[self.selector]);
+        let selectors = decompose_using_graph(egraph, subst[self.selectors]);
+        let args = decompose_using_graph(egraph, subst[self.args]);
+        match selectors.iter().position(|s| egraph.find(*s) == selector) {
+            Some(i) if i < args.len() && egraph.union_trusted(eclass, args[i], rule_name) => {
+                vec![eclass]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+// Applies the right-hand side only if no pattern variable ends up under a binder for one of
+// its free variables that it was not under on the left-hand side (capture), or leaves a
+// binder for one of its free variables (leak).
+struct BinderSafe {
+    left: MarPattern,
+    right: MarPattern,
+}
+
+impl Applier<Marlang, MarAnalysis> for BinderSafe {
+    fn apply_one(
+        &self,
+        egraph: &mut MarGraph,
+        eclass: MarId,
+        subst: &Subst,
+        searcher_ast: Option<&MarPatternAst>,
+        rule_name: Symbol,
+    ) -> Vec<MarId> {
+        let left = scopes(egraph, &self.left.ast, subst);
+        let right = scopes(egraph, &self.right.ast, subst);
+        for (v, inner) in right {
+            let free = &egraph[subst[v]].data.free;
+            for outer in left.get(&v).into_iter().flatten() {
+                for bound in &inner {
+                    if bound.symmetric_difference(outer).any(|n| free.contains(n)) {
+                        return vec![];
+                    }
+                }
+            }
+        }
+        self.right
+            .apply_one(egraph, eclass, subst, searcher_ast, rule_name)
+    }
+
+    fn get_pattern_ast(&self) -> Option<&MarPatternAst> {
+        Some(&self.right.ast)
+    }
+
+    fn vars(&self) -> Vec<Var> {
+        self.right.vars()
+    }
+}
+
+// For every occurrence of every variable in the pattern, the names bound above it
+fn scopes(
+    egraph: &MarGraph,
+    pattern: &MarPatternAst,
+    subst: &Subst,
+) -> HashMap<Var, Vec<HashSet<String>>> {
+    let mut out = HashMap::default();
+    let root = (pattern.as_ref().len() - 1).into();
+    scopes_rec(egraph, pattern, subst, root, &HashSet::default(), &mut out);
+    out
+}
+
+fn scopes_rec(
+    egraph: &MarGraph,
+    pattern: &MarPatternAst,
+    subst: &Subst,
+    id: MarId,
+    bound: &HashSet<String>,
+    out: &mut HashMap<Var, Vec<HashSet<String>>>,
+) {
+    match &pattern[id] {
+        ENodeOrVar::Var(v) => out.entry(*v).or_default().push(bound.clone()),
+        ENodeOrVar::ENode(node) => {
+            let binder = match node {
+                Marlang::Let([vars, body])
+                | Marlang::Forall([vars, body])
+                | Marlang::Exists([vars, body])
+                | Marlang::DefineFun([_, vars, _, body]) => Some((*vars, *body)),
+                _ => None,
+            };
+            for child in node.children() {
+                match binder {
+                    Some((vars, body)) if *child == body => {
+                        let mut inner = bound.clone();
+                        inner.extend(pattern_names(egraph, pattern, subst, vars));
+                        scopes_rec(egraph, pattern, subst, *child, &inner, out)
+                    }
+                    _ => scopes_rec(egraph, pattern, subst, *child, bound, out),
+                }
+            }
+        }
+    }
+}
+
+// The names bound by a list of sorted variables or let bindings, which may be partly a pattern
+fn pattern_names(
+    egraph: &MarGraph,
+    pattern: &MarPatternAst,
+    subst: &Subst,
+    vars: MarId,
+) -> Vec<String> {
+    match &pattern[vars] {
+        ENodeOrVar::Var(v) => bound_names(egraph, subst[*v]),
+        ENodeOrVar::ENode(Marlang::Cons([var, rest])) => {
+            let name = match &pattern[*var] {
+                ENodeOrVar::Var(v) => decompose_using_graph(egraph, subst[*v])
+                    .first()
+                    .and_then(|n| symbol(egraph, *n)),
+                ENodeOrVar::ENode(Marlang::Cons([n, _])) => match &pattern[*n] {
+                    ENodeOrVar::Var(v) => symbol(egraph, subst[*v]),
+                    ENodeOrVar::ENode(Marlang::Symbol(s)) => Some(s.clone()),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let mut out: Vec<String> = name.into_iter().collect();
+            out.extend(pattern_names(egraph, pattern, subst, *rest));
+            out
+        }
+        _ => vec![],
+    }
+}
@@ -0,0 +1,234 @@
+use egg::Language;
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+
+use crate::{
+    ast::{symbol, MarGraph, MarId, Marlang},
+    context::MarContext,
+    util::decompose_using_graph,
+};
+
+// Which declarations and definitions each command of a script refers to. Functions and sorts are
+// referred to by their e-class and datatypes by their name.
+pub struct Dependencies {
+    commands: Vec<MarId>,
+    uses: HashMap<MarId, Vec<MarId>>,
+    names: HashMap<String, MarId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    // introduces names that other commands can use
+    Declaration,
+    // constrains the names it uses, and only matters if it is kept
+    Assertion,
+    // everything else, like check-sat, which stays in every slice
+    Other,
+}
+
+impl Dependencies {
+    pub fn new(script: &MarContext) -> Self {
+        let egraph = script.graph();
+        let commands = script.commands();
+
+        let mut declared: HashMap<MarId, MarId> = HashMap::default();
+        let mut names: HashMap<String, MarId> = HashMap::default();
+        for &c in &commands {
+            for (class, name) in declarations(egraph, c) {
+                declared.insert(class, c);
+                if let Some(name) = name {
+                    names.insert(name, c);
+                }
+            }
+        }
+
+        let mut uses = HashMap::default();
+        for &c in &commands {
+            let mut found = vec![];
+            let mut seen = HashSet::default();
+            let mut stack: Vec<MarId> = egraph[c]
+                .iter()
+                .flat_map(|node| node.children().to_vec())
+                .collect();
+            while let Some(class) = stack.pop() {
+                let class = egraph.find(class);
+                if !seen.insert(class) {
+                    continue;
+                }
+                match declared.get(&class) {
+                    Some(d) if *d != c => {
+                        found.push(*d);
+                        continue;
+                    }
+                    _ => (),
+                }
+                for node in egraph[class].iter() {
+                    if let Marlang::DatatypeSort([n]) | Marlang::Constructor([n, _, _]) = node {
+                        match symbol(egraph, *n).and_then(|n| names.get(&n)) {
+                            Some(d) if *d != c => found.push(*d),
+                            _ => (),
+                        }
+                    }
+                    stack.extend(node.children());
+                }
+            }
+            found.sort();
+            found.dedup();
+            uses.insert(c, found);
+        }
+
+        Self {
+            commands,
+            uses,
+            names,
+        }
+    }
+
+    // The declarations and definitions a command refers to directly
+    pub fn uses(&self, command: MarId) -> &[MarId] {
+        self.uses.get(&command).map(|u| &u[..]).unwrap_or(&[])
+    }
+
+    // The command that declares or defines a name
+    pub fn declaration(&self, name: &str) -> Option<MarId> {
+        self.names.get(name).copied()
+    }
+
+    // The given commands and everything they depend on, transitively
+    pub fn cone(&self, roots: &[MarId]) -> HashSet<MarId> {
+        let mut cone = HashSet::default();
+        let mut stack = roots.to_vec();
+        while let Some(c) = stack.pop() {
+            if cone.insert(c) {
+                stack.extend(self.uses(c));
+            }
+        }
+        cone
+    }
+}
+
+// Drops the declare-fun and define-fun commands that no other command depends on, directly or
+// through other definitions
+pub fn eliminate_dead_declarations(script: &MarContext) -> MarContext {
+    let dependencies = Dependencies::new(script);
+    let roots: Vec<MarId> = dependencies
+        .commands
+        .iter()
+        .filter(|c| kind(script.graph(), **c) != Kind::Declaration)
+        .copied()
+        .collect();
+    let cone = dependencies.cone(&roots);
+    rebuild(script, |c| {
+        let removable = script.graph()[c]
+            .iter()
+            .any(|node| matches!(node, Marlang::DeclareFun(_) | Marlang::DefineFun(_)));
+        !removable || cone.contains(&c)
+    })
+}
+
+// Keeps the given assertions and declarations, what they depend on, and the commands that are
+// neither, like set-logic, push and check-sat. Other assertions and unused declarations go.
+pub fn slice(script: &MarContext, roots: &[MarId]) -> MarContext {
+    let dependencies = Dependencies::new(script);
+    let mut roots = roots.to_vec();
+    roots.extend(
+        dependencies
+            .commands
+            .iter()
+            .filter(|c| kind(script.graph(), **c) == Kind::Other),
+    );
+    let cone = dependencies.cone(&roots);
+    rebuild(script, |c| cone.contains(&c))
+}
+
+fn rebuild(script: &MarContext, keep: impl Fn(MarId) -> bool) -> MarContext {
+    let mut out = MarContext::new();
+    for command in script.commands() {
+        if keep(command) {
+            let c = out.import(&script.get_expr(command), |_| None);
+            out.commit(c);
+        }
+    }
+    out
+}
+
+fn kind(egraph: &MarGraph, command: MarId) -> Kind {
+    match egraph[command].nodes[0] {
+        Marlang::DeclareFun(_)
+        | Marlang::DefineFun(_)
+        | Marlang::DefineFunRec(_)
+        | Marlang::DefineFunsRec(_)
+        | Marlang::DeclareSort(_)
+        | Marlang::DefineSort(_)
+        | Marlang::DeclareDatatype(_)
+        | Marlang::DeclareDatatypes(_)
+        | Marlang::DeclareRel(_)
+        | Marlang::DeclareVar(_)
+        | Marlang::SynthFun(_) => Kind::Declaration,
+        Marlang::Assert(_)
+        | Marlang::Constraint(_)
+        | Marlang::Rule(_)
+        | Marlang::Query(_)
+        | Marlang::Minimize(_)
+        | Marlang::Maximize(_) => Kind::Assertion,
+        _ => Kind::Other,
+    }
+}
+
+// The classes that calls and sorts point to when they refer to what a command declares, with
+// the declared names
+fn declarations(egraph: &MarGraph, command: MarId) -> Vec<(MarId, Option<String>)> {
+    let mut out = vec![];
+    for node in egraph[command].iter() {
+        match node {
+            Marlang::DeclareFun([n, _, _])
+            | Marlang::DefineFun([n, _, _, _])
+            | Marlang::DeclareSort([n, _])
+            | Marlang::DefineSort([n, _, _])
+            | Marlang::DeclareRel([n, _])
+            | Marlang::DeclareVar([n, _])
+            | Marlang::SynthFun([n, _, _, _]) => {
+                out.push((egraph.find(command), symbol(egraph, *n)))
+            }
+            Marlang::DefineFunRec(_) => out.extend(recursive_functions(egraph, command)),
+            Marlang::DefineFunsRec([ds]) => {
+                for d in decompose_using_graph(egraph, *ds) {
+                    out.extend(recursive_functions(egraph, d));
+                }
+            }
+            Marlang::DeclareDatatype([d]) => out.extend(datatype(egraph, *d)),
+            Marlang::DeclareDatatypes([ds]) => {
+                for d in decompose_using_graph(egraph, *ds) {
+                    out.extend(datatype(egraph, d));
+                }
+            }
+            _ => (),
+        }
+    }
+    out
+}
+
+fn recursive_functions(egraph: &MarGraph, definition: MarId) -> Vec<(MarId, Option<String>)> {
+    egraph[definition]
+        .iter()
+        .filter_map(|node| match node {
+            Marlang::DefineFunRec([f, _, _]) => {
+                let name = egraph[*f].iter().find_map(|node| match node {
+                    Marlang::RecFun([n, _, _]) => symbol(egraph, *n),
+                    _ => None,
+                });
+                Some((egraph.find(*f), name))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn datatype(egraph: &MarGraph, datatype: MarId) -> Vec<(MarId, Option<String>)> {
+    egraph[datatype]
+        .iter()
+        .filter_map(|node| match node {
+            Marlang::Datatype([n, _]) => Some((egraph.find(datatype), symbol(egraph, *n))),
+            _ => None,
+        })
+        .collect()
+}
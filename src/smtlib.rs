@@ -0,0 +1,444 @@
+use std::io::{self, Write};
+
+use egg::Language;
+
+use crate::{
+    ast::{MarId, MarRecExpr, Marlang},
+    util::decompose_using_expr,
+};
+
+pub fn write_smtlib<T: Write>(dest: &mut T, mexpr: &MarRecExpr) -> io::Result<()> {
+    let root = mexpr.as_ref().len() - 1;
+    for command in decompose_using_expr(mexpr, root.into()) {
+        writeln!(dest, "{}", to_smtlib(mexpr, command))?;
+    }
+    Ok(())
+}
+
+// Writes Horn clause commands in the HORN logic of CHC solvers, where relations are Boolean
+// functions, rules are assertions and every query asserts that its body cannot hold
+pub fn write_horn<T: Write>(dest: &mut T, mexpr: &MarRecExpr) -> io::Result<()> {
+    let root = mexpr.as_ref().len() - 1;
+    writeln!(dest, "(set-logic HORN)")?;
+    for command in decompose_using_expr(mexpr, root.into()) {
+        match &mexpr[command] {
+            Marlang::SetLogic(_) | Marlang::CheckSat => (),
+            Marlang::DeclareRel([n, ss]) => writeln!(
+                dest,
+                "(declare-fun {} {} Bool)",
+                to_smtlib(mexpr, *n),
+                to_smtlib(mexpr, *ss)
+            )?,
+            Marlang::Rule([c]) => writeln!(dest, "(assert {})", to_smtlib(mexpr, *c))?,
+            Marlang::Query([q]) => match &mexpr[*q] {
+                Marlang::Exists([vars, body]) => writeln!(
+                    dest,
+                    "(assert (forall ({}) (=> {} false)))",
+                    sorted_vars(mexpr, *vars),
+                    to_smtlib(mexpr, *body)
+                )?,
+                _ => writeln!(dest, "(assert (=> {} false))", to_smtlib(mexpr, *q))?,
+            },
+            _ => writeln!(dest, "{}", to_smtlib(mexpr, command))?,
+        }
+    }
+    writeln!(dest, "(check-sat)")
+}
+
+pub fn to_smtlib(mexpr: &MarRecExpr, id: MarId) -> String {
+    let term = |i: &MarId| to_smtlib(mexpr, *i);
+    let list = |i: &MarId| -> Vec<String> {
+        decompose_using_expr(mexpr, *i)
+            .iter()
+            .map(|x| to_smtlib(mexpr, *x))
+            .collect()
+    };
+    let indexed = |op: &str, indices: &[&MarId], x: &MarId| {
+        let indices: Vec<String> = indices.iter().map(|i| term(i)).collect();
+        format!("((_ {} {}) {})", op, indices.join(" "), term(x))
+    };
+
+    match &mexpr[id] {
+        Marlang::Call([def, args]) => apply(name(mexpr, *def), list(args)),
+        Marlang::Let([bindings, body]) => {
+            format!("(let ({}) {})", sorted_vars(mexpr, *bindings), term(body))
+        }
+        Marlang::Forall([vars, body]) => {
+            format!("(forall ({}) {})", sorted_vars(mexpr, *vars), term(body))
+        }
+        Marlang::Exists([vars, body]) => {
+            format!("(exists ({}) {})", sorted_vars(mexpr, *vars), term(body))
+        }
+        Marlang::Annotate([t, attributes]) => {
+            let mut out = vec![term(t)];
+            out.extend(list(attributes));
+            format!("(! {})", out.join(" "))
+        }
+        Marlang::PatternAttr([terms]) => format!(":pattern ({})", list(terms).join(" ")),
+        Marlang::NamedAttr([n]) => format!(":named {}", term(n)),
+        Marlang::IntNeg([x]) | Marlang::RealNeg([x]) => format!("(- {})", term(x)),
+
+        Marlang::BvExtract([i, j, x]) => indexed("extract", &[i, j], x),
+        Marlang::BvZeroExtend([i, x]) => indexed("zero_extend", &[i], x),
+        Marlang::BvSignExtend([i, x]) => indexed("sign_extend", &[i], x),
+        Marlang::BvRepeat([i, x]) => indexed("repeat", &[i], x),
+        Marlang::BvRotateLeft([i, x]) => indexed("rotate_left", &[i], x),
+        Marlang::BvRotateRight([i, x]) => indexed("rotate_right", &[i], x),
+        Marlang::ReLoop([i, j, r]) => indexed("re.loop", &[i, j], r),
+        Marlang::RePower([i, r]) => indexed("re.^", &[i], r),
+        Marlang::ConstArray([s, v]) => format!("((as const {}) {})", term(s), term(v)),
+        Marlang::FpFromReal([e, s, rm, x])
+        | Marlang::FpFromSbv([e, s, rm, x])
+        | Marlang::FpFromFp([e, s, rm, x]) => {
+            format!(
+                "((_ to_fp {} {}) {} {})",
+                term(e),
+                term(s),
+                term(rm),
+                term(x)
+            )
+        }
+        Marlang::FpFromBv([e, s, x]) => indexed("to_fp", &[e, s], x),
+        Marlang::FpFromUbv([e, s, rm, x]) => format!(
+            "((_ to_fp_unsigned {} {}) {} {})",
+            term(e),
+            term(s),
+            term(rm),
+            term(x)
+        ),
+        Marlang::FpToUbv([m, rm, x]) => {
+            format!("((_ fp.to_ubv {}) {} {})", term(m), term(rm), term(x))
+        }
+        Marlang::FpToSbv([m, rm, x]) => {
+            format!("((_ fp.to_sbv {}) {} {})", term(m), term(rm), term(x))
+        }
+
+        Marlang::Datatype([n, _])
+        | Marlang::Constructor([_, n, _])
+        | Marlang::Selector([_, n, _]) => term(n),
+        Marlang::Tester([c]) => format!("(_ is {})", name(mexpr, *c)),
+
+        Marlang::SetLogic([l]) => format!("(set-logic {})", term(l)),
+        Marlang::CheckSat => "(check-sat)".into(),
+        Marlang::Assert([e]) => format!("(assert {})", term(e)),
+        Marlang::Push([n]) => format!("(push {})", term(n)),
+        Marlang::Pop([n]) => format!("(pop {})", term(n)),
+        Marlang::ResetAssertions => "(reset-assertions)".into(),
+        Marlang::CheckSatAssuming([ls]) => format!("(check-sat-assuming {})", term(ls)),
+        Marlang::GetModel => "(get-model)".into(),
+        Marlang::GetUnsatCore => "(get-unsat-core)".into(),
+        Marlang::GetValue([ts]) => format!("(get-value {})", term(ts)),
+        Marlang::SetOption([k, v]) => {
+            format!("(set-option {} {})", symbol(mexpr, *k), symbol(mexpr, *v))
+        }
+        Marlang::SetInfo([k, v]) => {
+            format!("(set-info {} {})", symbol(mexpr, *k), symbol(mexpr, *v))
+        }
+        Marlang::Exit => "(exit)".into(),
+        Marlang::SynthFun([n, ps, s, g]) => {
+            let grammar = decompose_using_expr(mexpr, *g);
+            let mut out = format!(
+                "(synth-fun {} ({}) {}",
+                term(n),
+                sorted_vars(mexpr, *ps),
+                term(s)
+            );
+            if !grammar.is_empty() {
+                let declared: Vec<String> = grammar
+                    .iter()
+                    .map(|nt| match &mexpr[*nt] {
+                        Marlang::NonTerminal([n, s, _]) => format!("({} {})", term(n), term(s)),
+                        _ => panic!("grammars must point to nonterminals"),
+                    })
+                    .collect();
+                out += &format!(" ({}) {}", declared.join(" "), term(g));
+            }
+            out + ")"
+        }
+        Marlang::NonTerminal([n, s, rs]) => format!("({} {} {})", term(n), term(s), term(rs)),
+        Marlang::GrammarConstant([s]) => format!("(Constant {})", term(s)),
+        Marlang::GrammarVariable([s]) => format!("(Variable {})", term(s)),
+        Marlang::DeclareVar([n, s]) => format!("(declare-var {} {})", term(n), term(s)),
+        Marlang::Constraint([t]) => format!("(constraint {})", term(t)),
+        Marlang::CheckSynth => "(check-synth)".into(),
+        Marlang::DeclareRel([n, ss]) => format!("(declare-rel {} {})", term(n), term(ss)),
+        Marlang::Rule([c]) => format!("(rule {})", term(c)),
+        Marlang::Query([q]) => format!("(query {})", term(q)),
+        Marlang::Minimize([t]) => format!("(minimize {})", term(t)),
+        Marlang::Maximize([t]) => format!("(maximize {})", term(t)),
+        Marlang::GetObjectives => "(get-objectives)".into(),
+        Marlang::DeclareFun([n, ps, s]) => {
+            format!(
+                "(declare-fun {} ({}) {})",
+                term(n),
+                list(ps).join(" "),
+                term(s)
+            )
+        }
+        Marlang::DefineFun([n, ps, s, b]) => format!(
+            "(define-fun {} ({}) {} {})",
+            term(n),
+            sorted_vars(mexpr, *ps),
+            term(s),
+            term(b)
+        ),
+        Marlang::RecFun([n, _, _]) => term(n),
+        Marlang::DefineFunRec([f, ps, b]) => {
+            format!(
+                "(define-fun-rec {} {})",
+                rec_signature(mexpr, *f, *ps),
+                term(b)
+            )
+        }
+        Marlang::DefineFunsRec([ds]) => {
+            let (signatures, bodies): (Vec<String>, Vec<String>) = decompose_using_expr(mexpr, *ds)
+                .into_iter()
+                .map(|d| match &mexpr[d] {
+                    Marlang::DefineFunRec([f, ps, b]) => {
+                        (format!("({})", rec_signature(mexpr, *f, *ps)), term(b))
+                    }
+                    _ => panic!("define-funs-rec must point to define-fun-rec definitions"),
+                })
+                .unzip();
+            format!(
+                "(define-funs-rec ({}) ({}))",
+                signatures.join(" "),
+                bodies.join(" ")
+            )
+        }
+        Marlang::DeclareSort([n, a]) => format!("(declare-sort {} {})", term(n), term(a)),
+        Marlang::DefineSort([n, ps, s]) => {
+            format!("(define-sort {} {} {})", term(n), term(ps), term(s))
+        }
+        Marlang::DeclareDatatype([d]) => match &mexpr[*d] {
+            Marlang::Datatype([n, cs]) => {
+                format!(
+                    "(declare-datatype {} {})",
+                    term(n),
+                    constructors(mexpr, *cs)
+                )
+            }
+            _ => panic!("declare-datatype must point to a datatype"),
+        },
+        Marlang::DeclareDatatypes([ds]) => {
+            let (names, bodies): (Vec<String>, Vec<String>) = decompose_using_expr(mexpr, *ds)
+                .into_iter()
+                .map(|d| match &mexpr[d] {
+                    Marlang::Datatype([n, cs]) => {
+                        (format!("({} 0)", term(n)), constructors(mexpr, *cs))
+                    }
+                    _ => panic!("declare-datatypes must point to datatypes"),
+                })
+                .unzip();
+            format!(
+                "(declare-datatypes ({}) ({}))",
+                names.join(" "),
+                bodies.join(" ")
+            )
+        }
+
+        Marlang::BoolSort => "Bool".into(),
+        Marlang::IntSort => "Int".into(),
+        Marlang::RealSort => "Real".into(),
+        Marlang::StringSort => "String".into(),
+        Marlang::RegLanSort => "RegLan".into(),
+        Marlang::BitVecSort([w]) => format!("(_ BitVec {})", term(w)),
+        Marlang::ArraySort([i, e]) => format!("(Array {} {})", term(i), term(e)),
+        Marlang::DatatypeSort([n]) => term(n),
+        Marlang::FpSort([e, s]) => format!("(_ FloatingPoint {} {})", term(e), term(s)),
+        Marlang::RoundingModeSort => "RoundingMode".into(),
+        Marlang::SortApply([def, args]) => apply(name(mexpr, *def), list(args)),
+
+        Marlang::BoolVal([v]) | Marlang::IntVal([v]) => numeral(&symbol(mexpr, *v)),
+        Marlang::RealVal([v]) => decimal(&symbol(mexpr, *v)),
+        Marlang::StringVal([v]) => escape_string(&symbol(mexpr, *v)),
+        Marlang::BvVal([v, w]) => format!("(_ bv{} {})", term(v), term(w)),
+        Marlang::RmVal([v]) => term(v),
+        Marlang::FpPosZero([e, s]) => format!("(_ +zero {} {})", term(e), term(s)),
+        Marlang::FpNegZero([e, s]) => format!("(_ -zero {} {})", term(e), term(s)),
+        Marlang::FpPosInf([e, s]) => format!("(_ +oo {} {})", term(e), term(s)),
+        Marlang::FpNegInf([e, s]) => format!("(_ -oo {} {})", term(e), term(s)),
+        Marlang::FpNaN([e, s]) => format!("(_ NaN {} {})", term(e), term(s)),
+
+        Marlang::Cons(_) | Marlang::Nil => format!("({})", list(&id).join(" ")),
+        Marlang::Symbol(s) => quote_symbol(s),
+
+        node => {
+            let args = node
+                .children()
+                .iter()
+                .flat_map(|c| match &mexpr[*c] {
+                    Marlang::Cons(_) | Marlang::Nil => list(c),
+                    _ => vec![term(c)],
+                })
+                .collect();
+            apply(operator(node), args)
+        }
+    }
+}
+
+fn apply(f: String, args: Vec<String>) -> String {
+    if args.is_empty() {
+        f
+    } else {
+        format!("({} {})", f, args.join(" "))
+    }
+}
+
+fn name(mexpr: &MarRecExpr, def: MarId) -> String {
+    match &mexpr[def] {
+        Marlang::DeclareFun([n, _, _])
+        | Marlang::DefineFun([n, _, _, _])
+        | Marlang::SynthFun([n, _, _, _])
+        | Marlang::DeclareVar([n, _])
+        | Marlang::DeclareRel([n, _])
+        | Marlang::DeclareSort([n, _])
+        | Marlang::DefineSort([n, _, _]) => to_smtlib(mexpr, *n),
+        _ => to_smtlib(mexpr, def),
+    }
+}
+
+// The name, sorted parameters and sort of a recursive definition
+fn rec_signature(mexpr: &MarRecExpr, f: MarId, params: MarId) -> String {
+    match &mexpr[f] {
+        Marlang::RecFun([n, _, s]) => format!(
+            "{} ({}) {}",
+            to_smtlib(mexpr, *n),
+            sorted_vars(mexpr, params),
+            to_smtlib(mexpr, *s)
+        ),
+        _ => panic!("define-fun-rec must point to a recursive function"),
+    }
+}
+
+fn operator(node: &Marlang) -> String {
+    let op = node.to_string();
+    for theory in ["core", "int", "real", "bv", "array", "ltl"] {
+        if let Some(op) = op.strip_prefix(&format!("marlang.operator.{}.", theory)) {
+            return op.into();
+        }
+    }
+    op.strip_prefix("marlang.operator.").unwrap_or(&op).into()
+}
+
+fn sorted_vars(mexpr: &MarRecExpr, vars: MarId) -> String {
+    decompose_using_expr(mexpr, vars)
+        .into_iter()
+        .map(|v| to_smtlib(mexpr, v))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn constructors(mexpr: &MarRecExpr, constructors: MarId) -> String {
+    let constructors: Vec<String> = decompose_using_expr(mexpr, constructors)
+        .into_iter()
+        .map(|c| match &mexpr[c] {
+            Marlang::Constructor([_, n, sels]) => {
+                let mut out = vec![to_smtlib(mexpr, *n)];
+                for s in decompose_using_expr(mexpr, *sels) {
+                    match &mexpr[s] {
+                        Marlang::Selector([_, sn, ss]) => out.push(format!(
+                            "({} {})",
+                            to_smtlib(mexpr, *sn),
+                            to_smtlib(mexpr, *ss)
+                        )),
+                        _ => panic!("constructors must point to selectors"),
+                    }
+                }
+                format!("({})", out.join(" "))
+            }
+            _ => panic!("datatypes must point to constructors"),
+        })
+        .collect();
+    format!("({})", constructors.join(" "))
+}
+
+fn symbol(mexpr: &MarRecExpr, id: MarId) -> String {
+    match &mexpr[id] {
+        Marlang::Symbol(s) => s.clone(),
+        _ => panic!("values must point to symbols"),
+    }
+}
+
+fn numeral(s: &str) -> String {
+    match s.strip_prefix('-') {
+        Some(s) => format!("(- {})", s),
+        None => s.into(),
+    }
+}
+
+fn decimal(s: &str) -> String {
+    if let Some(s) = s.strip_prefix('-') {
+        format!("(- {})", decimal(s))
+    } else if let Some((n, d)) = s.split_once('/') {
+        format!("(/ {} {})", decimal(n), decimal(d))
+    } else if s.contains('.') {
+        s.into()
+    } else {
+        format!("{}.0", s)
+    }
+}
+
+// Only printable ASCII is written as is; everything else, and the backslash that would start an
+// escape sequence, is written as \u{..}.
+pub fn escape_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\"\""),
+            ' '..='~' if c != '\\' => out.push(c),
+            c => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn unescape_string(literal: &str) -> Option<String> {
+    let chars: Vec<char> = literal
+        .strip_prefix('"')?
+        .strip_suffix('"')?
+        .chars()
+        .collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (c, next) = match chars[i..] {
+            ['"', '"', ..] => (Some('"'), i + 2),
+            ['"', ..] => return None,
+            ['\\', 'u', '{', ..] => {
+                let end = chars[i..].iter().position(|c| *c == '}').map(|e| i + e);
+                match end {
+                    Some(end) if end > i + 3 && end <= i + 8 => {
+                        (code_point(&chars[i + 3..end]), end + 1)
+                    }
+                    _ => (None, i + 1),
+                }
+            }
+            ['\\', 'u', a, b, c, d, ..] => (code_point(&[a, b, c, d]), i + 6),
+            _ => (None, i + 1),
+        };
+        out.push(c.unwrap_or(chars[i]));
+        i = if c.is_some() { next } else { i + 1 };
+    }
+    Some(out)
+}
+
+fn code_point(digits: &[char]) -> Option<char> {
+    let digits: String = digits.iter().collect();
+    let code = u32::from_str_radix(&digits, 16).ok()?;
+    if code <= 0x2FFFF {
+        char::from_u32(code)
+    } else {
+        None
+    }
+}
+
+fn quote_symbol(s: &str) -> String {
+    let simple = |c: char| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c);
+    if !s.is_empty() && s.chars().all(simple) {
+        s.into()
+    } else {
+        format!("|{}|", s)
+    }
+}
@@ -0,0 +1,13 @@
+use crate::{context::MarContext, eval::MarModel};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SatResult {
+    Sat(MarModel),
+    Unsat,
+    Unknown,
+}
+
+// Anything that can decide a script, like an external solver or a test double
+pub trait Backend {
+    fn check(&mut self, script: &mut MarContext) -> SatResult;
+}
@@ -0,0 +1,515 @@
+use egg::Language;
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+
+use crate::{
+    ast::{symbol, MarGraph, MarId, MarRecExpr, MarSort, Marlang},
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    util::{decompose_using_expr, decompose_using_graph},
+};
+
+// A grammar rule as a tree whose leaves may name nonterminals. These are the holes that get
+// filled with smaller candidates, in the order they appear.
+struct Rule {
+    template: MarRecExpr,
+    holes: Vec<(usize, usize)>,
+}
+
+// Builds candidates bottom-up, by the number of rules applied. Candidates of a nonterminal that
+// agree on every point end up in the same e-class, and only the first one found, which is also
+// the smallest, is used to build larger ones. Without points, only equal candidates are merged.
+struct Enumerator<'a> {
+    grammar: &'a [Vec<Rule>],
+    points: Option<Vec<MarModel>>,
+    egraph: MarGraph,
+    seen: HashMap<(usize, Vec<Option<MarValue>>), MarId>,
+    known: HashSet<(usize, MarId)>,
+    // by nonterminal and then size
+    bank: Vec<Vec<Vec<MarRecExpr>>>,
+}
+
+// Searches for a body of the synth-fun of the problem, using at most the given number of
+// grammar rules, that satisfies the constraints on every input. The inputs play the part of the
+// verifier: the first one that a candidate fails on becomes a counterexample, and candidates are
+// enumerated again, told apart by their values on the counterexamples found so far. The result
+// is a define-fun in the problem, which is not committed. Only problems with a single synth-fun
+// are solved, and constants are only enumerated for Int and Bool, so for other problems there is
+// no result either.
+pub fn synthesize(problem: &mut MarContext, inputs: &[MarModel], max_size: usize) -> Option<MarId> {
+    let functions: Vec<MarId> = problem
+        .active_commands()
+        .into_iter()
+        .filter(|c| {
+            problem.graph()[*c]
+                .iter()
+                .any(|node| matches!(node, Marlang::SynthFun(_)))
+        })
+        .collect();
+    let function = match functions[..] {
+        [function] => function,
+        _ => return None,
+    };
+
+    let constraints: Vec<MarRecExpr> = problem
+        .active_commands()
+        .into_iter()
+        .filter_map(|c| {
+            problem.graph()[c].iter().find_map(|node| match node {
+                Marlang::Constraint([t]) => Some(problem.get_expr(*t)),
+                _ => None,
+            })
+        })
+        .collect();
+    let grammar = grammar(problem, function, &constraints)?;
+    let declaration = problem.get_expr(function);
+
+    let mut examples: Vec<MarModel> = inputs.first().cloned().into_iter().collect();
+    'restart: loop {
+        let points = points(&declaration, &constraints, &examples);
+        let mut enumerator = Enumerator::new(&grammar, points);
+        for size in 1..=max_size {
+            for body in enumerator.grow(size) {
+                let definition = define(&declaration, &body);
+                let instances: Vec<MarRecExpr> = constraints
+                    .iter()
+                    .map(|c| instantiate(c, &definition))
+                    .collect();
+                if !examples.iter().all(|e| satisfies(&instances, e)) {
+                    continue;
+                }
+                match inputs.iter().find(|i| !satisfies(&instances, i)) {
+                    Some(counterexample) => {
+                        examples.push(counterexample.clone());
+                        continue 'restart;
+                    }
+                    None => return Some(solution(problem, function, body)),
+                }
+            }
+        }
+        return None;
+    }
+}
+
+// Every combination of the given values for the Int and Bool variables of the problem, if it
+// has no variables of other sorts
+pub fn input_grid(problem: &MarContext, ints: &[i64]) -> Option<Vec<MarModel>> {
+    let graph = problem.graph();
+    let mut grid = vec![MarModel::new()];
+    for command in problem.active_commands() {
+        for node in graph[command].iter() {
+            if let Marlang::DeclareVar([n, s]) = node {
+                let values: Vec<MarValue> = match graph[*s].data.sort {
+                    Some(MarSort::Bool) => vec![MarValue::Bool(false), MarValue::Bool(true)],
+                    Some(MarSort::Int) => ints.iter().map(|i| MarValue::Int((*i).into())).collect(),
+                    _ => return None,
+                };
+                let name = &symbol(graph, *n).expect("declare-var must point to a symbol");
+                grid = grid
+                    .into_iter()
+                    .flat_map(|model| {
+                        values.iter().map(move |v| {
+                            let mut model = model.clone();
+                            model.insert(name, v.clone());
+                            model
+                        })
+                    })
+                    .collect();
+            }
+        }
+    }
+    Some(grid)
+}
+
+impl<'a> Enumerator<'a> {
+    fn new(grammar: &'a [Vec<Rule>], points: Option<Vec<MarModel>>) -> Self {
+        Self {
+            grammar,
+            points,
+            egraph: MarGraph::default(),
+            seen: HashMap::default(),
+            known: HashSet::default(),
+            bank: grammar.iter().map(|_| vec![vec![]]).collect(),
+        }
+    }
+
+    // The new candidates of the start symbol with the given size
+    fn grow(&mut self, size: usize) -> Vec<MarRecExpr> {
+        let grammar = self.grammar;
+        let mut terms = vec![];
+        for (nt, rules) in grammar.iter().enumerate() {
+            for rule in rules {
+                let holes: Vec<usize> = rule.holes.iter().map(|(_, nt)| *nt).collect();
+                for children in self.combinations(&holes, size - 1) {
+                    terms.push((nt, fill(rule, &children)));
+                }
+            }
+        }
+        for bank in self.bank.iter_mut() {
+            bank.push(vec![]);
+        }
+        for (nt, term) in terms {
+            self.insert(nt, size, term);
+        }
+        self.egraph.rebuild();
+        self.bank[0][size].clone()
+    }
+
+    // The ways to fill the holes with candidates whose sizes add up to the budget
+    fn combinations(&self, holes: &[usize], budget: usize) -> Vec<Vec<&MarRecExpr>> {
+        let (first, rest) = match holes.split_first() {
+            Some(split) => split,
+            None if budget == 0 => return vec![vec![]],
+            None => return vec![],
+        };
+        let mut out = vec![];
+        for size in 1..=budget.saturating_sub(rest.len()) {
+            for term in &self.bank[*first][size] {
+                for mut others in self.combinations(rest, budget - size) {
+                    others.insert(0, term);
+                    out.push(others);
+                }
+            }
+        }
+        out
+    }
+
+    fn insert(&mut self, nt: usize, size: usize, term: MarRecExpr) {
+        if let Some(id) = self.egraph.lookup_expr(&term) {
+            if self.known.contains(&(nt, self.egraph.find(id))) {
+                return;
+            }
+        }
+        let root = (term.as_ref().len() - 1).into();
+        let signature: Option<Vec<Option<MarValue>>> = self
+            .points
+            .as_ref()
+            .map(|points| points.iter().map(|p| eval(&term, root, p).ok()).collect());
+        let id = self.egraph.add_expr(&term);
+        match signature {
+            Some(signature) => match self.seen.get(&(nt, signature.clone())) {
+                Some(class) => {
+                    self.egraph.union(*class, id);
+                }
+                None => {
+                    self.seen.insert((nt, signature), id);
+                    self.bank[nt][size].push(term);
+                }
+            },
+            None => self.bank[nt][size].push(term),
+        }
+        self.known.insert((nt, self.egraph.find(id)));
+    }
+}
+
+// The rules of each nonterminal, with the start symbol first. Variable and Constant rules stand
+// for the parameters of their sort and for the small constants and the literals of the
+// constraints.
+fn grammar(
+    problem: &mut MarContext,
+    function: MarId,
+    constraints: &[MarRecExpr],
+) -> Option<Vec<Vec<Rule>>> {
+    let (params, sort, nonterminals) = problem.graph()[function]
+        .iter()
+        .find_map(|node| match node {
+            Marlang::SynthFun([_, ps, s, g]) => Some((*ps, *s, *g)),
+            _ => None,
+        })
+        .expect("expected a synth-fun");
+    let graph = problem.graph();
+    let params: Vec<(String, MarId)> = decompose_using_graph(graph, params)
+        .into_iter()
+        .filter_map(|p| match decompose_using_graph(graph, p)[..] {
+            [n, s] => Some((symbol(graph, n)?, s)),
+            _ => None,
+        })
+        .collect();
+    let mut nonterminals = decompose_using_graph(graph, nonterminals);
+    if nonterminals.is_empty() {
+        nonterminals = default_grammar(problem, sort)?;
+    }
+
+    let mut literals: Vec<String> = vec!["0".into(), "1".into()];
+    for c in constraints {
+        for node in c.as_ref() {
+            if let Marlang::IntVal([v]) = node {
+                if let Marlang::Symbol(v) = &c[*v] {
+                    if !literals.contains(v) {
+                        literals.push(v.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    let graph = problem.graph();
+    let declared: Vec<(String, MarId)> = nonterminals
+        .iter()
+        .map(|nt| {
+            graph[*nt]
+                .iter()
+                .find_map(|node| match node {
+                    Marlang::NonTerminal([n, _, rules]) => Some((symbol(graph, *n)?, *rules)),
+                    _ => None,
+                })
+                .expect("grammars must point to nonterminals")
+        })
+        .collect();
+    let names: Vec<&String> = declared.iter().map(|(n, _)| n).collect();
+
+    let mut grammar = vec![];
+    for (_, rules) in &declared {
+        let mut out = vec![];
+        for rule in decompose_using_graph(graph, *rules) {
+            let mut templates = vec![];
+            for node in graph[rule].iter() {
+                match node {
+                    Marlang::GrammarVariable([s]) => {
+                        for (p, ps) in &params {
+                            if graph.find(*ps) == graph.find(*s) {
+                                templates.push(leaf(Marlang::Symbol(p.clone()), None));
+                            }
+                        }
+                    }
+                    Marlang::GrammarConstant([s]) => match graph[*s].data.sort {
+                        Some(MarSort::Int) => {
+                            for l in &literals {
+                                templates
+                                    .push(leaf(Marlang::Symbol(l.clone()), Some(Marlang::IntVal)));
+                            }
+                        }
+                        Some(MarSort::Bool) => {
+                            for b in ["false", "true"] {
+                                templates
+                                    .push(leaf(Marlang::Symbol(b.into()), Some(Marlang::BoolVal)));
+                            }
+                        }
+                        _ => return None,
+                    },
+                    _ => continue,
+                }
+                break;
+            }
+            if templates.is_empty() {
+                let expr = problem.get_expr(rule);
+                let mut template = MarRecExpr::default();
+                tree(&expr, (expr.as_ref().len() - 1).into(), &mut template);
+                templates.push(template);
+            }
+            for template in templates {
+                let holes = template
+                    .as_ref()
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, node)| match node {
+                        Marlang::Symbol(s) => Some((i, names.iter().position(|n| *n == s)?)),
+                        _ => None,
+                    })
+                    .collect();
+                out.push(Rule { template, holes });
+            }
+        }
+        grammar.push(out);
+    }
+    Some(grammar)
+}
+
+// Linear integer arithmetic with conditionals, for functions that come without a grammar
+fn default_grammar(problem: &mut MarContext, sort: MarId) -> Option<Vec<MarId>> {
+    let (ints, bools) = match &problem.graph()[sort].data.sort {
+        Some(MarSort::Int) => ("Start", "StartBool"),
+        Some(MarSort::Bool) => ("StartInt", "Start"),
+        _ => return None,
+    };
+    let int_sort = problem.mk_int_sort();
+    let bool_sort = problem.mk_bool_sort();
+    let i = problem.mk_symbol(ints);
+    let b = problem.mk_symbol(bools);
+
+    let int_rules = vec![
+        problem.mk_grammar_variable(int_sort),
+        problem.mk_grammar_constant(int_sort),
+        problem.mk_int_add(vec![i, i]),
+        problem.mk_int_sub(vec![i, i]),
+        problem.mk_ite(b, i, i),
+    ];
+    let bool_rules = vec![
+        problem.mk_grammar_variable(bool_sort),
+        problem.mk_grammar_constant(bool_sort),
+        problem.mk_int_le(vec![i, i]),
+        problem.mk_eq(vec![i, i]),
+        problem.mk_and(vec![b, b]),
+        problem.mk_not(b),
+    ];
+    let int_nt = problem.mk_nonterminal(ints, int_sort, int_rules);
+    let bool_nt = problem.mk_nonterminal(bools, bool_sort, bool_rules);
+    if ints == "Start" {
+        Some(vec![int_nt, bool_nt])
+    } else {
+        Some(vec![bool_nt, int_nt])
+    }
+}
+
+// The arguments the function is applied to on the examples, which is where candidates are
+// compared. When an argument itself calls the function, like in (f (f x)), where the outer call
+// lands depends on the candidate, so there are no points to compare on.
+fn points(
+    declaration: &MarRecExpr,
+    constraints: &[MarRecExpr],
+    examples: &[MarModel],
+) -> Option<Vec<MarModel>> {
+    let root = declaration.as_ref().len() - 1;
+    let params: Vec<String> = match &declaration[root.into()] {
+        Marlang::SynthFun([_, ps, _, _]) => decompose_using_expr(declaration, *ps)
+            .into_iter()
+            .filter_map(
+                |p| match &declaration[decompose_using_expr(declaration, p)[0]] {
+                    Marlang::Symbol(s) => Some(s.clone()),
+                    _ => None,
+                },
+            )
+            .collect(),
+        _ => panic!("expected a synth-fun"),
+    };
+
+    for c in constraints {
+        for node in c.as_ref() {
+            if let Marlang::Call([def, args]) = node {
+                if matches!(c[*def], Marlang::SynthFun(_)) && calls(c, *args) {
+                    return None;
+                }
+            }
+        }
+    }
+
+    let mut points = vec![];
+    for example in examples {
+        for c in constraints {
+            for node in c.as_ref() {
+                if let Marlang::Call([def, args]) = node {
+                    if !matches!(c[*def], Marlang::SynthFun(_)) {
+                        continue;
+                    }
+                    let values: Result<Vec<MarValue>, _> = decompose_using_expr(c, *args)
+                        .into_iter()
+                        .map(|a| eval(c, a, example))
+                        .collect();
+                    if let Ok(values) = values {
+                        let mut point = MarModel::new();
+                        for (p, v) in params.iter().zip(values) {
+                            point.insert(p, v);
+                        }
+                        if !points.contains(&point) {
+                            points.push(point);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Some(points)
+}
+
+// Whether the subterm calls the synth-fun
+fn calls(expr: &MarRecExpr, id: MarId) -> bool {
+    match &expr[id] {
+        Marlang::Call([def, _]) if matches!(expr[*def], Marlang::SynthFun(_)) => true,
+        node => node.children().iter().any(|c| calls(expr, *c)),
+    }
+}
+
+fn satisfies(constraints: &[MarRecExpr], input: &MarModel) -> bool {
+    constraints.iter().all(|c| {
+        let root = (c.as_ref().len() - 1).into();
+        eval(c, root, input) == Ok(MarValue::Bool(true))
+    })
+}
+
+// The synth-fun turned into a define-fun with the given body
+fn define(declaration: &MarRecExpr, body: &MarRecExpr) -> MarRecExpr {
+    let mut out = declaration.clone();
+    let (n, ps, s) = match out.as_ref().last() {
+        Some(Marlang::SynthFun([n, ps, s, _])) => (*n, *ps, *s),
+        _ => panic!("expected a synth-fun"),
+    };
+    let body = append(&mut out, body);
+    out.add(Marlang::DefineFun([n, ps, s, body]));
+    out
+}
+
+// The constraint with its calls to the synth-fun pointing to the definition instead
+fn instantiate(constraint: &MarRecExpr, definition: &MarRecExpr) -> MarRecExpr {
+    let mut out = definition.clone();
+    let defined = (out.as_ref().len() - 1).into();
+    let mut ids: Vec<MarId> = vec![];
+    for node in constraint.as_ref() {
+        let id = match node {
+            Marlang::SynthFun(_) => defined,
+            node => out.add(node.clone().map_children(|c| ids[usize::from(c)])),
+        };
+        ids.push(id);
+    }
+    out
+}
+
+fn solution(problem: &mut MarContext, function: MarId, body: MarRecExpr) -> MarId {
+    let graph = problem.graph();
+    let (name, params, sort) = graph[function]
+        .iter()
+        .find_map(|node| match node {
+            Marlang::SynthFun([n, ps, s, _]) => Some((symbol(graph, *n)?, *ps, *s)),
+            _ => None,
+        })
+        .expect("expected a synth-fun");
+    let params: Vec<(String, MarId)> = decompose_using_graph(graph, params)
+        .into_iter()
+        .filter_map(|p| match decompose_using_graph(graph, p)[..] {
+            [n, s] => Some((symbol(graph, n)?, s)),
+            _ => None,
+        })
+        .collect();
+    let body = problem.add_recexpr(body);
+    problem.mk_define_fun(name, params, sort, body)
+}
+
+fn fill(rule: &Rule, children: &[&MarRecExpr]) -> MarRecExpr {
+    let mut out = MarRecExpr::default();
+    let mut ids: Vec<MarId> = vec![];
+    let mut children = children.iter();
+    for (i, node) in rule.template.as_ref().iter().enumerate() {
+        let id = if rule.holes.iter().any(|(h, _)| *h == i) {
+            let child = children
+                .next()
+                .expect("expected a candidate for every hole");
+            append(&mut out, child)
+        } else {
+            out.add(node.clone().map_children(|c| ids[usize::from(c)]))
+        };
+        ids.push(id);
+    }
+    out
+}
+
+// Copies the nodes of an expression to the end of another, returning the new root
+fn append(out: &mut MarRecExpr, expr: &MarRecExpr) -> MarId {
+    let mut ids: Vec<MarId> = vec![];
+    for node in expr.as_ref() {
+        ids.push(out.add(node.clone().map_children(|c| ids[usize::from(c)])));
+    }
+    *ids.last().expect("cannot append an empty expression")
+}
+
+// Copies a subterm without sharing, so that every occurrence of a nonterminal is its own hole
+fn tree(expr: &MarRecExpr, id: MarId, out: &mut MarRecExpr) -> MarId {
+    let node = expr[id].clone().map_children(|c| tree(expr, c, out));
+    out.add(node)
+}
+
+fn leaf(value: Marlang, wrap: Option<fn([MarId; 1]) -> Marlang>) -> MarRecExpr {
+    let mut out = MarRecExpr::default();
+    let id = out.add(value);
+    if let Some(wrap) = wrap {
+        out.add(wrap([id]));
+    }
+    out
+}
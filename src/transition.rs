@@ -0,0 +1,305 @@
+use fxhash::FxHashMap as HashMap;
+
+use crate::{
+    ast::{MarGraph, MarId, Marlang},
+    context::MarContext,
+    eval::{eval, EvalError, MarModel, MarValue},
+};
+
+struct Variable {
+    name: String,
+    sort: MarId,
+}
+
+// A system described by terms over its state variables, their primed (next-state) copies and
+// its inputs. The terms live in their own context and get copied into every script.
+pub struct TransitionSystem {
+    context: MarContext,
+    states: Vec<Variable>,
+    inputs: Vec<Variable>,
+    init: Vec<MarId>,
+    next: Vec<MarId>,
+    properties: Vec<(String, MarId)>,
+}
+
+// The declarations of every variable at each step of an unrolling
+pub struct Path {
+    steps: Vec<HashMap<String, MarId>>,
+}
+
+impl TransitionSystem {
+    pub fn new() -> Self {
+        Self {
+            context: MarContext::new(),
+            states: vec![],
+            inputs: vec![],
+            init: vec![],
+            next: vec![],
+            properties: vec![],
+        }
+    }
+
+    pub fn context(&mut self) -> &mut MarContext {
+        &mut self.context
+    }
+
+    pub fn graph(&self) -> &MarGraph {
+        self.context.graph()
+    }
+
+    // Returns the current and the next-state value of the variable
+    pub fn add_state<T: ToString>(&mut self, name: T, sort: MarId) -> (MarId, MarId) {
+        let name = name.to_string();
+        let current = self.variable(&name, sort);
+        let next = self.variable(&primed(&name), sort);
+        self.states.push(Variable { name, sort });
+        (current, next)
+    }
+
+    pub fn add_input<T: ToString>(&mut self, name: T, sort: MarId) -> MarId {
+        let name = name.to_string();
+        let input = self.variable(&name, sort);
+        self.inputs.push(Variable { name, sort });
+        input
+    }
+
+    pub fn add_init(&mut self, term: MarId) {
+        self.current_only(term);
+        self.init.push(term);
+    }
+
+    pub fn add_next(&mut self, term: MarId) {
+        self.next.push(term);
+    }
+
+    pub fn add_property<T: ToString>(&mut self, name: T, term: MarId) {
+        self.current_only(term);
+        self.properties.push((name.to_string(), term));
+    }
+
+    pub fn states(&self) -> Vec<String> {
+        self.states.iter().map(|v| v.name.clone()).collect()
+    }
+
+    pub fn inputs(&self) -> Vec<String> {
+        self.inputs.iter().map(|v| v.name.clone()).collect()
+    }
+
+    pub fn properties(&self) -> Vec<(String, MarId)> {
+        self.properties.clone()
+    }
+
+    // Declares the states and inputs of steps 0 to `length` in the script, after the functions,
+    // parameters and sorts declared in the context of the system
+    pub fn declare_path(&self, script: &mut MarContext, length: usize) -> Path {
+        for command in self.context.active_commands() {
+            let expr = self.context.get_expr(command);
+            if let Some(
+                Marlang::DeclareFun(_)
+                | Marlang::DefineFun(_)
+                | Marlang::DefineFunRec(_)
+                | Marlang::DefineFunsRec(_)
+                | Marlang::DeclareDatatype(_)
+                | Marlang::DeclareDatatypes(_)
+                | Marlang::DeclareSort(_)
+                | Marlang::DefineSort(_),
+            ) = expr.as_ref().last()
+            {
+                let c = script.import(&expr, |_| None);
+                script.commit(c);
+            }
+        }
+        let mut steps = vec![];
+        for i in 0..=length {
+            let mut step = HashMap::default();
+            for v in self.states.iter().chain(&self.inputs) {
+                let sort = script.import(&self.context.get_expr(v.sort), |_| None);
+                let decl = script.declare_const(step_name(&v.name, i), sort);
+                step.insert(v.name.clone(), decl);
+            }
+            steps.push(step);
+        }
+        Path { steps }
+    }
+
+    // Copies a term into the script, reading unprimed variables at step i and primed ones at
+    // step i + 1
+    pub fn at(&self, script: &mut MarContext, path: &Path, term: MarId, i: usize) -> MarId {
+        self.between(script, path, term, i, i + 1)
+    }
+
+    // Like `at`, but the primed variables are read at step j, which lets a path loop back
+    pub fn between(
+        &self,
+        script: &mut MarContext,
+        path: &Path,
+        term: MarId,
+        i: usize,
+        j: usize,
+    ) -> MarId {
+        let step = |v: &Variable, i: usize| {
+            path.get(&v.name, i)
+                .unwrap_or_else(|| panic!("the path has no step {} for {}", i, v.name))
+        };
+        let rename = |n: &str| {
+            if let Some(v) = self.states.iter().chain(&self.inputs).find(|v| v.name == n) {
+                return Some(step(v, i));
+            }
+            let v = self.states.iter().find(|v| primed(&v.name) == n)?;
+            Some(step(v, j))
+        };
+        script.import(&self.context.get_expr(term), rename)
+    }
+
+    pub fn init_at(&self, script: &mut MarContext, path: &Path, i: usize) -> MarId {
+        let init: Vec<MarId> = self
+            .init
+            .iter()
+            .map(|t| self.at(script, path, *t, i))
+            .collect();
+        conjunction(script, init)
+    }
+
+    pub fn next_at(&self, script: &mut MarContext, path: &Path, i: usize) -> MarId {
+        self.next_between(script, path, i, i + 1)
+    }
+
+    pub fn next_between(&self, script: &mut MarContext, path: &Path, i: usize, j: usize) -> MarId {
+        let next: Vec<MarId> = self
+            .next
+            .iter()
+            .map(|t| self.between(script, path, *t, i, j))
+            .collect();
+        conjunction(script, next)
+    }
+
+    // Whether the next-state relation allows going from one decoded step to another
+    pub fn is_transition(&self, before: &MarModel, after: &MarModel) -> Result<bool, EvalError> {
+        let mut model = before.clone();
+        for v in &self.states {
+            if let Some(value) = after.get(&v.name) {
+                model.insert(primed(&v.name), value.clone());
+            }
+        }
+        for t in &self.next {
+            let expr = self.context.get_expr(*t);
+            let root = (expr.as_ref().len() - 1).into();
+            if eval(&expr, root, &model)? != MarValue::Bool(true) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn property_at(&self, script: &mut MarContext, path: &Path, i: usize) -> MarId {
+        let properties: Vec<MarId> = self
+            .properties
+            .iter()
+            .map(|(_, t)| self.at(script, path, *t, i))
+            .collect();
+        conjunction(script, properties)
+    }
+
+    // A script that is satisfiable iff some property fails within k steps of an initial state
+    pub fn unroll(&self, k: usize) -> MarContext {
+        let mut script = MarContext::new();
+        let path = self.declare_path(&mut script, k);
+        let init = self.init_at(&mut script, &path, 0);
+        script.assert(init);
+        for i in 0..k {
+            let next = self.next_at(&mut script, &path, i);
+            script.assert(next);
+        }
+        let bad: Vec<MarId> = (0..=k)
+            .map(|i| {
+                let p = self.property_at(&mut script, &path, i);
+                script.mk_not(p)
+            })
+            .collect();
+        let bad = disjunction(&mut script, bad);
+        script.assert(bad);
+        script.check_sat();
+        script
+    }
+
+    // Splits a model of an unrolling into the values of the variables at each step
+    pub fn decode_trace(&self, model: &MarModel, k: usize) -> Vec<MarModel> {
+        (0..=k)
+            .map(|i| {
+                let mut step = MarModel::new();
+                for v in self.states.iter().chain(&self.inputs) {
+                    if let Some(value) = model.get(&step_name(&v.name, i)) {
+                        step.insert(&v.name, value.clone());
+                    }
+                }
+                step
+            })
+            .collect()
+    }
+
+    // Only the next-state relation can read the primed variables
+    fn current_only(&self, term: MarId) {
+        let expr = self.context.get_expr(term);
+        for node in expr.as_ref() {
+            if let Marlang::DeclareFun([n, _, _]) = node {
+                let name = expr[*n].to_string();
+                assert!(
+                    !self.states.iter().any(|v| primed(&v.name) == name),
+                    "{} can only be used in the next-state relation",
+                    name
+                );
+            }
+        }
+    }
+
+    fn variable(&mut self, name: &str, sort: MarId) -> MarId {
+        let decl = self.context.mk_declare_const(name, sort);
+        let empty = self.context.mk_nil();
+        self.context.mk_call(decl, empty)
+    }
+}
+
+impl Default for TransitionSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Path {
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    // The declaration of the variable at step i, if the path is long enough
+    pub fn get(&self, name: &str, i: usize) -> Option<MarId> {
+        self.steps.get(i)?.get(name).cloned()
+    }
+}
+
+pub fn step_name(name: &str, i: usize) -> String {
+    format!("{}@{}", name, i)
+}
+
+fn primed(name: &str) -> String {
+    format!("{}'", name)
+}
+
+pub fn conjunction(script: &mut MarContext, mut terms: Vec<MarId>) -> MarId {
+    match terms.len() {
+        0 => script.mk_bool_val(true),
+        1 => terms.remove(0),
+        _ => script.mk_and(terms),
+    }
+}
+
+pub fn disjunction(script: &mut MarContext, mut terms: Vec<MarId>) -> MarId {
+    match terms.len() {
+        0 => script.mk_bool_val(false),
+        1 => terms.remove(0),
+        _ => script.mk_or(terms),
+    }
+}
@@ -8,7 +8,7 @@ use std::{
 
 use egg::Language;
 
-use crate::ast::{MarId, MarRecExpr, Marlang};
+use crate::ast::{MarGraph, MarId, MarRecExpr, Marlang};
 
 pub fn decompose_using_expr(mexpr: &MarRecExpr, ls: MarId) -> Vec<MarId> {
     let last = ls.into();
@@ -28,6 +28,22 @@ fn decompose_using_expr_rec(mexpr: &MarRecExpr, i: usize) -> Vec<MarId> {
     }
 }
 
+pub fn decompose_using_graph(mgraph: &MarGraph, ls: MarId) -> Vec<MarId> {
+    let cons = mgraph[ls].iter().find_map(|node| match node {
+        Marlang::Cons([x, s]) => Some((*x, *s)),
+        _ => None,
+    });
+    match cons {
+        Some((x, s)) => {
+            let mut x = vec![x];
+            let mut rest = decompose_using_graph(mgraph, s);
+            x.append(&mut rest);
+            x
+        }
+        None => vec![],
+    }
+}
+
 pub fn write_leda<T: Write>(dest: &mut T, mexpr: &MarRecExpr) -> io::Result<()> {
     writeln!(dest, "LEDA.GRAPH\nstring\nstring\n-1")?;
     let nodes = mexpr.as_ref().to_owned();
@@ -145,6 +161,16 @@ pub fn read_leda<T: Read>(source: &mut T) -> io::Result<MarRecExpr> {
             "marlang.operator.int.>=" => mexpr.add(Marlang::IntGe([children[0]])),
             "marlang.operator.int.<" => mexpr.add(Marlang::IntLt([children[0]])),
             "marlang.operator.int.<=" => mexpr.add(Marlang::IntLe([children[0]])),
+            "marlang.operator.int.div" => mexpr.add(Marlang::IntDiv([children[0]])),
+            "marlang.operator.ltl.G" => mexpr.add(Marlang::Globally([children[0]])),
+            "marlang.operator.ltl.F" => mexpr.add(Marlang::Finally([children[0]])),
+            "marlang.operator.ltl.X" => mexpr.add(Marlang::Next([children[0]])),
+            "marlang.operator.ltl.U" => mexpr.add(Marlang::Until([children[0], children[1]])),
+            "marlang.operator.ltl.R" => mexpr.add(Marlang::Release([children[0], children[1]])),
+            "marlang.operator.int.mod" => mexpr.add(Marlang::IntMod([children[0], children[1]])),
+            "marlang.operator.int.abs" => mexpr.add(Marlang::IntAbs([children[0]])),
+            "marlang.operator.int.neg" => mexpr.add(Marlang::IntNeg([children[0]])),
+            "marlang.operator.int.to_real" => mexpr.add(Marlang::ToReal([children[0]])),
             "marlang.operator.real.+" => mexpr.add(Marlang::RealAdd([children[0]])),
             "marlang.operator.real.-" => mexpr.add(Marlang::RealSub([children[0]])),
             "marlang.operator.real.*" => mexpr.add(Marlang::RealMul([children[0]])),
@@ -153,17 +179,224 @@ pub fn read_leda<T: Read>(source: &mut T) -> io::Result<MarRecExpr> {
             "marlang.operator.real.>=" => mexpr.add(Marlang::RealGe([children[0]])),
             "marlang.operator.real.<" => mexpr.add(Marlang::RealLt([children[0]])),
             "marlang.operator.real.<=" => mexpr.add(Marlang::RealLe([children[0]])),
+            "marlang.operator.real.neg" => mexpr.add(Marlang::RealNeg([children[0]])),
+            "marlang.operator.real.to_int" => mexpr.add(Marlang::ToInt([children[0]])),
+            "marlang.operator.real.is_int" => mexpr.add(Marlang::IsInt([children[0]])),
             "marlang.operator.str.++" => mexpr.add(Marlang::Concat([children[0]])),
+            "marlang.operator.str.<" => mexpr.add(Marlang::StrLt([children[0]])),
+            "marlang.operator.str.<=" => mexpr.add(Marlang::StrLe([children[0]])),
+            "marlang.operator.re.++" => mexpr.add(Marlang::ReConcat([children[0]])),
+            "marlang.operator.re.union" => mexpr.add(Marlang::ReUnion([children[0]])),
+            "marlang.operator.re.inter" => mexpr.add(Marlang::ReInter([children[0]])),
+            "marlang.operator.bv.bvadd" => mexpr.add(Marlang::BvAdd([children[0]])),
+            "marlang.operator.bv.bvmul" => mexpr.add(Marlang::BvMul([children[0]])),
+            "marlang.operator.bv.bvand" => mexpr.add(Marlang::BvAnd([children[0]])),
+            "marlang.operator.bv.bvor" => mexpr.add(Marlang::BvOr([children[0]])),
+            "marlang.operator.bv.bvxor" => mexpr.add(Marlang::BvXor([children[0]])),
             "marlang.operator.core.and" => mexpr.add(Marlang::And([children[0]])),
             "marlang.operator.core.or" => mexpr.add(Marlang::Or([children[0]])),
             "marlang.operator.core.xor" => mexpr.add(Marlang::Xor([children[0]])),
             "marlang.operator.core.let" => mexpr.add(Marlang::Let([children[0], children[1]])),
             "marlang.operator.core.=" => mexpr.add(Marlang::Eq([children[0]])),
+            "marlang.operator.core.distinct" => mexpr.add(Marlang::Distinct([children[0]])),
             "marlang.operator.core.not" => mexpr.add(Marlang::Not([children[0]])),
             "marlang.operator.core.=>" => mexpr.add(Marlang::Implies([children[0], children[1]])),
             "marlang.operator.core.ite" => {
                 mexpr.add(Marlang::Ite([children[0], children[1], children[2]]))
             }
+            "marlang.operator.bv.bvnot" => mexpr.add(Marlang::BvNot([children[0]])),
+            "marlang.operator.bv.bvneg" => mexpr.add(Marlang::BvNeg([children[0]])),
+            "marlang.operator.bv.bvsub" => mexpr.add(Marlang::BvSub([children[0], children[1]])),
+            "marlang.operator.bv.bvudiv" => mexpr.add(Marlang::BvUdiv([children[0], children[1]])),
+            "marlang.operator.bv.bvurem" => mexpr.add(Marlang::BvUrem([children[0], children[1]])),
+            "marlang.operator.bv.bvsdiv" => mexpr.add(Marlang::BvSdiv([children[0], children[1]])),
+            "marlang.operator.bv.bvsrem" => mexpr.add(Marlang::BvSrem([children[0], children[1]])),
+            "marlang.operator.bv.bvsmod" => mexpr.add(Marlang::BvSmod([children[0], children[1]])),
+            "marlang.operator.bv.bvnand" => mexpr.add(Marlang::BvNand([children[0], children[1]])),
+            "marlang.operator.bv.bvnor" => mexpr.add(Marlang::BvNor([children[0], children[1]])),
+            "marlang.operator.bv.bvxnor" => mexpr.add(Marlang::BvXnor([children[0], children[1]])),
+            "marlang.operator.bv.bvshl" => mexpr.add(Marlang::BvShl([children[0], children[1]])),
+            "marlang.operator.bv.bvlshr" => mexpr.add(Marlang::BvLshr([children[0], children[1]])),
+            "marlang.operator.bv.bvashr" => mexpr.add(Marlang::BvAshr([children[0], children[1]])),
+            "marlang.operator.bv.bvcomp" => mexpr.add(Marlang::BvComp([children[0], children[1]])),
+            "marlang.operator.bv.bvult" => mexpr.add(Marlang::BvUlt([children[0], children[1]])),
+            "marlang.operator.bv.bvule" => mexpr.add(Marlang::BvUle([children[0], children[1]])),
+            "marlang.operator.bv.bvugt" => mexpr.add(Marlang::BvUgt([children[0], children[1]])),
+            "marlang.operator.bv.bvuge" => mexpr.add(Marlang::BvUge([children[0], children[1]])),
+            "marlang.operator.bv.bvslt" => mexpr.add(Marlang::BvSlt([children[0], children[1]])),
+            "marlang.operator.bv.bvsle" => mexpr.add(Marlang::BvSle([children[0], children[1]])),
+            "marlang.operator.bv.bvsgt" => mexpr.add(Marlang::BvSgt([children[0], children[1]])),
+            "marlang.operator.bv.bvsge" => mexpr.add(Marlang::BvSge([children[0], children[1]])),
+            "marlang.operator.bv.concat" => {
+                mexpr.add(Marlang::BvConcat([children[0], children[1]]))
+            }
+            "marlang.operator.bv.extract" => {
+                mexpr.add(Marlang::BvExtract([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.bv.zero_extend" => {
+                mexpr.add(Marlang::BvZeroExtend([children[0], children[1]]))
+            }
+            "marlang.operator.bv.sign_extend" => {
+                mexpr.add(Marlang::BvSignExtend([children[0], children[1]]))
+            }
+            "marlang.operator.bv.repeat" => {
+                mexpr.add(Marlang::BvRepeat([children[0], children[1]]))
+            }
+            "marlang.operator.bv.rotate_left" => {
+                mexpr.add(Marlang::BvRotateLeft([children[0], children[1]]))
+            }
+            "marlang.operator.bv.rotate_right" => {
+                mexpr.add(Marlang::BvRotateRight([children[0], children[1]]))
+            }
+            "marlang.operator.str.len" => mexpr.add(Marlang::StrLen([children[0]])),
+            "marlang.operator.str.at" => mexpr.add(Marlang::StrAt([children[0], children[1]])),
+            "marlang.operator.str.substr" => {
+                mexpr.add(Marlang::StrSubstr([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.str.contains" => {
+                mexpr.add(Marlang::StrContains([children[0], children[1]]))
+            }
+            "marlang.operator.str.indexof" => {
+                mexpr.add(Marlang::StrIndexOf([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.str.replace" => {
+                mexpr.add(Marlang::StrReplace([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.str.replace_all" => mexpr.add(Marlang::StrReplaceAll([
+                children[0],
+                children[1],
+                children[2],
+            ])),
+            "marlang.operator.str.prefixof" => {
+                mexpr.add(Marlang::StrPrefixOf([children[0], children[1]]))
+            }
+            "marlang.operator.str.suffixof" => {
+                mexpr.add(Marlang::StrSuffixOf([children[0], children[1]]))
+            }
+            "marlang.operator.str.is_digit" => mexpr.add(Marlang::StrIsDigit([children[0]])),
+            "marlang.operator.str.to_code" => mexpr.add(Marlang::StrToCode([children[0]])),
+            "marlang.operator.str.from_code" => mexpr.add(Marlang::StrFromCode([children[0]])),
+            "marlang.operator.str.to_int" => mexpr.add(Marlang::StrToInt([children[0]])),
+            "marlang.operator.str.from_int" => mexpr.add(Marlang::StrFromInt([children[0]])),
+            "marlang.operator.str.in_re" => mexpr.add(Marlang::StrInRe([children[0], children[1]])),
+            "marlang.operator.str.to_re" => mexpr.add(Marlang::StrToRe([children[0]])),
+            "marlang.operator.re.none" => mexpr.add(Marlang::ReNone),
+            "marlang.operator.re.all" => mexpr.add(Marlang::ReAll),
+            "marlang.operator.re.allchar" => mexpr.add(Marlang::ReAllChar),
+            "marlang.operator.re.*" => mexpr.add(Marlang::ReStar([children[0]])),
+            "marlang.operator.re.+" => mexpr.add(Marlang::RePlus([children[0]])),
+            "marlang.operator.re.opt" => mexpr.add(Marlang::ReOpt([children[0]])),
+            "marlang.operator.re.comp" => mexpr.add(Marlang::ReComp([children[0]])),
+            "marlang.operator.re.diff" => mexpr.add(Marlang::ReDiff([children[0], children[1]])),
+            "marlang.operator.re.range" => mexpr.add(Marlang::ReRange([children[0], children[1]])),
+            "marlang.operator.re.loop" => {
+                mexpr.add(Marlang::ReLoop([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.re.^" => mexpr.add(Marlang::RePower([children[0], children[1]])),
+            "marlang.operator.fp" => {
+                mexpr.add(Marlang::FpLit([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.abs" => mexpr.add(Marlang::FpAbs([children[0]])),
+            "marlang.operator.fp.neg" => mexpr.add(Marlang::FpNeg([children[0]])),
+            "marlang.operator.fp.add" => {
+                mexpr.add(Marlang::FpAdd([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.sub" => {
+                mexpr.add(Marlang::FpSub([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.mul" => {
+                mexpr.add(Marlang::FpMul([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.div" => {
+                mexpr.add(Marlang::FpDiv([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.fma" => mexpr.add(Marlang::FpFma([
+                children[0],
+                children[1],
+                children[2],
+                children[3],
+            ])),
+            "marlang.operator.fp.sqrt" => mexpr.add(Marlang::FpSqrt([children[0], children[1]])),
+            "marlang.operator.fp.rem" => mexpr.add(Marlang::FpRem([children[0], children[1]])),
+            "marlang.operator.fp.roundToIntegral" => {
+                mexpr.add(Marlang::FpRoundToIntegral([children[0], children[1]]))
+            }
+            "marlang.operator.fp.min" => mexpr.add(Marlang::FpMin([children[0], children[1]])),
+            "marlang.operator.fp.max" => mexpr.add(Marlang::FpMax([children[0], children[1]])),
+            "marlang.operator.fp.leq" => mexpr.add(Marlang::FpLeq([children[0], children[1]])),
+            "marlang.operator.fp.lt" => mexpr.add(Marlang::FpLt([children[0], children[1]])),
+            "marlang.operator.fp.geq" => mexpr.add(Marlang::FpGeq([children[0], children[1]])),
+            "marlang.operator.fp.gt" => mexpr.add(Marlang::FpGt([children[0], children[1]])),
+            "marlang.operator.fp.eq" => mexpr.add(Marlang::FpEq([children[0], children[1]])),
+            "marlang.operator.fp.isNormal" => mexpr.add(Marlang::FpIsNormal([children[0]])),
+            "marlang.operator.fp.isSubnormal" => mexpr.add(Marlang::FpIsSubnormal([children[0]])),
+            "marlang.operator.fp.isZero" => mexpr.add(Marlang::FpIsZero([children[0]])),
+            "marlang.operator.fp.isInfinite" => mexpr.add(Marlang::FpIsInfinite([children[0]])),
+            "marlang.operator.fp.isNaN" => mexpr.add(Marlang::FpIsNaN([children[0]])),
+            "marlang.operator.fp.isNegative" => mexpr.add(Marlang::FpIsNegative([children[0]])),
+            "marlang.operator.fp.isPositive" => mexpr.add(Marlang::FpIsPositive([children[0]])),
+            "marlang.operator.fp.to_fp.real" => mexpr.add(Marlang::FpFromReal([
+                children[0],
+                children[1],
+                children[2],
+                children[3],
+            ])),
+            "marlang.operator.fp.to_fp.bv" => {
+                mexpr.add(Marlang::FpFromBv([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.to_fp.sbv" => mexpr.add(Marlang::FpFromSbv([
+                children[0],
+                children[1],
+                children[2],
+                children[3],
+            ])),
+            "marlang.operator.fp.to_fp.fp" => mexpr.add(Marlang::FpFromFp([
+                children[0],
+                children[1],
+                children[2],
+                children[3],
+            ])),
+            "marlang.operator.fp.to_fp_unsigned" => mexpr.add(Marlang::FpFromUbv([
+                children[0],
+                children[1],
+                children[2],
+                children[3],
+            ])),
+            "marlang.operator.fp.to_ubv" => {
+                mexpr.add(Marlang::FpToUbv([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.to_sbv" => {
+                mexpr.add(Marlang::FpToSbv([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.fp.to_real" => mexpr.add(Marlang::FpToReal([children[0]])),
+            "marlang.operator.array.select" => {
+                mexpr.add(Marlang::Select([children[0], children[1]]))
+            }
+            "marlang.operator.array.store" => {
+                mexpr.add(Marlang::Store([children[0], children[1], children[2]]))
+            }
+            "marlang.operator.array.const" => {
+                mexpr.add(Marlang::ConstArray([children[0], children[1]]))
+            }
+            "marlang.datatype" => mexpr.add(Marlang::Datatype([children[0], children[1]])),
+            "marlang.datatype.constructor" => mexpr.add(Marlang::Constructor([
+                children[0],
+                children[1],
+                children[2],
+            ])),
+            "marlang.datatype.selector" => {
+                mexpr.add(Marlang::Selector([children[0], children[1], children[2]]))
+            }
+            "marlang.datatype.tester" => mexpr.add(Marlang::Tester([children[0]])),
+            "marlang.operator.core.forall" => {
+                mexpr.add(Marlang::Forall([children[0], children[1]]))
+            }
+            "marlang.operator.core.exists" => {
+                mexpr.add(Marlang::Exists([children[0], children[1]]))
+            }
+            "marlang.operator.core.!" => mexpr.add(Marlang::Annotate([children[0], children[1]])),
+            "marlang.attribute.pattern" => mexpr.add(Marlang::PatternAttr([children[0]])),
+            "marlang.attribute.named" => mexpr.add(Marlang::NamedAttr([children[0]])),
             "marlang.command.set-logic" => mexpr.add(Marlang::SetLogic([children[0]])),
             "marlang.command.check-sat" => mexpr.add(Marlang::CheckSat),
             "marlang.command.assert" => mexpr.add(Marlang::Assert([children[0]])),
@@ -180,16 +413,90 @@ pub fn read_leda<T: Read>(source: &mut T) -> io::Result<MarRecExpr> {
                 children[2],
                 children[3],
             ])),
+            "marlang.function.rec" => {
+                mexpr.add(Marlang::RecFun([children[0], children[1], children[2]]))
+            }
+            "marlang.command.define-fun-rec" => mexpr.add(Marlang::DefineFunRec([
+                children[0],
+                children[1],
+                children[2],
+            ])),
+            "marlang.command.define-funs-rec" => mexpr.add(Marlang::DefineFunsRec([children[0]])),
+            "marlang.command.declare-datatype" => {
+                mexpr.add(Marlang::DeclareDatatype([children[0]]))
+            }
+            "marlang.command.declare-datatypes" => {
+                mexpr.add(Marlang::DeclareDatatypes([children[0]]))
+            }
+            "marlang.command.declare-sort" => {
+                mexpr.add(Marlang::DeclareSort([children[0], children[1]]))
+            }
+            "marlang.command.define-sort" => {
+                mexpr.add(Marlang::DefineSort([children[0], children[1], children[2]]))
+            }
+            "marlang.command.push" => mexpr.add(Marlang::Push([children[0]])),
+            "marlang.command.pop" => mexpr.add(Marlang::Pop([children[0]])),
+            "marlang.command.reset-assertions" => mexpr.add(Marlang::ResetAssertions),
+            "marlang.command.check-sat-assuming" => {
+                mexpr.add(Marlang::CheckSatAssuming([children[0]]))
+            }
+            "marlang.command.get-model" => mexpr.add(Marlang::GetModel),
+            "marlang.command.get-value" => mexpr.add(Marlang::GetValue([children[0]])),
+            "marlang.command.set-option" => {
+                mexpr.add(Marlang::SetOption([children[0], children[1]]))
+            }
+            "marlang.command.set-info" => mexpr.add(Marlang::SetInfo([children[0], children[1]])),
+            "marlang.command.exit" => mexpr.add(Marlang::Exit),
+            "marlang.command.synth-fun" => mexpr.add(Marlang::SynthFun([
+                children[0],
+                children[1],
+                children[2],
+                children[3],
+            ])),
+            "marlang.command.declare-var" => {
+                mexpr.add(Marlang::DeclareVar([children[0], children[1]]))
+            }
+            "marlang.command.constraint" => mexpr.add(Marlang::Constraint([children[0]])),
+            "marlang.command.check-synth" => mexpr.add(Marlang::CheckSynth),
+            "marlang.sygus.nonterminal" => mexpr.add(Marlang::NonTerminal([
+                children[0],
+                children[1],
+                children[2],
+            ])),
+            "marlang.sygus.constant" => mexpr.add(Marlang::GrammarConstant([children[0]])),
+            "marlang.sygus.variable" => mexpr.add(Marlang::GrammarVariable([children[0]])),
+            "marlang.command.declare-rel" => {
+                mexpr.add(Marlang::DeclareRel([children[0], children[1]]))
+            }
+            "marlang.command.rule" => mexpr.add(Marlang::Rule([children[0]])),
+            "marlang.command.query" => mexpr.add(Marlang::Query([children[0]])),
+            "marlang.command.minimize" => mexpr.add(Marlang::Minimize([children[0]])),
+            "marlang.command.maximize" => mexpr.add(Marlang::Maximize([children[0]])),
+            "marlang.command.get-objectives" => mexpr.add(Marlang::GetObjectives),
             "marlang.meta.cons" => mexpr.add(Marlang::Cons([children[0], children[1]])),
             "marlang.meta.nil" => mexpr.add(Marlang::Nil),
             "marlang.sort.bool" => mexpr.add(Marlang::BoolSort),
             "marlang.sort.int" => mexpr.add(Marlang::IntSort),
             "marlang.sort.real" => mexpr.add(Marlang::RealSort),
             "marlang.sort.string" => mexpr.add(Marlang::StringSort),
+            "marlang.sort.reglan" => mexpr.add(Marlang::RegLanSort),
+            "marlang.sort.bitvec" => mexpr.add(Marlang::BitVecSort([children[0]])),
+            "marlang.sort.array" => mexpr.add(Marlang::ArraySort([children[0], children[1]])),
+            "marlang.sort.apply" => mexpr.add(Marlang::SortApply([children[0], children[1]])),
+            "marlang.sort.datatype" => mexpr.add(Marlang::DatatypeSort([children[0]])),
             "marlang.value.bool" => mexpr.add(Marlang::BoolVal([children[0]])),
             "marlang.value.int" => mexpr.add(Marlang::IntVal([children[0]])),
             "marlang.value.real" => mexpr.add(Marlang::RealVal([children[0]])),
             "marlang.value.string" => mexpr.add(Marlang::StringVal([children[0]])),
+            "marlang.value.bv" => mexpr.add(Marlang::BvVal([children[0], children[1]])),
+            "marlang.sort.fp" => mexpr.add(Marlang::FpSort([children[0], children[1]])),
+            "marlang.value.rm" => mexpr.add(Marlang::RmVal([children[0]])),
+            "marlang.value.fp.+zero" => mexpr.add(Marlang::FpPosZero([children[0], children[1]])),
+            "marlang.value.fp.-zero" => mexpr.add(Marlang::FpNegZero([children[0], children[1]])),
+            "marlang.value.fp.+oo" => mexpr.add(Marlang::FpPosInf([children[0], children[1]])),
+            "marlang.value.fp.-oo" => mexpr.add(Marlang::FpNegInf([children[0], children[1]])),
+            "marlang.value.fp.NaN" => mexpr.add(Marlang::FpNaN([children[0], children[1]])),
+            "marlang.sort.roundingmode" => mexpr.add(Marlang::RoundingModeSort),
             s => mexpr.add(Marlang::Symbol(s.into())),
         };
     }
@@ -0,0 +1,98 @@
+// Helpers shared by the integration tests. Each test crate only uses some of them.
+#![allow(dead_code)]
+
+use std::io::BufWriter;
+
+use marlang::{
+    ast::{MarId, Marlang},
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    smtlib::write_smtlib,
+    solver::{Backend, SatResult},
+};
+
+pub fn print(program: &mut MarContext) -> String {
+    let mut buffer = BufWriter::new(Vec::new());
+    write_smtlib(&mut buffer, &program.extract_best()).expect("Must be able to print program");
+    std::str::from_utf8(buffer.buffer()).unwrap().to_string()
+}
+
+pub fn call(program: &mut MarContext, def: MarId, args: Vec<MarId>) -> MarId {
+    let mut list = program.mk_nil();
+    for a in args.into_iter().rev() {
+        list = program.mk_cons(a, list);
+    }
+    program.mk_call(def, list)
+}
+
+pub fn value(program: &MarContext, id: MarId) -> MarValue {
+    value_in(program, id, &MarModel::new())
+}
+
+pub fn value_in(program: &MarContext, id: MarId, model: &MarModel) -> MarValue {
+    let expr = program.get_expr(id);
+    let root = (expr.as_ref().len() - 1).into();
+    eval(&expr, root, model).expect("Must be able to evaluate term")
+}
+
+// Decides scripts over constants by trying every assignment, where Bool constants range over
+// both values and all others over `values`. Counts the calls.
+pub struct Enumerate {
+    pub values: Vec<MarValue>,
+    pub calls: usize,
+}
+
+impl Enumerate {
+    pub fn new(values: impl IntoIterator<Item = MarValue>) -> Self {
+        Enumerate {
+            values: values.into_iter().collect(),
+            calls: 0,
+        }
+    }
+
+    pub fn ints(ints: impl IntoIterator<Item = i32>) -> Self {
+        Self::new(ints.into_iter().map(|i| MarValue::Int(i.into())))
+    }
+}
+
+impl Backend for Enumerate {
+    fn check(&mut self, script: &mut MarContext) -> SatResult {
+        self.calls += 1;
+        let mut vars = vec![];
+        let mut assertions = vec![];
+        for command in script.active_commands() {
+            let expr = script.get_expr(command);
+            match expr.as_ref().last() {
+                Some(Marlang::DeclareFun([n, _, s])) => {
+                    let values: Vec<MarValue> = match expr[*s] {
+                        Marlang::BoolSort => vec![MarValue::Bool(false), MarValue::Bool(true)],
+                        _ => self.values.clone(),
+                    };
+                    vars.push((expr[*n].to_string(), values));
+                }
+                Some(Marlang::Assert([e])) => {
+                    let e = *e;
+                    assertions.push((expr, e));
+                }
+                _ => (),
+            }
+        }
+
+        let total: usize = vars.iter().map(|(_, values)| values.len()).product();
+        for mut index in 0..total {
+            let mut model = MarModel::new();
+            for (name, values) in &vars {
+                model.insert(name, values[index % values.len()].clone());
+                index /= values.len();
+            }
+            let holds = assertions.iter().all(|(expr, e)| {
+                eval(expr, *e, &model).expect("Must be able to evaluate assertion")
+                    == MarValue::Bool(true)
+            });
+            if holds {
+                return SatResult::Sat(model);
+            }
+        }
+        SatResult::Unsat
+    }
+}
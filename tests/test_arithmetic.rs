@@ -0,0 +1,110 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::MarSort,
+    context::MarContext,
+    eval::MarValue,
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::{print, value};
+
+#[test]
+fn division_and_conversions() {
+    let mut program = MarContext::new();
+
+    let seven = program.mk_int_val(7);
+    let minus_seven = program.mk_int_val(-7);
+    let two = program.mk_int_val(2);
+    let minus_two = program.mk_int_val(-2);
+    let minus_one_half = program.mk_real_val("-1.5");
+
+    let div = program.mk_int_div(vec![minus_seven, two]);
+    let modulo = program.mk_int_mod(minus_seven, two);
+    let div_neg = program.mk_int_div(vec![seven, minus_two]);
+    let mod_neg = program.mk_int_mod(seven, minus_two);
+    let abs = program.mk_int_abs(minus_seven);
+    let neg = program.mk_int_neg(seven);
+    let to_int = program.mk_to_int(minus_one_half);
+    let is_int = program.mk_is_int(minus_one_half);
+    let to_real = program.mk_to_real(seven);
+    let distinct = program.mk_distinct(vec![seven, abs, two]);
+
+    assert_eq!(program.graph()[to_int].data.sort, Some(MarSort::Int));
+    assert_eq!(program.graph()[to_real].data.sort, Some(MarSort::Real));
+    assert_eq!(program.graph()[distinct].data.sort, Some(MarSort::Bool));
+
+    let int = |i: i32| MarValue::Int(i.into());
+    assert_eq!(value(&program, div), int(-4));
+    assert_eq!(value(&program, modulo), int(1));
+    assert_eq!(value(&program, div_neg), int(-3));
+    assert_eq!(value(&program, mod_neg), int(1));
+    assert_eq!(value(&program, abs), int(7));
+    assert_eq!(value(&program, neg), int(-7));
+    assert_eq!(value(&program, to_int), int(-2));
+    assert_eq!(value(&program, is_int), MarValue::Bool(false));
+    assert_eq!(value(&program, to_real), MarValue::Real(7.into()));
+    assert_eq!(value(&program, distinct), MarValue::Bool(false));
+}
+
+#[test]
+fn mixed_arithmetic() {
+    let mut program = MarContext::new().with_mixed_arithmetic();
+
+    let int_sort = program.mk_int_sort();
+    let real_sort = program.mk_real_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let y_def = program.declare_const("y", real_sort);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let y = program.mk_call(y_def, empty);
+    let half = program.mk_real_val("0.5");
+
+    let sum = program.mk_real_add(vec![x, y]);
+    let gt = program.mk_real_gt(vec![sum, half]);
+    program.assert(gt);
+    let eq = program.mk_eq(vec![x, y]);
+    program.assert(eq);
+    let neg = program.mk_int_neg(x);
+    let eq = program.mk_eq(vec![neg, x]);
+    program.assert(eq);
+
+    assert_eq!(
+        print(&mut program),
+        "(declare-fun x () Int)
+(declare-fun y () Real)
+(assert (> (+ (to_real x) y) 0.5))
+(assert (= (to_real x) y))
+(assert (= (- x) x))
+"
+    );
+}
+
+#[test]
+fn leda_round_trip() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let three = program.mk_int_val(3);
+    let div = program.mk_int_div(vec![x, three]);
+    let modulo = program.mk_int_mod(x, three);
+    let abs = program.mk_int_abs(modulo);
+    let real = program.mk_to_real(div);
+    let neg = program.mk_real_neg(real);
+    let to_int = program.mk_to_int(neg);
+    let is_int = program.mk_is_int(neg);
+    let distinct = program.mk_distinct(vec![abs, to_int]);
+    let both = program.mk_and(vec![distinct, is_int]);
+    program.assert(both);
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
@@ -0,0 +1,64 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::MarSort,
+    context::MarContext,
+    rewrites,
+    util::{read_leda, write_leda},
+};
+
+#[test]
+fn read_over_write() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let mem_sort = program.mk_array_sort(int_sort, int_sort);
+    let empty = program.mk_nil();
+    let m_def = program.declare_const("m", mem_sort);
+    let m = program.mk_call(m_def, empty);
+    let i_def = program.declare_const("i", int_sort);
+    let i = program.mk_call(i_def, empty);
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+
+    let stored = program.mk_store(m, i, one);
+    let read = program.mk_select(stored, i);
+    let read_eq_one = program.mk_eq(vec![read, one]);
+    program.assert(read_eq_one);
+
+    let zeros = program.mk_const_array(mem_sort, zero);
+    let read_zero = program.mk_select(zeros, one);
+    assert_eq!(program.graph()[read_zero].data.sort, Some(MarSort::Int));
+
+    program.add_rewrites(rewrites::arrays());
+    let program = program.simplify(5);
+
+    let read = program.get_expr(read);
+    let one = program.get_expr(one);
+    assert!(program.equiv(read, one));
+    let read_zero = program.get_expr(read_zero);
+    let zero = program.get_expr(zero);
+    assert!(program.equiv(read_zero, zero));
+}
+
+#[test]
+fn leda_round_trip() {
+    let mut program = MarContext::new();
+
+    let bv8 = program.mk_bitvec_sort(8);
+    let mem_sort = program.mk_array_sort(bv8, bv8);
+    let zero = program.mk_bv_val(0, 8);
+    let zeros = program.mk_const_array(mem_sort, zero);
+    let one = program.mk_bv_val(1, 8);
+    let stored = program.mk_store(zeros, zero, one);
+    let read = program.mk_select(stored, one);
+    let read_eq_zero = program.mk_eq(vec![read, zero]);
+    program.assert(read_eq_zero);
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
@@ -0,0 +1,140 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::{MarRecExpr, MarSort, Marlang},
+    context::MarContext,
+    eval::{eval, EvalError, MarModel},
+    util::{read_leda, write_leda},
+};
+
+#[test]
+fn widths() {
+    let mut program = MarContext::new();
+
+    let bv8 = program.mk_bitvec_sort(8);
+    let x_def = program.declare_const("x", bv8);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let one = program.mk_bv_literal("#x01");
+    let x_plus_one = program.mk_bv_add(vec![x, one]);
+    let high = program.mk_bv_extract(7, 4, x_plus_one);
+    let wide = program.mk_bv_concat(high, x);
+    let wider = program.mk_bv_sign_extend(4, wide);
+    let zero = program.mk_bv_val(0, 16);
+    let lt = program.mk_bv_slt(wider, zero);
+
+    let sort = |id| program.graph()[id].data.sort.clone();
+    assert_eq!(sort(x_plus_one), Some(MarSort::BitVec(8)));
+    assert_eq!(sort(high), Some(MarSort::BitVec(4)));
+    assert_eq!(sort(wide), Some(MarSort::BitVec(12)));
+    assert_eq!(sort(wider), Some(MarSort::BitVec(16)));
+    assert_eq!(sort(lt), Some(MarSort::Bool));
+    assert_eq!(program.mk_bv_literal("#b00000001"), one);
+}
+
+#[test]
+#[should_panic(expected = "bit-vector width mismatch")]
+fn width_mismatch() {
+    let mut program = MarContext::new();
+    let x = program.mk_bv_val(1, 8);
+    let y = program.mk_bv_val(1, 16);
+    program.mk_bv_add(vec![x, y]);
+}
+
+#[test]
+#[should_panic(expected = "bit-vectors must have a positive width")]
+fn zero_width_value() {
+    let mut program = MarContext::new();
+    program.mk_bv_val(0, 0);
+}
+
+#[test]
+fn overflowing_widths() {
+    let mut program = MarContext::new();
+    let x = program.mk_bv_val(1, 8);
+    let extended = program.mk_bv_zero_extend(u32::MAX, x);
+    let repeated = program.mk_bv_repeat(u32::MAX, x);
+    let whole = program.mk_bv_extract(7, 0, x);
+
+    let sort = |id| program.graph()[id].data.sort.clone();
+    assert_eq!(sort(extended), None);
+    assert_eq!(sort(repeated), None);
+    assert_eq!(sort(whole), Some(MarSort::BitVec(8)));
+}
+
+#[test]
+fn leda_round_trip() {
+    let mut program = MarContext::new();
+
+    let bv4 = program.mk_bitvec_sort(4);
+    let x_def = program.declare_const("x", bv4);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let two = program.mk_bv_val(2, 4);
+    let shifted = program.mk_bv_shl(x, two);
+    let rotated = program.mk_bv_rotate_left(1, shifted);
+    let extended = program.mk_bv_zero_extend(4, rotated);
+    let max = program.mk_bv_literal("#xff");
+    let le = program.mk_bv_ule(extended, max);
+    program.assert(le);
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
+
+#[test]
+fn malformed_extract() {
+    // (_ extract 0 3) cannot be built through the context, but can come from a file
+    let mut expr = MarRecExpr::default();
+    let zero = expr.add(Marlang::Symbol("0".into()));
+    let three = expr.add(Marlang::Symbol("3".into()));
+    let eight = expr.add(Marlang::Symbol("8".into()));
+    let x = expr.add(Marlang::BvVal([three, eight]));
+    let extract = expr.add(Marlang::BvExtract([zero, three, x]));
+    assert_eq!(
+        eval(&expr, extract, &MarModel::new()),
+        Err(EvalError::Malformed(
+            "extract 0 3 of a bit-vector of width 8".into()
+        ))
+    );
+}
+
+#[test]
+fn malformed_widths() {
+    // read_leda accepts any numerals here, so the evaluator has to check them itself
+    let mut expr = MarRecExpr::default();
+    let zero = expr.add(Marlang::Symbol("0".into()));
+    let one = expr.add(Marlang::Symbol("1".into()));
+    let eight = expr.add(Marlang::Symbol("8".into()));
+    let huge = expr.add(Marlang::Symbol(u32::MAX.to_string()));
+    let empty = expr.add(Marlang::BvVal([zero, zero]));
+    let rotated = expr.add(Marlang::BvRotateLeft([one, empty]));
+    let less = expr.add(Marlang::BvSlt([empty, empty]));
+    let x = expr.add(Marlang::BvVal([one, eight]));
+    let extended = expr.add(Marlang::BvZeroExtend([huge, x]));
+    let repeated = expr.add(Marlang::BvRepeat([huge, x]));
+    let bit = expr.add(Marlang::BvVal([one, one]));
+    let many = expr.add(Marlang::Symbol("100000".into()));
+    let long = expr.add(Marlang::BvRepeat([many, bit]));
+
+    let width_0 = Err(EvalError::Malformed("a bit-vector of width 0".into()));
+    let overflow = Err(EvalError::Malformed(
+        "a bit-vector width that overflows".into(),
+    ));
+    let model = MarModel::new();
+    assert_eq!(eval(&expr, empty, &model), width_0);
+    assert_eq!(eval(&expr, rotated, &model), width_0);
+    assert_eq!(eval(&expr, less, &model), width_0);
+    assert_eq!(eval(&expr, extended, &model), overflow);
+    assert_eq!(eval(&expr, repeated, &model), overflow);
+    assert_eq!(
+        eval(&expr, long, &model),
+        Err(EvalError::Unsupported(
+            "a bit-vector of width 100000".into()
+        ))
+    );
+}
@@ -0,0 +1,146 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::{MarId, MarSort},
+    context::MarContext,
+    rewrites,
+    smtlib::write_smtlib,
+    util::{read_leda, write_leda},
+};
+
+// (declare-datatypes ((List 0)) (((nil) (cons (head Int) (tail List)))))
+fn list_program() -> (MarContext, MarId, MarId, MarId, MarId, MarId) {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let list_sort = program.mk_datatype_sort("List");
+    let nil = program.mk_constructor("List", "nil", vec![]);
+    let head = program.mk_selector("List", "head", int_sort);
+    let tail = program.mk_selector("List", "tail", list_sort);
+    let cons = program.mk_constructor("List", "cons", vec![head, tail]);
+    let list = program.mk_datatype("List", vec![nil, cons]);
+    program.declare_datatypes(vec![list]);
+
+    (program, list_sort, nil, cons, head, tail)
+}
+
+#[test]
+fn selectors_and_testers() {
+    let (mut program, list_sort, nil, cons, head, tail) = list_program();
+
+    let empty = program.mk_nil();
+    let l_def = program.declare_const("l", list_sort);
+    let l = program.mk_call(l_def, empty);
+    let one = program.mk_int_val(1);
+    let args = program.mk_cons(l, empty);
+    let args = program.mk_cons(one, args);
+    let one_l = program.mk_call(cons, args);
+    let one_l = program.mk_cons(one_l, empty);
+    let head_one_l = program.mk_call(head, one_l);
+    let tail_one_l = program.mk_call(tail, one_l);
+    let is_cons = program.mk_tester(cons);
+    let is_cons_one_l = program.mk_call(is_cons, one_l);
+    let is_nil = program.mk_tester(nil);
+    let is_nil_one_l = program.mk_call(is_nil, one_l);
+    let t = program.mk_bool_val(true);
+    let f = program.mk_bool_val(false);
+
+    assert_eq!(
+        program.graph()[l].data.sort,
+        Some(MarSort::Datatype("List".into()))
+    );
+    assert_eq!(program.graph()[head_one_l].data.sort, Some(MarSort::Int));
+    assert_eq!(program.graph()[is_nil_one_l].data.sort, Some(MarSort::Bool));
+
+    program.add_rewrites(rewrites::datatypes());
+    let program = program.simplify(5);
+
+    let equiv = |x, y| program.equiv(program.get_expr(x), program.get_expr(y));
+    assert!(equiv(head_one_l, one));
+    assert!(equiv(tail_one_l, l));
+    assert!(equiv(is_cons_one_l, t));
+    assert!(equiv(is_nil_one_l, f));
+}
+
+#[test]
+fn print_smtlib() {
+    let (mut program, list_sort, nil, _, head, _) = list_program();
+
+    let empty = program.mk_nil();
+    let l_def = program.declare_const("l", list_sort);
+    let l = program.mk_call(l_def, empty);
+    let l = program.mk_cons(l, empty);
+    let is_nil = program.mk_tester(nil);
+    let l_is_nil = program.mk_call(is_nil, l);
+    let not_nil = program.mk_not(l_is_nil);
+    program.assert(not_nil);
+    let head_l = program.mk_call(head, l);
+    let zero = program.mk_int_val(0);
+    let head_gt_zero = program.mk_int_gt(vec![head_l, zero]);
+    program.assert(head_gt_zero);
+
+    let mut buffer = BufWriter::new(Vec::new());
+    write_smtlib(&mut buffer, &program.extract_best()).expect("Must be able to print program");
+    let output = std::str::from_utf8(buffer.buffer()).unwrap().to_string();
+
+    assert_eq!(
+        output,
+        "(declare-datatypes ((List 0)) (((nil) (cons (head Int) (tail List)))))
+(declare-fun l () List)
+(assert (not ((_ is nil) l)))
+(assert (> (head l) 0))
+"
+    );
+}
+
+#[test]
+fn leda_round_trip() {
+    let (mut program, list_sort, _, cons, _, _) = list_program();
+
+    let empty = program.mk_nil();
+    let l_def = program.declare_const("l", list_sort);
+    let l = program.mk_call(l_def, empty);
+    let l = program.mk_cons(l, empty);
+    let is_cons = program.mk_tester(cons);
+    let l_is_cons = program.mk_call(is_cons, l);
+    program.assert(l_is_cons);
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
+
+#[test]
+fn shared_selector_names() {
+    let mut program = MarContext::new();
+
+    // (declare-datatypes ((Box 0) (Pair 0)) (((box (val Int))) ((pair (key Int) (val Int)))))
+    let int_sort = program.mk_int_sort();
+    let box_val = program.mk_selector("Box", "val", int_sort);
+    let boxed = program.mk_constructor("Box", "box", vec![box_val]);
+    let box_datatype = program.mk_datatype("Box", vec![boxed]);
+    let key = program.mk_selector("Pair", "key", int_sort);
+    let pair_val = program.mk_selector("Pair", "val", int_sort);
+    let pair = program.mk_constructor("Pair", "pair", vec![key, pair_val]);
+    let pair_datatype = program.mk_datatype("Pair", vec![pair]);
+    program.declare_datatypes(vec![box_datatype, pair_datatype]);
+    assert_ne!(box_val, pair_val);
+
+    // only the selector of Box reads the field of a box
+    let empty = program.mk_nil();
+    let five = program.mk_int_val(5);
+    let args = program.mk_cons(five, empty);
+    let five_boxed = program.mk_call(boxed, args);
+    let args = program.mk_cons(five_boxed, empty);
+    let box_val_five = program.mk_call(box_val, args);
+    let pair_val_five = program.mk_call(pair_val, args);
+
+    program.add_rewrites(rewrites::datatypes());
+    let program = program.simplify(5);
+    let equiv = |x, y| program.equiv(program.get_expr(x), program.get_expr(y));
+    assert!(equiv(box_val_five, five));
+    assert!(!equiv(pair_val_five, five));
+}
@@ -0,0 +1,203 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::{MarId, MarRecExpr, MarSort, Marlang},
+    context::MarContext,
+    eval::{eval, EvalError, MarModel, MarValue},
+    float::{Fp, FpFormat},
+    smtlib::write_smtlib,
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::value;
+
+fn bits(program: &MarContext, id: MarId) -> u64 {
+    match value(program, id) {
+        MarValue::Float(eb, sb, x) => {
+            let (sign, e, m) = FpFormat::new(eb, sb).to_bits(&x);
+            let e = e.to_u64().unwrap();
+            let m = m.to_u64().unwrap();
+            ((sign as u64) << (eb + sb - 1)) | (e << (sb - 1)) | m
+        }
+        v => panic!("expected a float but found {}", v),
+    }
+}
+
+const DOUBLES: [f64; 9] = [
+    0.1,
+    -0.3,
+    1.0,
+    3.0,
+    1e308,
+    -2.5e-310,
+    4.9e-324,
+    123456789.125,
+    -0.0,
+];
+
+#[test]
+fn float64_matches_hardware() {
+    let mut program = MarContext::new();
+    let rne = program.mk_rounding_mode("RNE");
+
+    for x in DOUBLES {
+        for y in DOUBLES {
+            let fx = program.mk_float64(x);
+            let fy = program.mk_float64(y);
+            let add = program.mk_fp_add(rne, fx, fy);
+            let sub = program.mk_fp_sub(rne, fx, fy);
+            let mul = program.mk_fp_mul(rne, fx, fy);
+            let div = program.mk_fp_div(rne, fx, fy);
+            let fma = program.mk_fp_fma(rne, fx, fy, fx);
+            let sqrt = program.mk_fp_sqrt(rne, fx);
+
+            let expected = [x + y, x - y, x * y, x / y, x.mul_add(y, x), x.sqrt()];
+            for (term, expected) in [add, sub, mul, div, fma, sqrt].into_iter().zip(expected) {
+                if expected.is_nan() {
+                    assert!(matches!(
+                        value(&program, term),
+                        MarValue::Float(_, _, Fp::NaN)
+                    ));
+                } else {
+                    assert_eq!(bits(&program, term), expected.to_bits(), "{} {}", x, y);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn float32_conversions() {
+    let mut program = MarContext::new();
+    let rne = program.mk_rounding_mode("roundNearestTiesToEven");
+    let rtz = program.mk_rounding_mode("RTZ");
+    let rtp = program.mk_rounding_mode("RTP");
+
+    let tenth = program.mk_real_val("0.1");
+    let tenth = program.mk_to_fp_from_real(8, 24, rne, tenth);
+    let big = program.mk_float64(1e300);
+    let narrowed = program.mk_to_fp_from_fp(8, 24, rne, big);
+    let truncated = program.mk_to_fp_from_fp(8, 24, rtz, big);
+    let minus_seven = program.mk_bv_val(249, 8);
+    let from_sbv = program.mk_to_fp_from_sbv(8, 24, rne, minus_seven);
+    let from_ubv = program.mk_to_fp_from_ubv(8, 24, rne, minus_seven);
+    let raw = program.mk_bv_val(0x3fc0_0000u32, 32);
+    let from_bv = program.mk_to_fp_from_bv(8, 24, raw);
+    let half_up = program.mk_float32(2.5);
+    let to_sbv = program.mk_fp_to_sbv(8, rtp, half_up);
+    let to_integral = program.mk_fp_round_to_integral(rne, half_up);
+    let to_real = program.mk_fp_to_real(half_up);
+
+    assert_eq!(
+        program.graph()[tenth].data.sort,
+        Some(MarSort::FloatingPoint(8, 24))
+    );
+    assert_eq!(program.graph()[to_sbv].data.sort, Some(MarSort::BitVec(8)));
+
+    assert_eq!(bits(&program, tenth), 0.1f32.to_bits() as u64);
+    assert_eq!(bits(&program, narrowed), f32::INFINITY.to_bits() as u64);
+    assert_eq!(bits(&program, truncated), f32::MAX.to_bits() as u64);
+    assert_eq!(bits(&program, from_sbv), (-7.0f32).to_bits() as u64);
+    assert_eq!(bits(&program, from_ubv), 249.0f32.to_bits() as u64);
+    assert_eq!(bits(&program, from_bv), 1.5f32.to_bits() as u64);
+    assert_eq!(value(&program, to_sbv), MarValue::BitVec(8, 3.into()));
+    assert_eq!(bits(&program, to_integral), 2.0f32.to_bits() as u64);
+    assert_eq!(value(&program, to_real), MarValue::Real((5, 2).into()));
+}
+
+#[test]
+fn classification() {
+    let mut program = MarContext::new();
+
+    let zero = program.mk_fp_neg_zero(8, 24);
+    let nan = program.mk_fp_nan(8, 24);
+    let tiny = program.mk_float32(1e-40);
+    let one = program.mk_float32(1.0);
+
+    let checks = [
+        (program.mk_fp_is_zero(zero), true),
+        (program.mk_fp_is_negative(zero), true),
+        (program.mk_fp_is_positive(nan), false),
+        (program.mk_fp_is_nan(nan), true),
+        (program.mk_fp_is_subnormal(tiny), true),
+        (program.mk_fp_is_normal(tiny), false),
+        (program.mk_fp_is_normal(one), true),
+        (program.mk_fp_eq(nan, nan), false),
+        (program.mk_fp_leq(zero, tiny), true),
+    ];
+    let positive_zero = program.mk_fp_pos_zero(8, 24);
+    let zeros_equal = program.mk_fp_eq(zero, positive_zero);
+    let zeros_same = program.mk_eq(vec![zero, positive_zero]);
+
+    for (term, expected) in checks {
+        assert_eq!(value(&program, term), MarValue::Bool(expected));
+    }
+    assert_eq!(value(&program, zeros_equal), MarValue::Bool(true));
+    assert_eq!(value(&program, zeros_same), MarValue::Bool(false));
+}
+
+#[test]
+fn print_and_round_trip() {
+    let mut program = MarContext::new();
+
+    let float_sort = program.mk_fp_sort(8, 24);
+    let x_def = program.declare_const("x", float_sort);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let rm_sort = program.mk_rounding_mode_sort();
+    let rm_def = program.declare_const("rm", rm_sort);
+    let rm = program.mk_call(rm_def, empty);
+    let one = program.mk_float32(1.0);
+    let sum = program.mk_fp_add(rm, x, one);
+    let inf = program.mk_fp_pos_inf(8, 24);
+    let lt = program.mk_fp_lt(sum, inf);
+    program.assert(lt);
+    let rtz = program.mk_rounding_mode("RTZ");
+    let sbv = program.mk_fp_to_sbv(32, rtz, x);
+    let zero = program.mk_bv_val(0, 32);
+    let positive = program.mk_bv_sgt(sbv, zero);
+    program.assert(positive);
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_smtlib(&mut buffer, &expr).expect("Must be able to print program");
+    let output = std::str::from_utf8(buffer.buffer()).unwrap().to_string();
+    assert_eq!(
+        output,
+        "(declare-fun x () (_ FloatingPoint 8 24))
+(declare-fun rm () RoundingMode)
+(assert (fp.lt (fp.add rm x (fp (_ bv0 1) (_ bv127 8) (_ bv0 23))) (_ +oo 8 24)))
+(assert (bvsgt ((_ fp.to_sbv 32) RTZ x) (_ bv0 32)))
+"
+    );
+
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
+
+#[test]
+fn malformed_widths() {
+    // read_leda accepts any numerals here, so the evaluator has to check them itself
+    let mut expr = MarRecExpr::default();
+    let zero = expr.add(Marlang::Symbol("0".into()));
+    let eight = expr.add(Marlang::Symbol("8".into()));
+    let twenty_four = expr.add(Marlang::Symbol("24".into()));
+    let narrow = expr.add(Marlang::FpPosZero([zero, twenty_four]));
+    let rm = expr.add(Marlang::Symbol("RNE".into()));
+    let rne = expr.add(Marlang::RmVal([rm]));
+    let x = expr.add(Marlang::FpPosZero([eight, twenty_four]));
+    let to_sbv = expr.add(Marlang::FpToSbv([zero, rne, x]));
+
+    let model = MarModel::new();
+    assert_eq!(
+        eval(&expr, narrow, &model),
+        Err(EvalError::Malformed("(_ FloatingPoint 0 24)".into()))
+    );
+    assert_eq!(
+        eval(&expr, to_sbv, &model),
+        Err(EvalError::Malformed("a bit-vector of width 0".into()))
+    );
+}
@@ -0,0 +1,160 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::MarId,
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    smtlib::write_horn,
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::print;
+
+fn print_horn(program: &mut MarContext) -> String {
+    let mut buffer = BufWriter::new(Vec::new());
+    write_horn(&mut buffer, &program.extract_best()).expect("Must be able to print clauses");
+    std::str::from_utf8(buffer.buffer()).unwrap().to_string()
+}
+
+fn apply(program: &mut MarContext, relation: MarId, args: Vec<MarId>) -> MarId {
+    let mut list = program.mk_nil();
+    for a in args.into_iter().rev() {
+        list = program.mk_cons(a, list);
+    }
+    program.mk_call(relation, list)
+}
+
+#[test]
+fn counter_clauses() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let inv = program.declare_rel("inv", vec![int_sort]);
+    let x = program.mk_symbol("x");
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let ten = program.mk_int_val(10);
+    let inv_x = apply(&mut program, inv, vec![x]);
+    let succ = program.mk_int_add(vec![x, one]);
+    let inv_succ = apply(&mut program, inv, vec![succ]);
+    let is_zero = program.mk_eq(vec![x, zero]);
+    let below = program.mk_int_lt(vec![x, ten]);
+    let above = program.mk_int_gt(vec![x, ten]);
+
+    program.rule(vec![("x", int_sort)], vec![is_zero], inv_x);
+    program.rule(vec![("x", int_sort)], vec![inv_x, below], inv_succ);
+    program.query(vec![("x", int_sort)], vec![inv_x, above]);
+    assert_eq!(program.horn_clauses().len(), 3);
+
+    assert_eq!(
+        print(&mut program),
+        "(declare-rel inv (Int))
+(rule (forall ((x Int)) (=> (= x 0) (inv x))))
+(rule (forall ((x Int)) (=> (and (inv x) (< x 10)) (inv (+ x 1)))))
+(query (exists ((x Int)) (and (inv x) (> x 10))))
+"
+    );
+    assert_eq!(
+        print_horn(&mut program),
+        "(set-logic HORN)
+(declare-fun inv (Int) Bool)
+(assert (forall ((x Int)) (=> (= x 0) (inv x))))
+(assert (forall ((x Int)) (=> (and (inv x) (< x 10)) (inv (+ x 1)))))
+(assert (forall ((x Int)) (=> (and (inv x) (> x 10)) false)))
+(check-sat)
+"
+    );
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write clauses to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse clauses");
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
+
+#[test]
+fn simplify_clause_bodies() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let bool_sort = program.mk_bool_sort();
+    let reach = program.declare_rel("reach", vec![int_sort, bool_sort]);
+    let x = program.mk_symbol("x");
+    let b = program.mk_symbol("b");
+    let zero = program.mk_int_val(0);
+    let x_plus_zero = program.mk_int_add(vec![x, zero]);
+    let reach_x = apply(&mut program, reach, vec![x_plus_zero, b]);
+    let positive = program.mk_int_gt(vec![x_plus_zero, zero]);
+    let not_b = program.mk_not(b);
+    let reach_not = apply(&mut program, reach, vec![x, not_b]);
+    program.rule(
+        vec![("x", int_sort), ("b", bool_sort)],
+        vec![reach_x, positive],
+        reach_not,
+    );
+
+    let a = program.mk_symbol("a");
+    let a_plus_zero = program.mk_int_add(vec![a, zero]);
+    let left = program.get_pattern(a_plus_zero, vec![a]);
+    let right = program.get_pattern(a, vec![a]);
+    program.add_rewrite("add-zero".into(), left, right);
+    let mut program = program.simplify(4);
+
+    assert_eq!(
+        print_horn(&mut program),
+        "(set-logic HORN)
+(declare-fun reach (Int Bool) Bool)
+(assert (forall ((x Int) (b Bool)) (=> (and (reach x b) (> x 0)) (reach x (not b)))))
+(check-sat)
+"
+    );
+}
+
+#[test]
+fn facts_and_relation_models() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    program.set_logic("HORN".into());
+    let start = program.declare_rel("start", vec![]);
+    let even = program.declare_rel("even", vec![int_sort]);
+    let start_holds = apply(&mut program, start, vec![]);
+    let n = program.mk_symbol("n");
+    let two = program.mk_int_val(2);
+    let zero = program.mk_int_val(0);
+    let even_zero = apply(&mut program, even, vec![zero]);
+    let plus_two = program.mk_int_add(vec![n, two]);
+    let even_n = apply(&mut program, even, vec![n]);
+    let even_plus_two = apply(&mut program, even, vec![plus_two]);
+    program.rule(Vec::<(&str, MarId)>::new(), vec![], start_holds);
+    program.rule(Vec::<(&str, MarId)>::new(), vec![start_holds], even_zero);
+    program.rule(vec![("n", int_sort)], vec![even_n], even_plus_two);
+    let one = program.mk_int_val(1);
+    let even_one = apply(&mut program, even, vec![one]);
+    program.query(Vec::<(&str, MarId)>::new(), vec![even_one]);
+    program.check_sat();
+
+    assert_eq!(
+        print_horn(&mut program),
+        "(set-logic HORN)
+(declare-fun start () Bool)
+(declare-fun even (Int) Bool)
+(assert start)
+(assert (=> start (even 0)))
+(assert (forall ((n Int)) (=> (even n) (even (+ n 2)))))
+(assert (=> (even 1) false))
+(check-sat)
+"
+    );
+
+    // relations are interpreted by the model, like declared functions
+    let body = program.get_expr(even_one);
+    let mut model = MarModel::new();
+    model.insert_entry("even", vec![MarValue::Int(0.into())], MarValue::Bool(true));
+    model.insert_default("even", MarValue::Bool(false));
+    assert_eq!(
+        eval(&body, (body.as_ref().len() - 1).into(), &model),
+        Ok(MarValue::Bool(false))
+    );
+}
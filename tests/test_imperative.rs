@@ -0,0 +1,149 @@
+use marlang::{
+    eval::{eval, MarModel, MarValue},
+    imperative::{compile, Location},
+};
+
+mod common;
+use common::print;
+
+const DISTANCE: &str = "
+var x: int;
+var y: int = 0;
+assume x > 0;
+if x > 10 {
+    y = x - 10;
+} else {
+    y = 10 - x; // the distance to 10
+}
+for i in 0..2 {
+    y = y + i;
+}
+assert y >= 1;
+assert y > 1;
+";
+
+#[test]
+fn ssa_and_verification_conditions() {
+    let mut compiled = compile(DISTANCE).expect("Must be able to compile program");
+
+    assert_eq!(
+        print(&mut compiled.context),
+        "(declare-fun x@0 () Int)
+(define-fun y@0 () Int 0)
+(define-fun y@1 () Int (- x@0 10))
+(define-fun y@2 () Int (- 10 x@0))
+(define-fun y@3 () Int (ite (> x@0 10) y@1 y@2))
+(define-fun y@4 () Int (+ y@3 0))
+(define-fun y@5 () Int (+ y@4 1))
+(push 1)
+(assert (not (=> (> x@0 0) (>= y@5 1))))
+(check-sat)
+(pop 1)
+(push 1)
+(assert (not (=> (> x@0 0) (> y@5 1))))
+(check-sat)
+(pop 1)
+"
+    );
+
+    let locations: Vec<Location> = compiled.vcs.iter().map(|vc| vc.location).collect();
+    assert_eq!(
+        locations,
+        vec![
+            Location {
+                line: 13,
+                column: 1
+            },
+            Location {
+                line: 14,
+                column: 1
+            }
+        ]
+    );
+
+    // the first condition is valid while the second fails when x is 10
+    let holds = |vc: usize, x: i32| {
+        let expr = compiled.context.get_expr(compiled.vcs[vc].term);
+        let root = (expr.as_ref().len() - 1).into();
+        let mut model = MarModel::new();
+        model.insert("x@0", MarValue::Int(x.into()));
+        eval(&expr, root, &model).expect("Must be able to evaluate condition")
+            == MarValue::Bool(true)
+    };
+    assert!((-5..30).all(|x| holds(0, x)));
+    assert!(holds(1, 9));
+    assert!(!holds(1, 10));
+}
+
+#[test]
+fn branches_and_types() {
+    let source = "
+var r: real = 0.5;
+var b: bool;
+var n: int;
+if b {
+    r = r * 2.0;
+    assert r > 0.5;
+} else if n < -3 {
+    r = -r;
+} else {
+    assume !b && n >= -3;
+}
+assert r != 0.0 || b;
+";
+    let mut compiled = compile(source).expect("Must be able to compile program");
+    let output = print(&mut compiled.context);
+
+    assert!(output.contains("(define-fun r@1 () Real (* r@0 2.0))"));
+    assert!(output.contains("(define-fun r@2 () Real (- r@0))"));
+    assert!(output.contains("(define-fun r@3 () Real (ite (< n@0 (- 3)) r@2 r@0))"));
+    assert!(output.contains("(define-fun r@4 () Real (ite b@0 r@1 r@3))"));
+    // the branch condition guards the first assertion and the assumption
+    assert!(output.contains("(assert (not (=> b@0 (> r@1 0.5))))"));
+    assert!(output.contains(
+        "(assert (not (=> (=> (and (not b@0) (not (< n@0 (- 3)))) (and (not b@0) (>= n@0 (- 3)))) (or (distinct r@4 0.0) b@0))))"
+    ));
+    assert_eq!(compiled.vcs.len(), 2);
+}
+
+#[test]
+fn source_located_errors() {
+    let cases = [
+        ("var x: int;\nx = true;", "2:5: expected int but found bool"),
+        ("var x: int;\ny = 1;", "2:1: y is not declared"),
+        (
+            "for i in 0..3 {\n  i = 1;\n}",
+            "2:3: cannot assign to loop counter i",
+        ),
+        (
+            "var x: int\nassert x;",
+            "2:1: expected ';' but found 'assert'",
+        ),
+        ("assert 1 < true;", "1:12: expected int but found bool"),
+        ("var x: int = 1 @ 2;", "1:16: unexpected character '@'"),
+        (
+            "if true { var z: int; }\nassert z == 0;",
+            "2:8: z is not declared",
+        ),
+        ("var x: int;\nvar x: bool;", "2:1: x is already declared"),
+        ("assert true && 1;", "1:16: expected bool but found int"),
+        (
+            "var b: bool;\nassert b + b;",
+            "2:10: + does not apply to bool",
+        ),
+        (
+            "for i in 0..n { }",
+            "1:13: expected a constant loop bound but found 'n'",
+        ),
+        (
+            "if true {\n  assert true;\n",
+            "3:1: expected '}' but found the end of the input",
+        ),
+    ];
+    for (source, message) in cases {
+        match compile(source) {
+            Err(e) => assert_eq!(e.to_string(), message, "{}", source),
+            Ok(_) => panic!("expected an error for {}", source),
+        }
+    }
+}
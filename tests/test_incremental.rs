@@ -0,0 +1,118 @@
+use std::io::BufWriter;
+
+use marlang::{
+    context::MarContext,
+    smtlib::write_smtlib,
+    util::{read_leda, write_leda},
+};
+
+#[test]
+fn scopes() {
+    let mut program = MarContext::new().with_assume_mode();
+
+    program.set_option(":produce-models", "true");
+    program.set_info(":status", "sat");
+    let int_sort = program.mk_int_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let x_gt_zero = program.mk_int_gt(vec![x, zero]);
+    let first = program.assert(x_gt_zero);
+
+    program.push(1);
+    let y_def = program.declare_const("y", int_sort);
+    let y = program.mk_call(y_def, empty);
+    let y_is_one = program.mk_eq(vec![y, one]);
+    let x_is_y = program.mk_eq(vec![x, y]);
+    program.assert(y_is_one);
+    program.assert(x_is_y);
+    assert_eq!(program.scope_depth(), 1);
+    assert_eq!(program.active_commands().len(), 5);
+    program.check_sat();
+    program.get_value(vec![x, y]);
+    program.pop(1);
+
+    assert_eq!(program.scope_depth(), 0);
+    assert_eq!(program.active_commands(), vec![x_def, first]);
+    assert_eq!(program.active_assertions(), vec![x_gt_zero]);
+
+    let not_one = program.mk_not(x_is_y);
+    program.check_sat_assuming(vec![not_one]);
+    program.get_model();
+    program.exit();
+
+    let mut buffer = BufWriter::new(Vec::new());
+    write_smtlib(&mut buffer, &program.extract_best()).expect("Must be able to print program");
+    let output = std::str::from_utf8(buffer.buffer()).unwrap().to_string();
+
+    assert_eq!(
+        output,
+        "(set-option :produce-models true)
+(set-info :status sat)
+(declare-fun x () Int)
+(assert (> x 0))
+(push 1)
+(declare-fun y () Int)
+(assert (= y 1))
+(assert (= x y))
+(check-sat)
+(get-value (x y))
+(pop 1)
+(check-sat-assuming ((not (= x y))))
+(get-model)
+(exit)
+"
+    );
+
+    // the popped equalities must not be assumed
+    let program = program.simplify(1);
+    assert!(!program.equiv(program.get_expr(x), program.get_expr(one)));
+}
+
+#[test]
+fn reset_assertions() {
+    let mut program = MarContext::new();
+
+    let bool_sort = program.mk_bool_sort();
+    let p_def = program.declare_const("p", bool_sort);
+    program.push(2);
+    let empty = program.mk_nil();
+    let p = program.mk_call(p_def, empty);
+    program.assert(p);
+    program.reset_assertions();
+
+    assert_eq!(program.scope_depth(), 0);
+    assert!(program.active_commands().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "cannot pop 2 scopes out of 1")]
+fn pop_too_far() {
+    let mut program = MarContext::new();
+    program.push(1);
+    program.pop(2);
+}
+
+#[test]
+fn leda_round_trip() {
+    let mut program = MarContext::new();
+
+    program.set_option(":produce-models", "true");
+    program.push(1);
+    let t = program.mk_bool_val(true);
+    program.check_sat_assuming(vec![t]);
+    program.get_value(vec![t]);
+    program.get_model();
+    program.pop(1);
+    program.reset_assertions();
+    program.exit();
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
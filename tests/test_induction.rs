@@ -0,0 +1,149 @@
+use marlang::{
+    ast::MarId,
+    eval::MarValue,
+    induction::{check_invariant, inductive_step, k_induction, Inductiveness, Verdict},
+    solver::{Backend, SatResult},
+    transition::TransitionSystem,
+};
+
+mod common;
+use common::Enumerate;
+
+// x starts at 0 and flips between 0 and 1, while 3 is a stuck state that can jump to 4.
+// Only the paths -2, 3, 4 and -2, 3, 3, 4, which stays at 3 under a different input, lead to 4
+// without revisiting a step.
+fn flipper() -> (TransitionSystem, MarId) {
+    let mut system = TransitionSystem::new();
+
+    let int_sort = system.context().mk_int_sort();
+    let bool_sort = system.context().mk_bool_sort();
+    let (x, next_x) = system.add_state("x", int_sort);
+    let b = system.add_input("b", bool_sort);
+
+    let program = system.context();
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let three = program.mk_int_val(3);
+    let four = program.mk_int_val(4);
+    let init = program.mk_eq(vec![x, zero]);
+    let is_three = program.mk_eq(vec![x, three]);
+    let jump = program.mk_ite(b, three, four);
+    let flip = program.mk_int_sub(vec![one, x]);
+    let step = program.mk_ite(is_three, jump, flip);
+    let next = program.mk_eq(vec![next_x, step]);
+    let safe = program.mk_distinct(vec![x, four]);
+
+    system.add_init(init);
+    system.add_next(next);
+    system.add_property("safe", safe);
+    (system, x)
+}
+
+#[test]
+fn proved_with_simple_paths() {
+    let (system, _) = flipper();
+    let mut backend = Enumerate::ints(-2..=4);
+
+    // -2, 3, 3, 4 still reaches the bad state in three steps without revisiting a step
+    assert!(matches!(
+        backend.check(&mut inductive_step(&system, 3)),
+        SatResult::Sat(_)
+    ));
+    assert_eq!(k_induction(&system, &mut backend, 4), Verdict::Proved(4));
+    assert_eq!(k_induction(&system, &mut backend, 3), Verdict::Unknown);
+}
+
+#[test]
+fn counterexample() {
+    let mut system = TransitionSystem::new();
+    let int_sort = system.context().mk_int_sort();
+    let (x, next_x) = system.add_state("x", int_sort);
+    let program = system.context();
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let two = program.mk_int_val(2);
+    let init = program.mk_eq(vec![x, zero]);
+    let succ = program.mk_int_add(vec![x, one]);
+    let next = program.mk_eq(vec![next_x, succ]);
+    let small = program.mk_int_lt(vec![x, two]);
+    system.add_init(init);
+    system.add_next(next);
+    system.add_property("small", small);
+
+    let mut backend = Enumerate::ints(0..=3);
+    match k_induction(&system, &mut backend, 5) {
+        Verdict::Counterexample(trace) => {
+            let xs: Vec<Option<&MarValue>> = trace.iter().map(|step| step.get("x")).collect();
+            assert_eq!(
+                xs,
+                vec![
+                    Some(&MarValue::Int(0.into())),
+                    Some(&MarValue::Int(1.into())),
+                    Some(&MarValue::Int(2.into()))
+                ]
+            );
+        }
+        verdict => panic!("expected a counterexample but got {:?}", verdict),
+    }
+}
+
+#[test]
+fn properties_over_inputs() {
+    // x never changes, so only a difference in the input keeps the path simple
+    let mut system = TransitionSystem::new();
+    let int_sort = system.context().mk_int_sort();
+    let (x, next_x) = system.add_state("x", int_sort);
+    let i = system.add_input("i", int_sort);
+    let program = system.context();
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let init_x = program.mk_eq(vec![x, zero]);
+    let init_i = program.mk_eq(vec![i, one]);
+    let init = program.mk_and(vec![init_x, init_i]);
+    let next = program.mk_eq(vec![next_x, x]);
+    let nonnegative = program.mk_int_ge(vec![i, zero]);
+    system.add_init(init);
+    system.add_next(next);
+    system.add_property("nonnegative", nonnegative);
+
+    let mut backend = Enumerate::ints(-1..=1);
+    match k_induction(&system, &mut backend, 3) {
+        Verdict::Counterexample(trace) => {
+            assert_eq!(trace.len(), 2);
+            assert_eq!(trace[1].get("i"), Some(&MarValue::Int((-1).into())));
+        }
+        verdict => panic!("expected a counterexample but got {:?}", verdict),
+    }
+}
+
+#[test]
+fn invariants() {
+    let (mut system, x) = flipper();
+    let mut backend = Enumerate::ints(-2..=4);
+
+    let program = system.context();
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let nonnegative = program.mk_int_ge(vec![x, zero]);
+    let at_most_one = program.mk_int_le(vec![x, one]);
+    let bit = program.mk_and(vec![nonnegative, at_most_one]);
+    let is_one = program.mk_eq(vec![x, one]);
+
+    assert_eq!(
+        check_invariant(&system, bit, &mut backend),
+        Inductiveness::Inductive
+    );
+
+    let int = |i: i32| Some(MarValue::Int(i.into()));
+    match check_invariant(&system, nonnegative, &mut backend) {
+        Inductiveness::NotInductive(before, after) => {
+            assert_eq!(before.get("x").cloned(), int(2));
+            assert_eq!(after.get("x").cloned(), int(-1));
+        }
+        result => panic!("expected a step out of the invariant but got {:?}", result),
+    }
+    match check_invariant(&system, is_one, &mut backend) {
+        Inductiveness::NotInitial(state) => assert_eq!(state.get("x").cloned(), int(0)),
+        result => panic!("expected a bad initial state but got {:?}", result),
+    }
+}
@@ -0,0 +1,182 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::{MarId, MarRecExpr, Marlang},
+    context::MarContext,
+    eval::{eval, EvalError, MarModel, MarValue},
+    lets::{expand_lets, extract_with_lets},
+    smtlib::write_smtlib,
+    util::decompose_using_expr,
+};
+
+mod common;
+use common::print;
+
+fn show(expr: &MarRecExpr) -> String {
+    let mut buffer = BufWriter::new(Vec::new());
+    write_smtlib(&mut buffer, expr).expect("Must be able to print program");
+    std::str::from_utf8(buffer.buffer()).unwrap().to_string()
+}
+
+fn constant(program: &mut MarContext, name: &str) -> MarId {
+    let int_sort = program.mk_int_sort();
+    let def = program.declare_const(name, int_sort);
+    let empty = program.mk_nil();
+    program.mk_call(def, empty)
+}
+
+// Quantified assertions come out as errors, since they cannot be evaluated
+fn assertions(expr: &MarRecExpr, model: &MarModel) -> Vec<Result<MarValue, EvalError>> {
+    decompose_using_expr(expr, (expr.as_ref().len() - 1).into())
+        .into_iter()
+        .filter_map(|c| match expr[c] {
+            Marlang::Assert([e]) => Some(eval(expr, e, model)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn expand_parallel_bindings() {
+    let mut program = MarContext::new();
+
+    let a = program.mk_symbol("a");
+    let b = program.mk_symbol("b");
+    let one = program.mk_int_val(1);
+    let two = program.mk_int_val(2);
+    let difference = program.mk_int_sub(vec![a, b]);
+    let swapped = program.mk_let(vec![("a".into(), b), ("b".into(), a)], difference);
+    let outer = program.mk_let(vec![("a".into(), one), ("b".into(), two)], swapped);
+    let correct = program.mk_eq(vec![outer, one]);
+    program.assert(correct);
+
+    assert_eq!(
+        print(&mut program),
+        "(assert (= (let ((a 1) (b 2)) (let ((a b) (b a)) (- a b))) 1))\n"
+    );
+    let mut expanded = expand_lets(&program);
+    assert_eq!(print(&mut expanded), "(assert (= (- 2 1) 1))\n");
+    assert_eq!(
+        assertions(&expanded.extract_best(), &MarModel::new()),
+        assertions(&program.extract_best(), &MarModel::new())
+    );
+}
+
+#[test]
+fn expand_without_capture() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let x = program.mk_symbol("x");
+    let z = program.mk_symbol("z");
+    let below = program.mk_int_lt(vec![x, z]);
+    let inner = program.mk_exists(vec![("z", int_sort)], below);
+    let bound = program.mk_let(vec![("x".into(), z)], inner);
+    let outer = program.mk_forall(vec![("z", int_sort)], bound);
+    program.assert(outer);
+    program.check_sat();
+
+    // the z that x stands for is the outer one, so the inner z has to make way
+    let mut expanded = expand_lets(&program);
+    assert_eq!(
+        print(&mut expanded),
+        "(assert (forall ((z Int)) (exists ((z!1 Int)) (< z z!1))))
+(check-sat)
+"
+    );
+    assert_eq!(expanded.active_assertions().len(), 1);
+}
+
+#[test]
+fn bind_shared_terms() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let x = constant(&mut program, "x");
+    let one = program.mk_int_val(1);
+    let zero = program.mk_int_val(0);
+    let s = program.mk_int_add(vec![x, one]);
+    let t = program.mk_int_mul(vec![s, s]);
+    let u = program.mk_int_mul(vec![t, t]);
+    let positive = program.mk_int_gt(vec![u, zero]);
+    program.assert(positive);
+    // (* s y) mentions y, so only s can be bound outside of the quantifier
+    let y = program.mk_symbol("y");
+    let scaled = program.mk_int_mul(vec![s, y]);
+    let above = program.mk_int_gt(vec![scaled, s]);
+    let all = program.mk_forall(vec![("y", int_sort)], above);
+    program.assert(all);
+
+    let shared = extract_with_lets(&mut program);
+    assert_eq!(
+        show(&shared),
+        "(declare-fun x () Int)
+(assert (let ((a!1 (+ x 1))) (let ((a!2 (* a!1 a!1))) (> (* a!2 a!2) 0))))
+(assert (let ((a!3 (+ x 1))) (forall ((y Int)) (> (* a!3 y) a!3))))
+"
+    );
+
+    let mut model = MarModel::new();
+    model.insert("x", MarValue::Int(2.into()));
+    assert_eq!(assertions(&shared, &model)[0], Ok(MarValue::Bool(true)));
+    assert_eq!(
+        assertions(&shared, &model),
+        assertions(&program.extract_best(), &model)
+    );
+}
+
+#[test]
+fn linear_output_for_deep_sharing() {
+    let mut program = MarContext::new();
+
+    // the tree of this term has 2^40 leaves
+    let mut t = constant(&mut program, "x");
+    for _ in 0..40 {
+        t = program.mk_int_add(vec![t, t]);
+    }
+    let zero = program.mk_int_val(0);
+    let is_zero = program.mk_eq(vec![t, zero]);
+    program.assert(is_zero);
+
+    let printed = show(&extract_with_lets(&mut program));
+    assert_eq!(printed.matches("(let ").count(), 39);
+    assert!(printed.len() < 2000, "{}", printed);
+}
+
+#[test]
+fn bindings_do_not_leak() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let x = constant(&mut program, "x");
+    let bound_x = program.mk_symbol("x");
+    let five = program.mk_int_val(5);
+    let sum = program.mk_int_add(vec![x, bound_x]);
+    let shadowed = program.mk_let(vec![("x".into(), five)], sum);
+    let six = program.mk_int_val(6);
+    let global = program.mk_eq(vec![shadowed, six]);
+    program.assert(global);
+
+    // the body of plus_m sees m from the model, not the m bound around the call
+    let n = program.mk_symbol("n");
+    let m = program.mk_symbol("m");
+    let plus = program.mk_int_add(vec![n, m]);
+    let plus_m = program.define_fun("plus_m", vec![("n", int_sort)], int_sort, plus);
+    let one = program.mk_int_val(1);
+    let hundred = program.mk_int_val(100);
+    let nil = program.mk_nil();
+    let args = program.mk_cons(one, nil);
+    let called = program.mk_call(plus_m, args);
+    let around = program.mk_let(vec![("m".into(), hundred)], called);
+    let two = program.mk_int_val(2);
+    let local = program.mk_eq(vec![around, two]);
+    program.assert(local);
+
+    let mut model = MarModel::new();
+    model.insert("x", MarValue::Int(1.into()));
+    model.insert("m", MarValue::Int(1.into()));
+    assert_eq!(
+        assertions(&program.extract_best(), &model),
+        [Ok(MarValue::Bool(true)), Ok(MarValue::Bool(true))]
+    );
+}
@@ -0,0 +1,136 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::MarId,
+    eval::MarValue,
+    ltl::{counterexample, loop_start, witness},
+    smtlib::to_smtlib,
+    solver::{Backend, SatResult},
+    transition::TransitionSystem,
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::Enumerate;
+
+// x counts 0, 1, 2, 0, ... and comes with the terms x = 0, x = 1 and x = 2
+fn cycle() -> (TransitionSystem, MarId, [MarId; 3]) {
+    let mut system = TransitionSystem::new();
+
+    let int_sort = system.context().mk_int_sort();
+    let (x, next_x) = system.add_state("x", int_sort);
+
+    let program = system.context();
+    let values = [0, 1, 2].map(|i| program.mk_int_val(i));
+    let is = values.map(|v| program.mk_eq(vec![x, v]));
+    let succ = program.mk_int_add(vec![x, values[1]]);
+    let step = program.mk_ite(is[2], values[0], succ);
+    let next = program.mk_eq(vec![next_x, step]);
+
+    system.add_init(is[0]);
+    system.add_next(next);
+    (system, x, is)
+}
+
+fn backend() -> Enumerate {
+    Enumerate::ints(0..=2)
+}
+
+#[test]
+fn liveness() {
+    let (mut system, _, is) = cycle();
+
+    let program = system.context();
+    let eventually_zero = program.mk_finally(is[0]);
+    let infinitely_zero = program.mk_globally(eventually_zero);
+    let always_zero = program.mk_globally(is[0]);
+    let stays_zero = program.mk_finally(always_zero);
+
+    for k in 0..=3 {
+        assert_eq!(
+            backend().check(&mut counterexample(&system, infinitely_zero, k)),
+            SatResult::Unsat
+        );
+    }
+
+    // the shortest lasso breaking F G (x = 0) goes around the whole cycle
+    assert_eq!(
+        backend().check(&mut counterexample(&system, stays_zero, 1)),
+        SatResult::Unsat
+    );
+    match backend().check(&mut counterexample(&system, stays_zero, 2)) {
+        SatResult::Sat(model) => {
+            let trace = system.decode_trace(&model, 2);
+            let xs: Vec<MarValue> = trace.iter().map(|s| s.get("x").unwrap().clone()).collect();
+            assert_eq!(xs, [0, 1, 2].map(|i| MarValue::Int(i.into())));
+            assert_eq!(loop_start(&system, &trace), Some(0));
+        }
+        result => panic!("expected a counterexample but got {:?}", result),
+    }
+}
+
+#[test]
+fn until_and_release() {
+    let (mut system, x, is) = cycle();
+
+    let program = system.context();
+    let two = program.mk_int_val(2);
+    let x_lt_two = program.mk_int_lt(vec![x, two]);
+    let below_until_two = program.mk_until(x_lt_two, is[2]);
+    let zero_until_two = program.mk_until(is[0], is[2]);
+    let two_releases_zero = program.mk_release(is[2], is[0]);
+    let next_one = program.mk_next(is[1]);
+
+    assert!(matches!(
+        backend().check(&mut witness(&system, below_until_two, 2)),
+        SatResult::Sat(_)
+    ));
+    // until needs its goal within the bound, even if it happens later
+    assert_eq!(
+        backend().check(&mut witness(&system, below_until_two, 1)),
+        SatResult::Unsat
+    );
+    for k in 0..=3 {
+        assert_eq!(
+            backend().check(&mut witness(&system, zero_until_two, k)),
+            SatResult::Unsat
+        );
+        // zero is released only by two, which x = 1 breaks first
+        assert_eq!(
+            backend().check(&mut witness(&system, two_releases_zero, k)),
+            SatResult::Unsat
+        );
+    }
+    assert!(matches!(
+        backend().check(&mut witness(&system, next_one, 1)),
+        SatResult::Sat(_)
+    ));
+    assert_eq!(
+        backend().check(&mut counterexample(&system, next_one, 1)),
+        SatResult::Unsat
+    );
+}
+
+#[test]
+fn print_and_round_trip() {
+    let (mut system, _, is) = cycle();
+
+    let program = system.context();
+    let eventually = program.mk_finally(is[1]);
+    let always = program.mk_globally(eventually);
+    let until = program.mk_until(is[0], always);
+    let release = program.mk_release(is[2], until);
+    let formula = program.mk_next(release);
+
+    let expr = program.get_expr(formula);
+    let root = (expr.as_ref().len() - 1).into();
+    assert_eq!(
+        to_smtlib(&expr, root),
+        "(X (R (= x 2) (U (= x 0) (G (F (= x 1))))))"
+    );
+
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write formula to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse formula");
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
@@ -0,0 +1,115 @@
+use marlang::{
+    ast::{MarId, Marlang},
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    lets::expand_definitions,
+    util::decompose_using_expr,
+};
+
+mod common;
+use common::{call, print};
+
+fn assertions(program: &mut MarContext, model: &MarModel) -> Vec<MarValue> {
+    let expr = program.extract_best();
+    decompose_using_expr(&expr, (expr.as_ref().len() - 1).into())
+        .into_iter()
+        .filter_map(|c| match expr[c] {
+            Marlang::Assert([e]) => Some(eval(&expr, e, model).expect("Must evaluate")),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn nested_definitions() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let a_def = program.declare_const("a", int_sort);
+    let x = program.mk_symbol("x");
+    let y = program.mk_symbol("y");
+    let square = program.mk_int_mul(vec![x, x]);
+    let sq = program.define_fun("sq", vec![("x", int_sort)], int_sort, square);
+    let sq_x = call(&mut program, sq, vec![x]);
+    let sum = program.mk_int_add(vec![sq_x, y]);
+    let f = program.define_fun("f", vec![("x", int_sort), ("y", int_sort)], int_sort, sum);
+    let a = call(&mut program, a_def, vec![]);
+    let three = program.mk_int_val(3);
+    let ten = program.mk_int_val(10);
+    let f_three_a = call(&mut program, f, vec![three, a]);
+    let correct = program.mk_eq(vec![f_three_a, ten]);
+    program.assert(correct);
+    program.check_sat();
+
+    let mut expanded = expand_definitions(&program);
+    assert_eq!(
+        print(&mut expanded),
+        "(declare-fun a () Int)
+(assert (= (+ (* 3 3) a) 10))
+(check-sat)
+"
+    );
+
+    let mut model = MarModel::new();
+    model.insert("a", MarValue::Int(1.into()));
+    assert_eq!(
+        assertions(&mut expanded, &model),
+        assertions(&mut program, &model)
+    );
+}
+
+#[test]
+fn arguments_are_not_captured() {
+    let mut program = MarContext::new();
+
+    // (define-fun g ((x Int)) Int (let ((y 1)) (+ x y))) applied to a bound y
+    let int_sort = program.mk_int_sort();
+    let x = program.mk_symbol("x");
+    let y = program.mk_symbol("y");
+    let one = program.mk_int_val(1);
+    let zero = program.mk_int_val(0);
+    let sum = program.mk_int_add(vec![x, y]);
+    let body = program.mk_let(vec![("y".into(), one)], sum);
+    let g = program.define_fun("g", vec![("x", int_sort)], int_sort, body);
+    let g_y = call(&mut program, g, vec![y]);
+    let is_zero = program.mk_eq(vec![g_y, zero]);
+    let all = program.mk_forall(vec![("y", int_sort)], is_zero);
+    program.assert(all);
+
+    assert_eq!(
+        print(&mut expand_definitions(&program)),
+        "(assert (forall ((y Int)) (= (let ((y!1 1)) (+ y y!1)) 0)))\n"
+    );
+}
+
+#[test]
+fn definitions_in_scopes() {
+    let mut program = MarContext::new();
+
+    let bool_sort = program.mk_bool_sort();
+    let p_def = program.declare_const("p", bool_sort);
+    let p = call(&mut program, p_def, vec![]);
+    program.push(1);
+    let not_p = program.mk_not(p);
+    let flipped = program.define_fun("flipped", Vec::<(&str, MarId)>::new(), bool_sort, not_p);
+    let flipped_call = call(&mut program, flipped, vec![]);
+    program.assert(flipped_call);
+    program.check_sat();
+    program.pop(1);
+    let t = program.mk_bool_val(true);
+    program.assert(t);
+
+    let mut expanded = expand_definitions(&program);
+    assert_eq!(expanded.scope_depth(), 0);
+    assert_eq!(expanded.active_commands().len(), 2);
+    assert_eq!(
+        print(&mut expanded),
+        "(declare-fun p () Bool)
+(push 1)
+(assert (not p))
+(check-sat)
+(pop 1)
+(assert true)
+"
+    );
+}
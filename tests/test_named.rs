@@ -0,0 +1,124 @@
+use std::io::BufWriter;
+
+use marlang::{
+    context::MarContext,
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::print;
+
+#[test]
+fn unsat_core_script() {
+    let mut program = MarContext::new();
+
+    program.set_option(":produce-unsat-cores", "true");
+    let int_sort = program.mk_int_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let zero = program.mk_int_val(0);
+    let positive = program.mk_int_gt(vec![x, zero]);
+    let negative = program.mk_int_lt(vec![x, zero]);
+    let first = program.assert_named(positive, "a1");
+    let second = program.assert_named(negative, "a2");
+    program.check_sat();
+    program.get_unsat_core();
+
+    assert_eq!(
+        program.named_assertions(),
+        vec![("a1".to_string(), first), ("a2".to_string(), second)]
+    );
+    assert_eq!(
+        print(&mut program),
+        "(set-option :produce-unsat-cores true)
+(declare-fun x () Int)
+(assert (! (> x 0) :named a1))
+(assert (! (< x 0) :named a2))
+(check-sat)
+(get-unsat-core)
+"
+    );
+}
+
+#[test]
+fn names_survive_simplify() {
+    for assume in [false, true] {
+        let mut program = MarContext::new();
+        if assume {
+            program = program.with_assume_mode();
+        }
+
+        let int_sort = program.mk_int_sort();
+        let x_def = program.declare_const("x", int_sort);
+        let empty = program.mk_nil();
+        let x = program.mk_call(x_def, empty);
+        let zero = program.mk_int_val(0);
+        let one = program.mk_int_val(1);
+        let x_plus_zero = program.mk_int_add(vec![x, zero]);
+        let left = program.get_pattern(x_plus_zero, vec![x]);
+        let right = program.get_pattern(x, vec![x]);
+        program.add_rewrite("add-zero".into(), left, right);
+        let is_one = program.mk_eq(vec![x_plus_zero, one]);
+        let first = program.assert_named(is_one, "first");
+        program.push(1);
+        let positive = program.mk_int_gt(vec![x_plus_zero, zero]);
+        program.assert_named(positive, "popped");
+        program.pop(1);
+        let not_one = program.mk_distinct(vec![x_plus_zero, one]);
+        let second = program.assert_named(not_one, "second");
+        program.check_sat();
+        program.get_unsat_core();
+
+        let mut program = program.simplify(5);
+        let output = print(&mut program);
+
+        // in assume mode x is merged with 1 everywhere but in the assumed assertion itself
+        let (positive, not_one) = if assume {
+            ("(> 1 0)", "(distinct 1 1)")
+        } else {
+            ("(> x 0)", "(distinct x 1)")
+        };
+        assert_eq!(
+            output,
+            format!(
+                "(declare-fun x () Int)
+(assert (! (= x 1) :named first))
+(push 1)
+(assert (! {} :named popped))
+(pop 1)
+(assert (! {} :named second))
+(check-sat)
+(get-unsat-core)
+",
+                positive, not_one
+            )
+        );
+        assert_eq!(
+            program.named_assertions(),
+            vec![("first".to_string(), first), ("second".to_string(), second)]
+        );
+    }
+}
+
+#[test]
+fn leda_round_trip() {
+    let mut program = MarContext::new();
+
+    let bool_sort = program.mk_bool_sort();
+    let p_def = program.declare_const("p", bool_sort);
+    let empty = program.mk_nil();
+    let p = program.mk_call(p_def, empty);
+    let not_p = program.mk_not(p);
+    program.assert_named(p, "yes");
+    program.assert_named(not_p, "no");
+    program.check_sat();
+    program.get_unsat_core();
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
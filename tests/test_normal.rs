@@ -0,0 +1,181 @@
+use marlang::{
+    ast::MarId,
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    normal::{cnf, dnf, nnf, prenex, TooLarge},
+};
+
+mod common;
+use common::{call, print};
+
+fn booleans(program: &mut MarContext, names: &[&str]) -> Vec<MarId> {
+    let bool_sort = program.mk_bool_sort();
+    names
+        .iter()
+        .map(|name| {
+            let def = program.declare_const(name, bool_sort);
+            call(program, def, vec![])
+        })
+        .collect()
+}
+
+fn values(script: &MarContext, model: &MarModel) -> Vec<MarValue> {
+    script
+        .active_assertions()
+        .into_iter()
+        .map(|a| {
+            let expr = script.get_expr(a);
+            eval(&expr, (expr.as_ref().len() - 1).into(), model).expect("Must be able to evaluate")
+        })
+        .collect()
+}
+
+#[test]
+fn negation_normal_form() {
+    let mut program = MarContext::new();
+
+    let [p, q, r, c]: [MarId; 4] = booleans(&mut program, &["p", "q", "r", "c"])
+        .try_into()
+        .unwrap();
+    let implies = program.mk_implies(q, r);
+    let ite = program.mk_ite(c, p, q);
+    let conjunction = program.mk_and(vec![p, implies, ite]);
+    let negated = program.mk_not(conjunction);
+    program.assert(negated);
+
+    let int_sort = program.mk_int_sort();
+    let x = program.mk_symbol("x");
+    let zero = program.mk_int_val(0);
+    let positive = program.mk_int_gt(vec![x, zero]);
+    let all = program.mk_forall(vec![("x", int_sort)], positive);
+    let not_all = program.mk_not(all);
+    program.assert(not_all);
+
+    assert_eq!(
+        print(&mut nnf(&program)),
+        "(declare-fun p () Bool)
+(declare-fun q () Bool)
+(declare-fun r () Bool)
+(declare-fun c () Bool)
+(assert (or (not p) (and q (not r)) (and (or (not c) (not p)) (or c (not q)))))
+(assert (exists ((x Int)) (not (> x 0))))
+"
+    );
+}
+
+#[test]
+fn clauses_and_cubes() {
+    let mut program = MarContext::new();
+
+    let [a, b, c, d]: [MarId; 4] = booleans(&mut program, &["a", "b", "c", "d"])
+        .try_into()
+        .unwrap();
+    let ab = program.mk_and(vec![a, b]);
+    let cd = program.mk_and(vec![c, d]);
+    let either = program.mk_or(vec![ab, cd]);
+    program.assert(either);
+    // the clause with both a and (not a) is dropped
+    let not_a = program.mk_not(a);
+    let tautology = program.mk_or(vec![a, not_a]);
+    let guarded = program.mk_and(vec![tautology, b]);
+    program.assert(guarded);
+
+    assert_eq!(
+        print(&mut cnf(&program, 10).unwrap()),
+        "(declare-fun a () Bool)
+(declare-fun b () Bool)
+(declare-fun c () Bool)
+(declare-fun d () Bool)
+(assert (and (or a c) (or a d) (or b c) (or b d)))
+(assert b)
+"
+    );
+    assert_eq!(
+        print(&mut dnf(&program, 10).unwrap()),
+        "(declare-fun a () Bool)
+(declare-fun b () Bool)
+(declare-fun c () Bool)
+(declare-fun d () Bool)
+(assert (or (and a b) (and c d)))
+(assert (or (and a b) (and (not a) b)))
+"
+    );
+}
+
+#[test]
+fn size_guards() {
+    let mut program = MarContext::new();
+
+    // 2^5 cubes, but only 5 clauses
+    let names = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"];
+    let vars = booleans(&mut program, &names);
+    let pairs: Vec<MarId> = vars
+        .chunks(2)
+        .map(|pair| program.mk_or(pair.to_vec()))
+        .collect();
+    let conjunction = program.mk_and(pairs);
+    let command = program.assert(conjunction);
+
+    assert!(cnf(&program, 5).is_ok());
+    assert_eq!(
+        dnf(&program, 16).err(),
+        Some(TooLarge {
+            command,
+            clauses: 17
+        })
+    );
+    assert!(dnf(&program, 32).is_ok());
+}
+
+#[test]
+fn prenex_and_equivalence() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let x = program.mk_symbol("x");
+    let y = program.mk_symbol("y");
+    let zero = program.mk_int_val(0);
+    let positive = program.mk_int_gt(vec![x, zero]);
+    let all = program.mk_forall(vec![("x", int_sort)], positive);
+    let same = program.mk_eq(vec![x, y]);
+    let some = program.mk_exists(vec![("y", int_sort)], same);
+    let all_some = program.mk_forall(vec![("x", int_sort)], some);
+    let not_all_some = program.mk_not(all_some);
+    let both = program.mk_and(vec![all, not_all_some]);
+    program.assert(both);
+
+    assert_eq!(
+        print(&mut prenex(&program)),
+        "(assert (forall ((x!1 Int)) (exists ((x!2 Int)) (forall ((y!3 Int)) (and (> x!1 0) (not (= x!2 y!3)))))))
+"
+    );
+
+    // every form agrees with the original on each assignment
+    let mut program = MarContext::new();
+    let [p, q, r, s]: [MarId; 4] = booleans(&mut program, &["p", "q", "r", "s"])
+        .try_into()
+        .unwrap();
+    let parity = program.mk_xor(vec![p, q, r]);
+    let implies = program.mk_implies(parity, s);
+    let ite = program.mk_ite(s, q, p);
+    let not_ite = program.mk_not(ite);
+    let formula = program.mk_or(vec![implies, not_ite]);
+    let negated = program.mk_not(formula);
+    program.assert(negated);
+    let forms = [
+        nnf(&program),
+        cnf(&program, 100).unwrap(),
+        dnf(&program, 100).unwrap(),
+        prenex(&program),
+    ];
+    for index in 0..16 {
+        let mut model = MarModel::new();
+        for (i, name) in ["p", "q", "r", "s"].iter().enumerate() {
+            model.insert(name, MarValue::Bool(index >> i & 1 == 1));
+        }
+        let expected = values(&program, &model);
+        for form in &forms {
+            assert_eq!(values(form, &model), expected);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::BufWriter;
+
+use rug::Rational;
+
+use marlang::{
+    ast::MarId,
+    context::MarContext,
+    eval::MarValue,
+    optimize::{optimize, Optimum},
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::{print, Enumerate};
+
+fn real(n: i32, d: i32) -> MarValue {
+    MarValue::Real(Rational::from((n, d)))
+}
+
+// every multiple of a half up to four
+fn backend() -> Enumerate {
+    Enumerate::new((0..=8).map(|i| real(i, 2)))
+}
+
+fn constant(program: &mut MarContext, name: &str) -> MarId {
+    let real_sort = program.mk_real_sort();
+    let def = program.declare_const(name, real_sort);
+    let empty = program.mk_nil();
+    program.mk_call(def, empty)
+}
+
+#[test]
+fn lexicographic_schedule() {
+    let mut program = MarContext::new();
+
+    // a takes one unit, b takes two and comes after a, and c takes one and must end with b
+    let a = constant(&mut program, "a");
+    let b = constant(&mut program, "b");
+    let c = constant(&mut program, "c");
+    let one = program.mk_real_val("1.0");
+    let two = program.mk_real_val("2.0");
+    let a_end = program.mk_real_add(vec![a, one]);
+    let b_end = program.mk_real_add(vec![b, two]);
+    let c_end = program.mk_real_add(vec![c, one]);
+    let ordered = program.mk_real_ge(vec![b, a_end]);
+    let within = program.mk_real_le(vec![c_end, b_end]);
+    program.assert(ordered);
+    program.assert(within);
+    program.minimize(b_end);
+    program.maximize(c);
+
+    let before = print(&mut program);
+    let optima = optimize(&program, &mut backend(), 20);
+    assert_eq!(optima.len(), 2);
+    match &optima[0] {
+        Optimum::Optimal(value, model) => {
+            assert_eq!(value, &real(3, 1));
+            assert_eq!(model.get("a"), Some(&real(0, 1)));
+            assert_eq!(model.get("b"), Some(&real(1, 1)));
+        }
+        optimum => panic!("expected an optimum but got {:?}", optimum),
+    }
+    // c would start at four on its own, but the makespan stays at three
+    match &optima[1] {
+        Optimum::Optimal(value, model) => {
+            assert_eq!(value, &real(2, 1));
+            assert_eq!(model.get("b"), Some(&real(1, 1)));
+        }
+        optimum => panic!("expected an optimum but got {:?}", optimum),
+    }
+    // the probes and bounds of the search stay out of the script
+    assert_eq!(print(&mut program), before);
+}
+
+#[test]
+fn infeasible_and_interrupted() {
+    let mut program = MarContext::new();
+
+    let x = constant(&mut program, "x");
+    let zero = program.mk_real_val("0.0");
+    let positive = program.mk_real_gt(vec![x, zero]);
+    program.assert(positive);
+    program.maximize(x);
+
+    // the first model has x at a half and one step only gets to one
+    match &optimize(&program, &mut backend(), 1)[..] {
+        [Optimum::Interrupted(value, _)] => assert_eq!(value, &real(1, 1)),
+        optima => panic!("expected an interrupted search but got {:?}", optima),
+    }
+    match &optimize(&program, &mut backend(), 10)[..] {
+        [Optimum::Optimal(value, _)] => assert_eq!(value, &real(4, 1)),
+        optima => panic!("expected an optimum but got {:?}", optima),
+    }
+
+    let five = program.mk_real_val("5.0");
+    let too_big = program.mk_real_gt(vec![x, five]);
+    program.assert(too_big);
+    assert_eq!(
+        optimize(&program, &mut backend(), 10),
+        vec![Optimum::Infeasible]
+    );
+}
+
+#[test]
+fn print_and_round_trip() {
+    let mut program = MarContext::new();
+
+    program.set_logic("QF_LRA".into());
+    let x = constant(&mut program, "x");
+    let y = constant(&mut program, "y");
+    let sum = program.mk_real_add(vec![x, y]);
+    let ten = program.mk_real_val("10.0");
+    let bounded = program.mk_real_le(vec![sum, ten]);
+    program.assert(bounded);
+    program.maximize(sum);
+    program.minimize(x);
+    program.check_sat();
+    program.get_objectives();
+    assert_eq!(program.objectives().len(), 2);
+
+    assert_eq!(
+        print(&mut program),
+        "(set-logic QF_LRA)
+(declare-fun x () Real)
+(declare-fun y () Real)
+(assert (<= (+ x y) 10.0))
+(maximize (+ x y))
+(minimize x)
+(check-sat)
+(get-objectives)
+"
+    );
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
+
+#[test]
+#[should_panic(expected = "objectives must be Int or Real but got Bool")]
+fn boolean_objective() {
+    let mut program = MarContext::new();
+
+    let t = program.mk_bool_val(true);
+    program.minimize(t);
+}
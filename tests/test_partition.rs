@@ -0,0 +1,120 @@
+use marlang::{
+    ast::MarId,
+    context::MarContext,
+    eval::{eval, MarValue},
+    partition::{check_parts, partition},
+    slicing::Dependencies,
+    solver::SatResult,
+};
+
+mod common;
+use common::{call, print, Enumerate};
+
+// x < y < 3 and z > x + 0 through a definition on one side, w > 2 on the other, and a popped
+// assertion that would have connected them
+fn script() -> (MarContext, Vec<MarId>) {
+    let mut program = MarContext::new();
+
+    program.set_logic("QF_LIA".into());
+    let int_sort = program.mk_int_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let y_def = program.declare_const("y", int_sort);
+    let z_def = program.declare_const("z", int_sort);
+    let w_def = program.declare_const("w", int_sort);
+    let x = call(&mut program, x_def, vec![]);
+    let y = call(&mut program, y_def, vec![]);
+    let z = call(&mut program, z_def, vec![]);
+    let w = call(&mut program, w_def, vec![]);
+    let zero = program.mk_int_val(0);
+    let two = program.mk_int_val(2);
+    let three = program.mk_int_val(3);
+    let shifted = program.mk_int_add(vec![x, zero]);
+    let low = program.define_fun("low", Vec::<(&str, MarId)>::new(), int_sort, shifted);
+    let low = call(&mut program, low, vec![]);
+
+    let ordered = program.mk_int_lt(vec![x, y, three]);
+    let above = program.mk_int_gt(vec![z, low]);
+    let big = program.mk_int_gt(vec![w, two]);
+    let assertions = vec![ordered, above, big];
+    program.assert(ordered);
+    program.assert(big);
+    program.assert(above);
+    program.push(1);
+    let linked = program.mk_eq(vec![w, z]);
+    program.assert(linked);
+    program.pop(1);
+    program.check_sat();
+    (program, assertions)
+}
+
+#[test]
+fn components() {
+    let (program, _) = script();
+
+    let mut parts = partition(&program);
+    assert_eq!(parts.len(), 2);
+    assert_eq!(
+        print(&mut parts[0]),
+        "(set-logic QF_LIA)
+(declare-fun x () Int)
+(declare-fun y () Int)
+(declare-fun z () Int)
+(define-fun low () Int (+ x 0))
+(assert (< x y 3))
+(assert (> z low))
+(push 1)
+(pop 1)
+(check-sat)
+"
+    );
+    assert_eq!(
+        print(&mut parts[1]),
+        "(set-logic QF_LIA)
+(declare-fun w () Int)
+(assert (> w 2))
+(push 1)
+(pop 1)
+(check-sat)
+"
+    );
+}
+
+#[test]
+fn merged_models() {
+    let (program, assertions) = script();
+
+    let mut parts = partition(&program);
+    let mut backend = Enumerate::ints(0..5);
+    let model = match check_parts(&mut parts, &mut backend) {
+        SatResult::Sat(model) => model,
+        result => panic!("expected a model but got {:?}", result),
+    };
+    assert_eq!(backend.calls, 2);
+    for name in ["x", "y", "z", "w"] {
+        assert!(model.get(name).is_some(), "{} must have a value", name);
+    }
+    // the definition of low comes along with each call to it
+    for a in assertions {
+        let expr = program.get_expr(a);
+        assert_eq!(
+            eval(&expr, (expr.as_ref().len() - 1).into(), &model),
+            Ok(MarValue::Bool(true))
+        );
+    }
+}
+
+#[test]
+fn unsat_part() {
+    let (mut program, _) = script();
+
+    let w_def = Dependencies::new(&program).declaration("w").unwrap();
+    let w = call(&mut program, w_def, vec![]);
+    let five = program.mk_int_val(5);
+    let too_big = program.mk_int_gt(vec![w, five]);
+    program.assert(too_big);
+
+    let mut parts = partition(&program);
+    assert_eq!(parts.len(), 2);
+    let mut backend = Enumerate::ints(0..5);
+    assert_eq!(check_parts(&mut parts, &mut backend), SatResult::Unsat);
+}
@@ -0,0 +1,197 @@
+use marlang::{
+    ast::Marlang,
+    context::MarContext,
+    passes::{
+        EliminateDeadDeclarations, ExpandDefinitions, ExpandLets, Pass, PassManager, PassStats,
+        Simplify, Unfold,
+    },
+    rewrites,
+};
+
+mod common;
+use common::{call, print, Enumerate};
+
+// Forgets the commands it is given, which is only equisatisfiable in special cases
+struct Drop {
+    name: &'static str,
+    drops: fn(&Marlang) -> bool,
+}
+
+impl Pass for Drop {
+    fn name(&self) -> String {
+        self.name.into()
+    }
+
+    fn run(&mut self, script: MarContext) -> (MarContext, PassStats) {
+        let mut out = MarContext::new();
+        let mut dropped = 0;
+        for command in script.commands() {
+            let expr = script.get_expr(command);
+            if expr.as_ref().last().is_some_and(self.drops) {
+                dropped += 1;
+                continue;
+            }
+            let c = out.import(&expr, |_| None);
+            out.commit(c);
+        }
+        let stats = PassStats {
+            counters: vec![("dropped".into(), dropped)],
+        };
+        (out, stats)
+    }
+}
+
+const DROP_ASSERTIONS: Drop = Drop {
+    name: "drop-assertions",
+    drops: |c| matches!(c, Marlang::Assert(_)),
+};
+
+// (> (inc x) bound) where inc adds one
+fn increment(bound: i64) -> MarContext {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let v = program.mk_symbol("v");
+    let one = program.mk_int_val(1);
+    let succ = program.mk_int_add(vec![v, one]);
+    let inc = program.define_fun("inc", vec![("v", int_sort)], int_sort, succ);
+    let x = call(&mut program, x_def, vec![]);
+    let inc_x = call(&mut program, inc, vec![x]);
+    let bound = program.mk_int_val(bound);
+    let above = program.mk_int_gt(vec![inc_x, bound]);
+    program.assert(above);
+    program.check_sat();
+    program
+}
+
+#[test]
+fn pipeline_with_reports() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let array_sort = program.mk_array_sort(int_sort, int_sort);
+    let a_def = program.declare_const("a", array_sort);
+    let i_def = program.declare_const("i", int_sort);
+    program.declare_const("unused", int_sort);
+    let b = program.mk_symbol("b");
+    let j = program.mk_symbol("j");
+    let read = program.mk_select(b, j);
+    let get = program.define_fun(
+        "get",
+        vec![("b", array_sort), ("j", int_sort)],
+        int_sort,
+        read,
+    );
+    let a = call(&mut program, a_def, vec![]);
+    let i = call(&mut program, i_def, vec![]);
+    let one = program.mk_int_val(1);
+    let zero = program.mk_int_val(0);
+    let written = program.mk_store(a, i, one);
+    let got = call(&mut program, get, vec![written, i]);
+    let positive = program.mk_int_gt(vec![got, zero]);
+    program.assert(positive);
+    program.check_sat();
+
+    let (mut out, reports) = PassManager::new()
+        .with_pass(ExpandDefinitions)
+        .with_pass(EliminateDeadDeclarations)
+        .with_pass(Simplify {
+            rewrites: vec![rewrites::arrays],
+            iter_limit: 5,
+        })
+        .run(program);
+
+    assert_eq!(
+        print(&mut out),
+        "(declare-fun a () (Array Int Int))
+(declare-fun i () Int)
+(assert (> 1 0))
+(check-sat)
+"
+    );
+    let names: Vec<&str> = reports.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "expand-definitions",
+            "eliminate-dead-declarations",
+            "simplify"
+        ]
+    );
+    assert_eq!(reports[0].stats.counters, [("dropped".to_string(), 1)]);
+    assert_eq!(reports[1].stats.counters, [("dropped".to_string(), 1)]);
+    for pair in reports.windows(2) {
+        assert_eq!(pair[0].size_after, pair[1].size_before);
+    }
+    assert!(reports[2].size_after < reports[2].size_before);
+    assert!(reports.iter().all(|r| r.equisat.is_none()));
+}
+
+#[test]
+fn equisatisfiability_checks() {
+    // x + 1 > 5 has no solution with x up to 4, so dropping it changes the answer
+    let (_, reports) = PassManager::new()
+        .with_pass(ExpandDefinitions)
+        .with_pass(DROP_ASSERTIONS)
+        .with_checker(Enumerate::ints(0..5))
+        .run(increment(5));
+    assert_eq!(reports[0].equisat, Some(true));
+    assert_eq!(reports[1].equisat, Some(false));
+    assert_eq!(reports[1].stats.counters, [("dropped".to_string(), 1)]);
+
+    // here the model of the result still has to satisfy x + 1 > 2, and x = 0 does not
+    let (_, reports) = PassManager::new()
+        .with_pass(ExpandDefinitions)
+        .with_pass(DROP_ASSERTIONS)
+        .with_checker(Enumerate::ints(0..5))
+        .run(increment(2));
+    assert_eq!(reports[0].equisat, Some(true));
+    assert_eq!(reports[1].equisat, Some(false));
+
+    // without its declaration, x is unbound in every model of the result
+    let (_, reports) = PassManager::new()
+        .with_pass(ExpandDefinitions)
+        .with_pass(Drop {
+            name: "drop-all",
+            drops: |_| true,
+        })
+        .with_checker(Enumerate::ints(0..5))
+        .run(increment(2));
+    assert_eq!(reports[1].equisat, Some(false));
+}
+
+#[test]
+fn recursive_pipeline() {
+    let mut program = MarContext::new();
+
+    // (define-fun-rec sum ((n Int)) Int (ite (<= n 0) 0 (+ n (sum (- n 1)))))
+    let int_sort = program.mk_int_sort();
+    let sum = program.mk_rec_fun("sum", vec![int_sort], int_sort);
+    let n = program.mk_symbol("n");
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let base = program.mk_int_le(vec![n, zero]);
+    let pred = program.mk_int_sub(vec![n, one]);
+    let recurse = call(&mut program, sum, vec![pred]);
+    let total = program.mk_int_add(vec![n, recurse]);
+    let body = program.mk_ite(base, zero, total);
+    program.define_fun_rec(sum, vec![("n", int_sort)], body);
+    let two = program.mk_int_val(2);
+    let three = program.mk_int_val(3);
+    let sum_two = call(&mut program, sum, vec![two]);
+    let correct = program.mk_eq(vec![sum_two, three]);
+    program.assert(correct);
+
+    let (mut out, reports) = PassManager::new()
+        .with_pass(Unfold { depth: 1 })
+        .with_pass(ExpandLets)
+        .run(program);
+    assert_eq!(reports[0].name, "unfold-1");
+    assert_eq!(
+        print(&mut out),
+        "(declare-fun sum (Int) Int)
+(assert (= (ite (<= 2 0) 0 (+ 2 (sum (- 2 1)))) 3))
+"
+    );
+}
@@ -0,0 +1,140 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::{MarPattern, MarRecExpr},
+    context::MarContext,
+    smtlib::write_smtlib,
+};
+
+fn pattern(s: &str) -> MarPattern {
+    s.parse().unwrap()
+}
+
+#[test]
+fn free_variables() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let y_def = program.declare_const("y", int_sort);
+    let empty = program.mk_nil();
+    let y = program.mk_call(y_def, empty);
+    let x = program.mk_symbol("x");
+    let z = program.mk_symbol("z");
+
+    let x_gt_y = program.mk_int_gt(vec![x, y]);
+    let closed = program.mk_forall(vec![("x", int_sort)], x_gt_y);
+    let x_gt_z = program.mk_int_gt(vec![x, z]);
+    let open = program.mk_exists(vec![("x", int_sort)], x_gt_z);
+    let bound = program.mk_let(vec![("z".into(), y)], open);
+
+    let free = |id| {
+        let mut free: Vec<String> = program.graph()[id].data.free.iter().cloned().collect();
+        free.sort();
+        free
+    };
+    assert_eq!(free(x_gt_y), vec!["x"]);
+    assert!(free(closed).is_empty());
+    assert_eq!(free(x_gt_z), vec!["x", "z"]);
+    assert_eq!(free(open), vec!["z"]);
+    assert!(free(bound).is_empty());
+}
+
+#[test]
+fn no_capture() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let y_def = program.declare_const("y", int_sort);
+    let empty = program.mk_nil();
+    let y = program.mk_call(y_def, empty);
+    let x = program.mk_symbol("x");
+    let zero = program.mk_int_val(0);
+    let x_gt_zero = program.mk_int_gt(vec![x, zero]);
+    let y_gt_zero = program.mk_int_gt(vec![y, zero]);
+    let body = program.mk_int_gt(vec![x, y]);
+    let q = program.mk_forall(vec![("x", int_sort)], body);
+
+    let captured = program.mk_and(vec![x_gt_zero, q]);
+    let x_and_body = program.mk_and(vec![x_gt_zero, body]);
+    let wrong = program.mk_forall(vec![("x", int_sort)], x_and_body);
+    let moved = program.mk_and(vec![y_gt_zero, q]);
+    let y_and_body = program.mk_and(vec![y_gt_zero, body]);
+    let right = program.mk_forall(vec![("x", int_sort)], y_and_body);
+
+    program.add_rewrite(
+        "and-forall".into(),
+        pattern("(marlang.operator.core.and (marlang.meta.cons ?p (marlang.meta.cons (marlang.operator.core.forall ?vs ?q) marlang.meta.nil)))"),
+        pattern("(marlang.operator.core.forall ?vs (marlang.operator.core.and (marlang.meta.cons ?p (marlang.meta.cons ?q marlang.meta.nil))))"),
+    );
+    let program = program.simplify(3);
+
+    let equiv = |x, y| program.equiv(program.get_expr(x), program.get_expr(y));
+    assert!(!equiv(captured, wrong));
+    assert!(equiv(moved, right));
+}
+
+#[test]
+fn no_leak() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let y_def = program.declare_const("y", int_sort);
+    let empty = program.mk_nil();
+    let y = program.mk_call(y_def, empty);
+    let x = program.mk_symbol("x");
+    let zero = program.mk_int_val(0);
+    let x_gt_zero = program.mk_int_gt(vec![x, zero]);
+    let y_gt_zero = program.mk_int_gt(vec![y, zero]);
+    let uses_x = program.mk_forall(vec![("x", int_sort)], x_gt_zero);
+    let ignores_x = program.mk_forall(vec![("x", int_sort)], y_gt_zero);
+
+    program.add_rewrite(
+        "forall-unused".into(),
+        pattern("(marlang.operator.core.forall ?vs ?b)"),
+        pattern("?b"),
+    );
+    let program = program.simplify(3);
+
+    let equiv = |x, y| program.equiv(program.get_expr(x), program.get_expr(y));
+    assert!(!equiv(uses_x, x_gt_zero));
+    assert!(equiv(ignores_x, y_gt_zero));
+}
+
+#[test]
+fn print_patterns() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let f_def = program.mk_declare_fun("f", vec![int_sort], int_sort);
+    program.commit(f_def);
+    let x = program.mk_symbol("x");
+    let empty = program.mk_nil();
+    let args = program.mk_cons(x, empty);
+    let f_x = program.mk_call(f_def, args);
+    let zero = program.mk_int_val(0);
+    let f_x_plus_zero = program.mk_int_add(vec![f_x, zero]);
+    let body = program.mk_int_gt(vec![f_x_plus_zero, zero]);
+    let trigger = program.mk_pattern_attr(vec![f_x]);
+    let body = program.mk_annotate(body, vec![trigger]);
+    let q = program.mk_forall(vec![("x", int_sort)], body);
+    program.assert(q);
+
+    let n = program.mk_symbol("n");
+    let n_plus_zero = program.mk_int_add(vec![n, zero]);
+    let left = program.get_pattern(n_plus_zero, vec![n]);
+    let right = program.get_pattern(n, vec![n]);
+    program.add_rewrite("add-zero".into(), left, right);
+    let mut program = program.simplify(3);
+
+    let expr: MarRecExpr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_smtlib(&mut buffer, &expr).expect("Must be able to print program");
+    let output = std::str::from_utf8(buffer.buffer()).unwrap().to_string();
+
+    assert_eq!(
+        output,
+        "(declare-fun f (Int) Int)
+(assert (forall ((x Int)) (! (> (f x) 0) :pattern ((f x)))))
+"
+    );
+}
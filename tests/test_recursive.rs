@@ -0,0 +1,228 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::{MarId, Marlang},
+    context::MarContext,
+    eval::{eval, EvalError, MarModel, MarValue},
+    recursion::unfold,
+    util::{decompose_using_expr, read_leda, write_leda},
+};
+
+mod common;
+use common::{call, print};
+
+// Evaluates the assertions of the whole program, so that calls can find their definitions
+fn holds(program: &mut MarContext, model: &MarModel) -> Vec<MarValue> {
+    let expr = program.extract_best();
+    decompose_using_expr(&expr, (expr.as_ref().len() - 1).into())
+        .into_iter()
+        .filter_map(|c| match expr[c] {
+            Marlang::Assert([e]) => Some(eval(&expr, e, model).expect("Must evaluate")),
+            _ => None,
+        })
+        .collect()
+}
+
+// (fact n) is 1 for n <= 0 and n * (fact (- n 1)) otherwise
+fn factorial(program: &mut MarContext) -> MarId {
+    let int_sort = program.mk_int_sort();
+    let fact = program.mk_rec_fun("fact", vec![int_sort], int_sort);
+    let n = program.mk_symbol("n");
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let base = program.mk_int_le(vec![n, zero]);
+    let pred = program.mk_int_sub(vec![n, one]);
+    let recurse = call(program, fact, vec![pred]);
+    let product = program.mk_int_mul(vec![n, recurse]);
+    let body = program.mk_ite(base, one, product);
+    program.define_fun_rec(fact, vec![("n", int_sort)], body);
+    fact
+}
+
+#[test]
+fn print_and_evaluate() {
+    let mut program = MarContext::new();
+
+    let fact = factorial(&mut program);
+    let int_sort = program.mk_int_sort();
+    let bool_sort = program.mk_bool_sort();
+    let even = program.mk_rec_fun("even", vec![int_sort], bool_sort);
+    let odd = program.mk_rec_fun("odd", vec![int_sort], bool_sort);
+    let n = program.mk_symbol("n");
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let is_zero = program.mk_eq(vec![n, zero]);
+    let pred = program.mk_int_sub(vec![n, one]);
+    let odd_pred = call(&mut program, odd, vec![pred]);
+    let even_pred = call(&mut program, even, vec![pred]);
+    let not_zero = program.mk_not(is_zero);
+    let even_body = program.mk_or(vec![is_zero, odd_pred]);
+    let odd_body = program.mk_and(vec![not_zero, even_pred]);
+    let even_def = program.mk_define_fun_rec(even, vec![("n", int_sort)], even_body);
+    let odd_def = program.mk_define_fun_rec(odd, vec![("n", int_sort)], odd_body);
+    program.define_funs_rec(vec![even_def, odd_def]);
+
+    let five = program.mk_int_val(5);
+    let fact_five = call(&mut program, fact, vec![five]);
+    let expected = program.mk_int_val(120);
+    let correct = program.mk_eq(vec![fact_five, expected]);
+    let even_five = call(&mut program, even, vec![five]);
+    program.assert(correct);
+    program.assert(even_five);
+
+    assert_eq!(
+        print(&mut program),
+        "(define-fun-rec fact ((n Int)) Int (ite (<= n 0) 1 (* n (fact (- n 1)))))
+(define-funs-rec ((even ((n Int)) Bool) (odd ((n Int)) Bool)) ((or (= n 0) (odd (- n 1))) (and (not (= n 0)) (even (- n 1)))))
+(assert (= (fact 5) 120))
+(assert (even 5))
+"
+    );
+    assert_eq!(
+        holds(&mut program, &MarModel::new()),
+        vec![MarValue::Bool(true), MarValue::Bool(false)]
+    );
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
+
+#[test]
+fn unfold_to_depth() {
+    let mut program = MarContext::new();
+
+    program.set_logic("UFLIA".into());
+    let fact = factorial(&mut program);
+    let three = program.mk_int_val(3);
+    let fact_three = call(&mut program, fact, vec![three]);
+    let six = program.mk_int_val(6);
+    let correct = program.mk_eq(vec![fact_three, six]);
+    program.assert(correct);
+    program.check_sat();
+
+    assert_eq!(
+        print(&mut unfold(&program, 0)),
+        "(set-logic UFLIA)
+(declare-fun fact (Int) Int)
+(assert (= (fact 3) 6))
+(check-sat)
+"
+    );
+    assert_eq!(
+        print(&mut unfold(&program, 1)),
+        "(set-logic UFLIA)
+(declare-fun fact (Int) Int)
+(assert (= (let ((n 3)) (ite (<= n 0) 1 (* n (fact (- n 1))))) 6))
+(check-sat)
+"
+    );
+
+    // the stub is only reached once the unfolding runs out, so a wrong stub only matters then
+    let mut model = MarModel::new();
+    model.insert_default("fact", MarValue::Int(0.into()));
+    assert_eq!(
+        holds(&mut unfold(&program, 2), &model),
+        vec![MarValue::Bool(false)]
+    );
+    assert_eq!(
+        holds(&mut unfold(&program, 4), &model),
+        vec![MarValue::Bool(true)]
+    );
+}
+
+#[test]
+fn unfold_mutual_recursion_in_scopes() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let bool_sort = program.mk_bool_sort();
+    let even = program.mk_rec_fun("even", vec![int_sort], bool_sort);
+    let odd = program.mk_rec_fun("odd", vec![int_sort], bool_sort);
+    let n = program.mk_symbol("n");
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let is_zero = program.mk_eq(vec![n, zero]);
+    let pred = program.mk_int_sub(vec![n, one]);
+    let odd_pred = call(&mut program, odd, vec![pred]);
+    let even_pred = call(&mut program, even, vec![pred]);
+    let not_zero = program.mk_not(is_zero);
+    let even_body = program.mk_or(vec![is_zero, odd_pred]);
+    let odd_body = program.mk_and(vec![not_zero, even_pred]);
+    let even_def = program.mk_define_fun_rec(even, vec![("n", int_sort)], even_body);
+    let odd_def = program.mk_define_fun_rec(odd, vec![("n", int_sort)], odd_body);
+    program.define_funs_rec(vec![even_def, odd_def]);
+    program.push(1);
+    let two = program.mk_int_val(2);
+    let odd_two = call(&mut program, odd, vec![two]);
+    program.assert(odd_two);
+    program.pop(1);
+
+    let mut unfolded = unfold(&program, 3);
+    assert_eq!(unfolded.scope_depth(), 0);
+    assert_eq!(unfolded.active_commands().len(), 2);
+    assert_eq!(
+        print(&mut unfolded),
+        "(declare-fun even (Int) Bool)
+(declare-fun odd (Int) Bool)
+(push 1)
+(assert (let ((n 2)) (and (not (= n 0)) (let ((n (- n 1))) (or (= n 0) (let ((n (- n 1))) (and (not (= n 0)) (even (- n 1)))))))))
+(pop 1)
+"
+    );
+    assert_eq!(
+        holds(&mut unfolded, &MarModel::new()),
+        vec![MarValue::Bool(false)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "function expects 1 parameters but got 2")]
+fn parameters_must_match() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let f = program.mk_rec_fun("f", vec![int_sort], int_sort);
+    let x = program.mk_symbol("x");
+    program.define_fun_rec(f, vec![("x", int_sort), ("y", int_sort)], x);
+}
+
+#[test]
+fn runaway_recursion() {
+    let mut program = MarContext::new();
+
+    let fact = factorial(&mut program);
+    let zero = program.mk_int_val(0);
+    for n in [400, 100000] {
+        let n = program.mk_int_val(n);
+        let fact_n = call(&mut program, fact, vec![n]);
+        let positive = program.mk_int_gt(vec![fact_n, zero]);
+        program.assert(positive);
+    }
+
+    // unoptimized builds use far more stack for each call than the default test thread has
+    let expr = program.extract_best();
+    let evaluated = std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(move || {
+            decompose_using_expr(&expr, (expr.as_ref().len() - 1).into())
+                .into_iter()
+                .filter_map(|c| match expr[c] {
+                    Marlang::Assert([e]) => Some(eval(&expr, e, &MarModel::new())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(
+        evaluated,
+        vec![
+            Ok(MarValue::Bool(true)),
+            Err(EvalError::Unsupported("fact calls deeper than 500".into()))
+        ]
+    );
+}
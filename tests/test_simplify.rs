@@ -1,5 +1,8 @@
 use marlang::context::MarContext;
 
+mod common;
+use common::print;
+
 #[test]
 fn add_zero() {
     let mut program = MarContext::new();
@@ -42,3 +45,68 @@ fn same_symbol() {
     let second = program.mk_call(decl, empty);
     assert_eq!(first, second);
 }
+
+#[test]
+fn assume_equalities() {
+    let mut program = MarContext::new().with_assume_mode();
+
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let int_sort = program.mk_int_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let y_def = program.declare_const("y", int_sort);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let y = program.mk_call(y_def, empty);
+    let y_plus_one = program.mk_int_add(vec![y, one]);
+    let x_eq_y_plus_one = program.mk_eq(vec![x, y_plus_one]);
+    program.assert(x_eq_y_plus_one);
+    let y_plus_one_gt_0 = program.mk_int_gt(vec![y_plus_one, zero]);
+    program.assert(y_plus_one_gt_0);
+
+    let x_expr = program.get_expr(x);
+    let y_plus_one_expr = program.get_expr(y_plus_one);
+    let gt_expr = program.get_expr(y_plus_one_gt_0);
+    let eq_expr = program.get_expr(x_eq_y_plus_one);
+
+    let mut program = program.simplify(1);
+
+    assert!(program.equiv(x_expr.clone(), y_plus_one_expr.clone()));
+    // the assertions themselves are not merged with true, or extraction would drop them
+    assert!(!program.equiv(
+        gt_expr.clone(),
+        "(marlang.value.bool true)".parse().unwrap()
+    ));
+    assert!(!program.equiv(eq_expr, "(marlang.value.bool true)".parse().unwrap()));
+
+    let explanation = program
+        .explain_equivalence(x_expr, y_plus_one_expr)
+        .get_flat_string();
+    assert!(explanation.contains("assume (marlang.command.assert (marlang.operator.core.="));
+}
+
+#[test]
+fn assume_keeps_assertions() {
+    let mut program = MarContext::new().with_assume_mode();
+
+    let one = program.mk_int_val(1);
+    let int_sort = program.mk_int_sort();
+    let x_def = program.declare_const("x", int_sort);
+    let empty = program.mk_nil();
+    let x = program.mk_call(x_def, empty);
+    let x_eq_one = program.mk_eq(vec![x, one]);
+    program.assert(x_eq_one);
+    let x_neq_one = program.mk_distinct(vec![x, one]);
+    program.assert(x_neq_one);
+
+    let mut program = program.simplify(1);
+
+    // the pair stays unsat
+    assert_eq!(
+        print(&mut program),
+        "(declare-fun x () Int)
+(assert (= x 1))
+(assert (distinct 1 1))
+"
+    );
+}
@@ -0,0 +1,178 @@
+use marlang::{
+    ast::MarId,
+    context::MarContext,
+    slicing::{eliminate_dead_declarations, slice, Dependencies},
+};
+
+mod common;
+use common::{call, print};
+
+struct Script {
+    program: MarContext,
+    x: MarId,
+    double: MarId,
+    first: MarId,
+    second: MarId,
+}
+
+// x and y are only constrained together through double, and z on its own
+fn script() -> Script {
+    let mut program = MarContext::new();
+
+    program.set_logic("QF_UFLIA".into());
+    let int_sort = program.mk_int_sort();
+    let u = program.declare_sort("U", 0);
+    let u_sort = program.mk_sort_apply(u, vec![]);
+    let x_def = program.declare_const("x", int_sort);
+    let y_def = program.declare_const("y", int_sort);
+    let z_def = program.declare_const("z", int_sort);
+    program.declare_const("w", u_sort);
+    let v = program.mk_symbol("v");
+    let sum = program.mk_int_add(vec![v, v]);
+    let double = program.define_fun("double", vec![("v", int_sort)], int_sort, sum);
+    let twice = call(&mut program, double, vec![v]);
+    let four_times = call(&mut program, double, vec![twice]);
+    program.define_fun("quadruple", vec![("v", int_sort)], int_sort, four_times);
+
+    let x = call(&mut program, x_def, vec![]);
+    let y = call(&mut program, y_def, vec![]);
+    let z = call(&mut program, z_def, vec![]);
+    let zero = program.mk_int_val(0);
+    let double_x = call(&mut program, double, vec![x]);
+    let above = program.mk_int_gt(vec![double_x, y]);
+    let first = program.assert(above);
+    let positive = program.mk_int_gt(vec![z, zero]);
+    let second = program.assert(positive);
+    program.check_sat();
+
+    Script {
+        program,
+        x: x_def,
+        double,
+        first,
+        second,
+    }
+}
+
+#[test]
+fn dependency_graph() {
+    let Script {
+        program,
+        x,
+        double,
+        first,
+        ..
+    } = script();
+
+    let dependencies = Dependencies::new(&program);
+    let y = dependencies.declaration("y").expect("y must be declared");
+    let quadruple = dependencies.declaration("quadruple").unwrap();
+    assert_eq!(dependencies.declaration("x"), Some(x));
+    let mut expected = [x, y, double];
+    expected.sort();
+    assert_eq!(dependencies.uses(first), &expected[..]);
+    assert_eq!(dependencies.uses(quadruple), &[double]);
+    assert_eq!(dependencies.uses(double), &[]);
+
+    // w depends on the sort it is declared with
+    let w = dependencies.declaration("w").unwrap();
+    let u = dependencies.declaration("U").unwrap();
+    assert_eq!(dependencies.uses(w), &[u]);
+    assert_eq!(dependencies.cone(&[quadruple]).len(), 2);
+}
+
+#[test]
+fn drop_dead_declarations() {
+    let Script { program, .. } = script();
+
+    // the sort stays even though nothing uses it any more, since only functions are dropped
+    assert_eq!(
+        print(&mut eliminate_dead_declarations(&program)),
+        "(set-logic QF_UFLIA)
+(declare-sort U 0)
+(declare-fun x () Int)
+(declare-fun y () Int)
+(declare-fun z () Int)
+(define-fun double ((v Int)) Int (+ v v))
+(assert (> (double x) y))
+(assert (> z 0))
+(check-sat)
+"
+    );
+}
+
+#[test]
+fn slice_by_assertions_and_symbols() {
+    let Script {
+        program,
+        first,
+        second,
+        ..
+    } = script();
+
+    assert_eq!(
+        print(&mut slice(&program, &[second])),
+        "(set-logic QF_UFLIA)
+(declare-fun z () Int)
+(assert (> z 0))
+(check-sat)
+"
+    );
+    let mut sliced = slice(&program, &[first]);
+    assert_eq!(sliced.active_assertions().len(), 1);
+    assert_eq!(
+        print(&mut sliced),
+        "(set-logic QF_UFLIA)
+(declare-fun x () Int)
+(declare-fun y () Int)
+(define-fun double ((v Int)) Int (+ v v))
+(assert (> (double x) y))
+(check-sat)
+"
+    );
+
+    let dependencies = Dependencies::new(&program);
+    let w = dependencies.declaration("w").unwrap();
+    let quadruple = dependencies.declaration("quadruple").unwrap();
+    assert_eq!(
+        print(&mut slice(&program, &[w, quadruple])),
+        "(set-logic QF_UFLIA)
+(declare-sort U 0)
+(declare-fun w () U)
+(define-fun double ((v Int)) Int (+ v v))
+(define-fun quadruple ((v Int)) Int (double (double v)))
+(check-sat)
+"
+    );
+}
+
+#[test]
+fn datatypes_by_name() {
+    let mut program = MarContext::new();
+
+    let int_sort = program.mk_int_sort();
+    let pair_sort = program.mk_datatype_sort("Pair");
+    let fst = program.mk_selector("Pair", "fst", int_sort);
+    let snd = program.mk_selector("Pair", "snd", int_sort);
+    let mk_pair = program.mk_constructor("Pair", "mk-pair", vec![fst, snd]);
+    let pair = program.mk_datatype("Pair", vec![mk_pair]);
+    let declared = program.declare_datatypes(vec![pair]);
+    let p_def = program.declare_const("p", pair_sort);
+    let n_def = program.declare_const("n", int_sort);
+    let p = call(&mut program, p_def, vec![]);
+    let n = call(&mut program, n_def, vec![]);
+    let one = program.mk_int_val(1);
+    let built = call(&mut program, mk_pair, vec![one, one]);
+    let same = program.mk_eq(vec![p, built]);
+    let assertion = program.assert(same);
+    let first = call(&mut program, fst, vec![p]);
+    let positive = program.mk_int_gt(vec![n, first]);
+    program.assert(positive);
+
+    let dependencies = Dependencies::new(&program);
+    let mut expected = [declared, p_def];
+    expected.sort();
+    assert_eq!(dependencies.uses(assertion), &expected[..]);
+    assert_eq!(dependencies.uses(p_def), &[declared]);
+    assert_eq!(slice(&program, &[assertion]).commands().len(), 3);
+}
@@ -0,0 +1,120 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::MarSort,
+    context::MarContext,
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::print;
+
+#[test]
+fn declared_and_defined_sorts() {
+    let mut program = MarContext::new();
+
+    let u_def = program.declare_sort("U", 0);
+    let list_def = program.declare_sort("List", 1);
+    let t = program.mk_symbol("T");
+    let bool_sort = program.mk_bool_sort();
+    let set_body = program.mk_array_sort(t, bool_sort);
+    let set_def = program.define_sort("Set", vec!["T"], set_body);
+
+    let u = program.mk_sort_apply(u_def, vec![]);
+    let int_sort = program.mk_int_sort();
+    let int_list = program.mk_sort_apply(list_def, vec![int_sort]);
+    let u_set = program.mk_sort_apply(set_def, vec![u]);
+    let table = program.mk_array_sort(int_sort, u_set);
+
+    let u_sort = MarSort::Uninterpreted("U".into(), vec![]);
+    assert_eq!(program.graph()[u].data.sort, Some(u_sort.clone()));
+    assert_eq!(
+        program.graph()[int_list].data.sort,
+        Some(MarSort::Uninterpreted("List".into(), vec![MarSort::Int]))
+    );
+    assert_eq!(
+        program.graph()[table].data.sort,
+        Some(MarSort::Array(
+            Box::new(MarSort::Int),
+            Box::new(MarSort::Array(Box::new(u_sort), Box::new(MarSort::Bool)))
+        ))
+    );
+
+    let xs_def = program.declare_const("xs", int_list);
+    let a_def = program.declare_const("a", table);
+    let e_def = program.declare_const("e", u);
+    let empty = program.mk_nil();
+    let a = program.mk_call(a_def, empty);
+    let e = program.mk_call(e_def, empty);
+    let zero = program.mk_int_val(0);
+    let set = program.mk_select(a, zero);
+    let member = program.mk_select(set, e);
+    program.assert(member);
+    let xs = program.mk_call(xs_def, empty);
+    let same = program.mk_eq(vec![xs, xs]);
+    program.assert(same);
+
+    assert_eq!(
+        print(&mut program),
+        "(declare-sort U 0)
+(declare-sort List 1)
+(define-sort Set (T) (Array T Bool))
+(declare-fun xs () (List Int))
+(declare-fun a () (Array Int (Set U)))
+(declare-fun e () U)
+(assert (select (select a 0) e))
+(assert (= xs xs))
+"
+    );
+}
+
+#[test]
+#[should_panic(expected = "sort constructor expects 1 arguments but got 2")]
+fn wrong_arity() {
+    let mut program = MarContext::new();
+    let list_def = program.declare_sort("List", 1);
+    let int_sort = program.mk_int_sort();
+    program.mk_sort_apply(list_def, vec![int_sort, int_sort]);
+}
+
+#[test]
+#[should_panic(expected = "sort mismatch")]
+fn ill_sorted_call() {
+    let mut program = MarContext::new();
+    let u_def = program.declare_sort("U", 0);
+    let u = program.mk_sort_apply(u_def, vec![]);
+    let bool_sort = program.mk_bool_sort();
+    let p_def = program.mk_declare_fun("p", vec![u], bool_sort);
+    let one = program.mk_int_val(1);
+    let empty = program.mk_nil();
+    let args = program.mk_cons(one, empty);
+    program.mk_call(p_def, args);
+}
+
+#[test]
+fn leda_round_trip() {
+    let mut program = MarContext::new();
+
+    let pair_def = program.declare_sort("Pair", 2);
+    let a = program.mk_symbol("A");
+    let pair_body = program.mk_sort_apply(pair_def, vec![a, a]);
+    let twin_def = program.define_sort("Twin", vec!["A"], pair_body);
+    let real_sort = program.mk_real_sort();
+    let twin = program.mk_sort_apply(twin_def, vec![real_sort]);
+    program.declare_const("p", twin);
+
+    assert_eq!(
+        program.graph()[twin].data.sort,
+        Some(MarSort::Uninterpreted(
+            "Pair".into(),
+            vec![MarSort::Real, MarSort::Real]
+        ))
+    );
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
@@ -0,0 +1,164 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::MarSort,
+    context::MarContext,
+    eval::{eval, EvalError, MarModel, MarValue},
+    smtlib::write_smtlib,
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::value_in;
+
+#[test]
+fn string_operators() {
+    let mut program = MarContext::new();
+
+    let string_sort = program.mk_string_sort();
+    let s_def = program.declare_const("s", string_sort);
+    let empty = program.mk_nil();
+    let s = program.mk_call(s_def, empty);
+    let ab = program.mk_string_literal("\"ab\"");
+    let c = program.mk_string_literal("\"c\"");
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let five = program.mk_int_val(5);
+    let six = program.mk_int_val(6);
+
+    let abs = program.mk_concat(vec![ab, s]);
+    let len = program.mk_str_len(abs);
+    let at = program.mk_str_at(abs, one);
+    let substr = program.mk_str_substr(abs, one, five);
+    let indexof = program.mk_str_indexof(abs, c, zero);
+    let replace_all = program.mk_str_replace_all(abs, c, ab);
+    let to_int = program.mk_str_to_int(s);
+    let out_of_range = program.mk_str_at(abs, six);
+
+    assert_eq!(program.graph()[len].data.sort, Some(MarSort::Int));
+
+    let mut model = MarModel::new();
+    model.insert("s", MarValue::String("c12c".into()));
+    let string = |s: &str| MarValue::String(s.into());
+    let int = |i: i32| MarValue::Int(i.into());
+
+    assert_eq!(value_in(&program, abs, &model), string("abc12c"));
+    assert_eq!(value_in(&program, len, &model), int(6));
+    assert_eq!(value_in(&program, at, &model), string("b"));
+    assert_eq!(value_in(&program, substr, &model), string("bc12c"));
+    assert_eq!(value_in(&program, indexof, &model), int(2));
+    assert_eq!(value_in(&program, replace_all, &model), string("abab12ab"));
+    assert_eq!(value_in(&program, to_int, &model), int(-1));
+    assert_eq!(value_in(&program, out_of_range, &model), string(""));
+}
+
+#[test]
+fn regular_expressions() {
+    let mut program = MarContext::new();
+
+    let string_sort = program.mk_string_sort();
+    let s_def = program.declare_const("s", string_sort);
+    let empty = program.mk_nil();
+    let s = program.mk_call(s_def, empty);
+    let zero = program.mk_string_literal("\"0\"");
+    let nine = program.mk_string_literal("\"9\"");
+    let dot = program.mk_string_literal("\".\"");
+
+    // [0-9]+(\.[0-9]{1,2})?
+    let digit = program.mk_re_range(zero, nine);
+    let digits = program.mk_re_plus(digit);
+    let dot = program.mk_str_to_re(dot);
+    let cents = program.mk_re_loop(1, 2, digit);
+    let fraction = program.mk_re_concat(vec![dot, cents]);
+    let fraction = program.mk_re_opt(fraction);
+    let amount = program.mk_re_concat(vec![digits, fraction]);
+    let matches = program.mk_str_in_re(s, amount);
+
+    assert_eq!(program.graph()[amount].data.sort, Some(MarSort::RegLan));
+
+    for (input, expected) in [
+        ("12", true),
+        ("12.5", true),
+        ("12.50", true),
+        ("12.500", false),
+        (".5", false),
+        ("", false),
+    ] {
+        let mut model = MarModel::new();
+        model.insert("s", MarValue::String(input.into()));
+        assert_eq!(
+            value_in(&program, matches, &model),
+            MarValue::Bool(expected),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn escaping() {
+    let mut program = MarContext::new();
+
+    let quote = program.mk_string_literal("\"say \"\"hi\"\"\"");
+    let unicode = program.mk_string_literal("\"\\u{e9}\\u00e8\\x\"");
+    let eq = program.mk_eq(vec![quote, unicode]);
+    program.assert(eq);
+
+    let mut buffer = BufWriter::new(Vec::new());
+    write_smtlib(&mut buffer, &program.extract_best()).expect("Must be able to print program");
+    let output = std::str::from_utf8(buffer.buffer()).unwrap().to_string();
+
+    assert_eq!(
+        output,
+        "(assert (= \"say \"\"hi\"\"\" \"\\u{e9}\\u{e8}\\u{5c}x\"))\n"
+    );
+}
+
+#[test]
+fn leda_round_trip() {
+    let mut program = MarContext::new();
+
+    let string_sort = program.mk_string_sort();
+    let s_def = program.declare_const("s", string_sort);
+    let empty = program.mk_nil();
+    let s = program.mk_call(s_def, empty);
+    let a = program.mk_string_literal("\"a\"");
+    let a = program.mk_str_to_re(a);
+    let all = program.mk_re_allchar();
+    let r = program.mk_re_concat(vec![a, all]);
+    let r = program.mk_re_power(3, r);
+    let r = program.mk_re_star(r);
+    let in_re = program.mk_str_in_re(s, r);
+    program.assert(in_re);
+
+    let expr = program.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write program to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse program");
+
+    assert_eq!(expr.to_string(), parsed.to_string());
+}
+
+#[test]
+fn code_points() {
+    let mut program = MarContext::new();
+
+    let a = program.mk_int_val(0x61);
+    let surrogate = program.mk_int_val(0xD800);
+    let too_big = program.mk_int_val(0x30000);
+    let [a, surrogate, too_big] = [a, surrogate, too_big].map(|n| program.mk_str_from_code(n));
+
+    let model = MarModel::new();
+    assert_eq!(value_in(&program, a, &model), MarValue::String("a".into()));
+    assert_eq!(
+        value_in(&program, too_big, &model),
+        MarValue::String("".into())
+    );
+    let expr = program.get_expr(surrogate);
+    assert_eq!(
+        eval(&expr, (expr.as_ref().len() - 1).into(), &model),
+        Err(EvalError::Unsupported(
+            "str.from_code of the surrogate 0xD800".into()
+        ))
+    );
+}
@@ -0,0 +1,184 @@
+use std::io::BufWriter;
+
+use marlang::{
+    ast::MarId,
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    smtlib::to_smtlib,
+    synthesis::{input_grid, synthesize},
+    util::{read_leda, write_leda},
+};
+
+mod common;
+use common::{call, print};
+
+fn show(program: &MarContext, id: MarId) -> String {
+    let expr = program.get_expr(id);
+    to_smtlib(&expr, (expr.as_ref().len() - 1).into())
+}
+
+// f(v) = 2v + 1 where f can only add up its argument and ones
+fn affine() -> (MarContext, MarId) {
+    let mut problem = MarContext::new();
+
+    let int_sort = problem.mk_int_sort();
+    let start = problem.mk_symbol("Start");
+    let variable = problem.mk_grammar_variable(int_sort);
+    let one = problem.mk_int_val(1);
+    let add = problem.mk_int_add(vec![start, start]);
+    let rules = problem.mk_nonterminal("Start", int_sort, vec![variable, one, add]);
+    let f = problem.synth_fun("f", vec![("x", int_sort)], int_sort, vec![rules]);
+
+    let v_def = problem.declare_var("v", int_sort);
+    let v = call(&mut problem, v_def, vec![]);
+    let f_v = call(&mut problem, f, vec![v]);
+    let two = problem.mk_int_val(2);
+    let twice = problem.mk_int_mul(vec![two, v]);
+    let expected = problem.mk_int_add(vec![twice, one]);
+    let spec = problem.mk_eq(vec![f_v, expected]);
+    problem.constraint(spec);
+    problem.check_synth();
+    (problem, f)
+}
+
+#[test]
+fn print_and_round_trip() {
+    let (mut problem, f) = affine();
+
+    assert_eq!(
+        print(&mut problem),
+        "(synth-fun f ((x Int)) Int ((Start Int)) ((Start Int ((Variable Int) 1 (+ Start Start)))))
+(declare-var v Int)
+(constraint (= (f v) (+ (* 2 v) 1)))
+(check-synth)
+"
+    );
+
+    let expr = problem.extract_best();
+    let mut buffer = BufWriter::new(Vec::new());
+    write_leda(&mut buffer, &expr).expect("Must be able to write problem to buffer");
+    let parsed = read_leda(&mut buffer.buffer()).expect("Must be able to parse problem");
+    assert_eq!(expr.to_string(), parsed.to_string());
+
+    // until it is synthesized, the function is interpreted by the model like a declared one
+    let three = problem.mk_int_val(3);
+    let f_three = call(&mut problem, f, vec![three]);
+    let seven = problem.mk_int_val(7);
+    let check = problem.mk_eq(vec![f_three, seven]);
+    let expr = problem.get_expr(check);
+    let mut model = MarModel::new();
+    model.insert_entry("f", vec![MarValue::Int(3.into())], MarValue::Int(7.into()));
+    assert_eq!(
+        eval(&expr, (expr.as_ref().len() - 1).into(), &model),
+        Ok(MarValue::Bool(true))
+    );
+}
+
+#[test]
+fn synthesize_with_grammar() {
+    let (mut problem, _) = affine();
+    let inputs = input_grid(&problem, &[-3, -2, -1, 0, 1, 2, 3])
+        .expect("Must be able to enumerate the inputs");
+    assert_eq!(inputs.len(), 7);
+
+    // 2x + 1 needs two additions, which is five rules
+    assert_eq!(synthesize(&mut problem, &inputs, 4), None);
+    let solution = synthesize(&mut problem, &inputs, 5).expect("Must find a solution");
+    assert_eq!(
+        show(&problem, solution),
+        "(define-fun f ((x Int)) Int (+ x (+ x 1)))"
+    );
+}
+
+#[test]
+fn synthesize_without_grammar() {
+    let mut problem = MarContext::new();
+
+    let int_sort = problem.mk_int_sort();
+    let max = problem.synth_fun(
+        "max2",
+        vec![("x", int_sort), ("y", int_sort)],
+        int_sort,
+        vec![],
+    );
+    let a_def = problem.declare_var("a", int_sort);
+    let b_def = problem.declare_var("b", int_sort);
+    let a = call(&mut problem, a_def, vec![]);
+    let b = call(&mut problem, b_def, vec![]);
+    let max_ab = call(&mut problem, max, vec![a, b]);
+    let above_a = problem.mk_int_ge(vec![max_ab, a]);
+    let above_b = problem.mk_int_ge(vec![max_ab, b]);
+    let is_a = problem.mk_eq(vec![max_ab, a]);
+    let is_b = problem.mk_eq(vec![max_ab, b]);
+    let either = problem.mk_or(vec![is_a, is_b]);
+    problem.constraint(above_a);
+    problem.constraint(above_b);
+    problem.constraint(either);
+    problem.check_synth();
+
+    let inputs =
+        input_grid(&problem, &[-2, -1, 0, 1, 2]).expect("Must be able to enumerate the inputs");
+    let solution = synthesize(&mut problem, &inputs, 6).expect("Must find a solution");
+    assert_eq!(
+        show(&problem, solution),
+        "(define-fun max2 ((x Int) (y Int)) Int (ite (<= x y) y x))"
+    );
+
+    for (x, y) in [(5, 9), (-4, -7), (3, 3)] {
+        let x_val = problem.mk_int_val(x);
+        let y_val = problem.mk_int_val(y);
+        let applied = call(&mut problem, solution, vec![x_val, y_val]);
+        let expr = problem.get_expr(applied);
+        let value = eval(&expr, (expr.as_ref().len() - 1).into(), &MarModel::new());
+        assert_eq!(value, Ok(MarValue::Int(x.max(y).into())));
+    }
+}
+
+#[test]
+fn nested_calls() {
+    let mut problem = MarContext::new();
+
+    // f(f(v)) = v + 2 where f can only add up its argument and ones
+    let int_sort = problem.mk_int_sort();
+    let start = problem.mk_symbol("Start");
+    let variable = problem.mk_grammar_variable(int_sort);
+    let one = problem.mk_int_val(1);
+    let add = problem.mk_int_add(vec![start, start]);
+    let rules = problem.mk_nonterminal("Start", int_sort, vec![variable, one, add]);
+    let f = problem.synth_fun("f", vec![("x", int_sort)], int_sort, vec![rules]);
+    let v_def = problem.declare_var("v", int_sort);
+    let v = call(&mut problem, v_def, vec![]);
+    let f_v = call(&mut problem, f, vec![v]);
+    let f_f_v = call(&mut problem, f, vec![f_v]);
+    let two = problem.mk_int_val(2);
+    let expected = problem.mk_int_add(vec![v, two]);
+    let spec = problem.mk_eq(vec![f_f_v, expected]);
+    problem.constraint(spec);
+    problem.check_synth();
+
+    // at x = 1 the constant 1 agrees with x, so pruning on that point alone never builds (+ x 1)
+    let inputs = input_grid(&problem, &[1, 2, 3]).expect("Must be able to enumerate the inputs");
+    let solution = synthesize(&mut problem, &inputs, 3).expect("Must find a solution");
+    assert_eq!(
+        show(&problem, solution),
+        "(define-fun f ((x Int)) Int (+ x 1))"
+    );
+}
+
+#[test]
+fn unsupported_problems() {
+    // only one synth-fun is solved at a time
+    let (mut problem, _) = affine();
+    let inputs = input_grid(&problem, &[0, 1]).expect("Must be able to enumerate the inputs");
+    let int_sort = problem.mk_int_sort();
+    problem.synth_fun("g", vec![("x", int_sort)], int_sort, vec![]);
+    assert_eq!(synthesize(&mut problem, &inputs, 5), None);
+
+    // Real functions need constants that cannot be enumerated, and so do Real inputs
+    let mut problem = MarContext::new();
+    let real_sort = problem.mk_real_sort();
+    problem.synth_fun("h", vec![("x", real_sort)], real_sort, vec![]);
+    assert_eq!(synthesize(&mut problem, &[MarModel::new()], 5), None);
+    problem.declare_var("r", real_sort);
+    assert_eq!(input_grid(&problem, &[0, 1]), None);
+}
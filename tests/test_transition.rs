@@ -0,0 +1,167 @@
+use marlang::{
+    ast::MarId,
+    context::MarContext,
+    eval::{eval, MarModel, MarValue},
+    transition::TransitionSystem,
+};
+
+mod common;
+use common::{call, print};
+
+// A counter that goes up by one whenever its input is set
+fn counter() -> (TransitionSystem, MarId) {
+    let mut system = TransitionSystem::new();
+
+    let int_sort = system.context().mk_int_sort();
+    let bool_sort = system.context().mk_bool_sort();
+    let (x, next_x) = system.add_state("x", int_sort);
+    let b = system.add_input("b", bool_sort);
+
+    let program = system.context();
+    let zero = program.mk_int_val(0);
+    let one = program.mk_int_val(1);
+    let two = program.mk_int_val(2);
+    let init = program.mk_eq(vec![x, zero]);
+    let succ = program.mk_int_add(vec![x, one]);
+    let step = program.mk_ite(b, succ, x);
+    let next = program.mk_eq(vec![next_x, step]);
+    let small = program.mk_int_lt(vec![x, two]);
+
+    system.add_init(init);
+    system.add_next(next);
+    system.add_property("small", small);
+    (system, small)
+}
+
+#[test]
+fn bmc_unrolling() {
+    let (system, _) = counter();
+
+    assert_eq!(
+        print(&mut system.unroll(2)),
+        "(declare-fun x@0 () Int)
+(declare-fun b@0 () Bool)
+(declare-fun x@1 () Int)
+(declare-fun b@1 () Bool)
+(declare-fun x@2 () Int)
+(declare-fun b@2 () Bool)
+(assert (= x@0 0))
+(assert (= x@1 (ite b@0 (+ x@0 1) x@0)))
+(assert (= x@2 (ite b@1 (+ x@1 1) x@1)))
+(assert (or (not (< x@0 2)) (not (< x@1 2)) (not (< x@2 2))))
+(check-sat)
+"
+    );
+    assert_eq!(
+        print(&mut system.unroll(0)),
+        "(declare-fun x@0 () Int)
+(declare-fun b@0 () Bool)
+(assert (= x@0 0))
+(assert (not (< x@0 2)))
+(check-sat)
+"
+    );
+}
+
+#[test]
+fn counterexample_trace() {
+    let (mut system, small) = counter();
+
+    let mut model = MarModel::new();
+    for (i, (x, b)) in [(0, true), (1, true), (2, false)].into_iter().enumerate() {
+        model.insert(format!("x@{}", i), MarValue::Int(x.into()));
+        model.insert(format!("b@{}", i), MarValue::Bool(b));
+    }
+    model.insert("unrelated", MarValue::Bool(true));
+
+    let trace = system.decode_trace(&model, 2);
+    assert_eq!(trace.len(), 3);
+    assert_eq!(trace[1].get("x"), Some(&MarValue::Int(1.into())));
+    assert_eq!(trace[1].get("b"), Some(&MarValue::Bool(true)));
+    assert_eq!(trace[2].get("unrelated"), None);
+
+    // the decoded steps are models of the original terms
+    let expr = system.context().get_expr(small);
+    let root = (expr.as_ref().len() - 1).into();
+    let holds: Vec<MarValue> = trace
+        .iter()
+        .map(|step| eval(&expr, root, step).expect("Must be able to evaluate property"))
+        .collect();
+    assert_eq!(
+        holds,
+        vec![
+            MarValue::Bool(true),
+            MarValue::Bool(true),
+            MarValue::Bool(false)
+        ]
+    );
+}
+
+#[test]
+fn path_lookup() {
+    let (system, _) = counter();
+
+    let mut script = MarContext::new();
+    let path = system.declare_path(&mut script, 3);
+    assert_eq!(path.len(), 4);
+    assert!(path.get("x", 3).is_some());
+    assert!(path.get("x", 4).is_none());
+    assert!(path.get("y", 0).is_none());
+
+    let init = system.init_at(&mut script, &path, 2);
+    script.assert(init);
+    let next = system.next_at(&mut script, &path, 2);
+    script.assert(next);
+    assert!(print(&mut script).ends_with(
+        "(assert (= x@2 0))
+(assert (= x@3 (ite b@2 (+ x@2 1) x@2)))
+"
+    ));
+}
+
+#[test]
+fn parameters_and_functions() {
+    let mut system = TransitionSystem::new();
+
+    // x' = (f x) for an uninterpreted f, and x stays below a parameter n
+    let int_sort = system.context().mk_int_sort();
+    let (x, next_x) = system.add_state("x", int_sort);
+    let program = system.context();
+    let n_def = program.declare_const("n", int_sort);
+    let f_def = program.mk_declare_fun("f", vec![int_sort], int_sort);
+    program.commit(f_def);
+    let n = call(program, n_def, vec![]);
+    let f_x = call(program, f_def, vec![x]);
+    let zero = program.mk_int_val(0);
+    let init = program.mk_eq(vec![x, zero]);
+    let next = program.mk_eq(vec![next_x, f_x]);
+    let below = program.mk_int_lt(vec![x, n]);
+    system.add_init(init);
+    system.add_next(next);
+    system.add_property("below", below);
+
+    assert_eq!(
+        print(&mut system.unroll(1)),
+        "(declare-fun n () Int)
+(declare-fun f (Int) Int)
+(declare-fun x@0 () Int)
+(declare-fun x@1 () Int)
+(assert (= x@0 0))
+(assert (= x@1 (f x@0)))
+(assert (or (not (< x@0 n)) (not (< x@1 n))))
+(check-sat)
+"
+    );
+}
+
+#[test]
+#[should_panic(expected = "x' can only be used in the next-state relation")]
+fn primed_property() {
+    let mut system = TransitionSystem::new();
+
+    // a property has no next step at the end of an unrolling
+    let int_sort = system.context().mk_int_sort();
+    let (x, next_x) = system.add_state("x", int_sort);
+    let grows = system.context().mk_int_lt(vec![x, next_x]);
+    system.add_property("grows", grows);
+}